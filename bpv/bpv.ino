@@ -15,17 +15,29 @@ const int chipSelect = 4;
 
 // Define data logging interval (in milliseconds)
 // 60000 = 1 minute N.B. 1 second needed to log a measurement
-// const unsigned long loggingInterval = 1799000; // log every 30 minutes allowing 1 second for recording a measurement
-// const unsigned long loggingInterval = 599000; // log every 10 minutes allowing 1 second for recording a measurement
-const unsigned long loggingInterval = 3599000; // log every 1 hour allowing 1 second for recording a measurement
- // const unsigned long loggingInterval = 59000; // log every 1 minute
+// 1799000 = 30 minutes, 599000 = 10 minutes, 59000 = 1 minute, allowing 1 second for recording a measurement
+// Can be changed from the desktop app with the 'I' command, and is kept in the settings file
+unsigned long loggingInterval = 3599000; // log every 1 hour allowing 1 second for recording a measurement
+
+// How the board waits between measurements, can be changed from the desktop app with the 'Z' command
+// "deep" uses the least power, "light" wakes up faster, "off" keeps the serial port available at all times
+String sleepMode = "deep";
+
+// Define file name for storing the logging interval and the sleep mode
+const char* settingsFileName = "settings";
 
 // Define file name for storing the serial number
 const char* serialNumberFileName = "serial";
 
+// Define file name for the manifest that lets the desktop app recognize the SD card, with a key=value pair per line
+const char* manifestFileName = "manifest";
+
 // Define file name for data logging
 const char* fileName = "data.csv";
 
+// Header row of the data file, written again after it's archived
+const char* dataFileHeader = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage";
+
 // Wi-Fi credentials
 const char* ssid = "your_wifi_ssid";
 const char* password = "your_wifi_password";
@@ -40,9 +52,33 @@ RTCZero rtc;
 // Declare a variable to store the light level
 float lightLevel;
 
+// Framed transfer protocol, must match src-tauri/src/frame.rs
+const uint8_t FRAME_MAGIC_0 = 0xB9;
+const uint8_t FRAME_MAGIC_1 = 0x50;
+const uint8_t PROTOCOL_VERSION = 1;
+const uint8_t FRAME_BEGIN = 0x01;
+const uint8_t FRAME_DATA = 0x02;
+const uint8_t FRAME_END = 0x03;
+const uint8_t FRAME_ERROR = 0x04;
+const int FRAME_PAYLOAD_SIZE = 256;
+
 const int sampleSize = 31;
 RunningMedian samples = RunningMedian(sampleSize);
 
+// Live samples are read from the sensors on demand, so don't let the desktop app ask for them too often
+const long MIN_LIVE_INTERVAL = 500;
+
+// How long the board keeps waiting for commands after the last byte from the computer, so the desktop app only needs to send a keepalive every few seconds
+const unsigned long SYNC_IDLE_TIMEOUT = 5000;
+
+// One reading of all the sensors
+struct Sample {
+  float temperature;
+  float humidity;
+  float lightLevel;
+  float voltage;
+};
+
 void setup() {
 
   Serial.begin(9600);
@@ -93,7 +129,7 @@ void setup() {
     while (1);
   }
   if (dataFile.size() == 0) {
-    dataFile.println("Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage");
+    dataFile.println(dataFileHeader);
   }
   dataFile.close();
 
@@ -104,13 +140,23 @@ void setup() {
     Serial.println("Failed to open file for serialnumber!");
     while (1);
   }
-  for (size_t i = 0; i < UniqueIDsize; i++)	{
- 	  if (UniqueID[i] < 0x10)
- 		  serialNumberDataFile.print("0");
-  	serialNumberDataFile.print(UniqueID[i], HEX);
-  }
+  printSerialNumber(serialNumberDataFile);
   serialNumberDataFile.close();
 
+  SD.remove(manifestFileName);
+  File manifestFile = SD.open(manifestFileName, FILE_WRITE);
+  if (!manifestFile) {
+    Serial.println("Failed to open file for manifest!");
+    while (1);
+  }
+  manifestFile.println("device=bpv");
+  manifestFile.print("serial_number=");
+  printSerialNumber(manifestFile);
+  manifestFile.println();
+  manifestFile.close();
+
+  loadSettings();
+
   // Setting voltage 
   analogReference(AR_DEFAULT); 
   analogReadResolution(12);
@@ -118,6 +164,15 @@ void setup() {
 }
 
 
+// Same hex string as the USB serial number of the board, which identifies it in the desktop app
+void printSerialNumber(Print& out) {
+  for (size_t i = 0; i < UniqueIDsize; i++)	{
+ 	  if (UniqueID[i] < 0x10)
+ 		  out.print("0");
+  	out.print(UniqueID[i], HEX);
+  }
+}
+
 void sendLastMeasurement() {
   Serial.print("BEGIN_L");
   File dataFile = SD.open(fileName, FILE_READ);
@@ -145,74 +200,426 @@ void sendMeasurements() {
   dataFile.close();
 }
 
-// Will not finish until the computer is disconnected
+uint32_t crc32Update(uint32_t crc, const uint8_t* bytes, size_t length) {
+  for (size_t i = 0; i < length; i++) {
+    crc ^= bytes[i];
+    for (int bit = 0; bit < 8; bit++) {
+      crc = (crc >> 1) ^ (0xEDB88320 & (0 - (crc & 1)));
+    }
+  }
+  return crc;
+}
+
+void writeUint32(uint8_t* buf, uint32_t value) {
+  buf[0] = value & 0xFF;
+  buf[1] = (value >> 8) & 0xFF;
+  buf[2] = (value >> 16) & 0xFF;
+  buf[3] = (value >> 24) & 0xFF;
+}
+
+void sendFrame(uint8_t kind, uint16_t sequence, const uint8_t* payload, uint16_t length) {
+  uint8_t header[8] = {
+    FRAME_MAGIC_0, FRAME_MAGIC_1, PROTOCOL_VERSION, kind,
+    (uint8_t)(sequence & 0xFF), (uint8_t)(sequence >> 8),
+    (uint8_t)(length & 0xFF), (uint8_t)(length >> 8)
+  };
+  // The checksum covers everything but the magic bytes
+  uint32_t crc = crc32Update(0xFFFFFFFF, header + 2, 6);
+  crc = crc32Update(crc, payload, length) ^ 0xFFFFFFFF;
+  uint8_t crcBytes[4];
+  writeUint32(crcBytes, crc);
+
+  Serial.write(header, 8);
+  Serial.write(payload, length);
+  Serial.write(crcBytes, 4);
+}
+
+// Sends the data file from the given byte offset until its end
+void sendFramedMeasurements(uint32_t offset) {
+  uint16_t sequence = 0;
+  File dataFile = SD.open(fileName, FILE_READ);
+  if (!dataFile) {
+    const char* reason = "Failed to open data file";
+    sendFrame(FRAME_ERROR, sequence, (const uint8_t*)reason, strlen(reason));
+    return;
+  }
+  dataFile.seek(offset);
+
+  uint8_t sizePayload[4];
+  writeUint32(sizePayload, dataFile.size() - offset);
+  sendFrame(FRAME_BEGIN, sequence++, sizePayload, 4);
+
+  uint32_t dataFrames = 0;
+  uint32_t bytesSent = 0;
+  uint8_t buf[FRAME_PAYLOAD_SIZE];
+  while (dataFile.available()) {
+    int bytes = dataFile.read(buf, FRAME_PAYLOAD_SIZE);
+    if (bytes <= 0) break;
+    sendFrame(FRAME_DATA, sequence++, buf, bytes);
+    dataFrames++;
+    bytesSent += bytes;
+  }
+  dataFile.close();
+
+  uint8_t endPayload[8];
+  writeUint32(endPayload, dataFrames);
+  writeUint32(endPayload + 4, bytesSent);
+  sendFrame(FRAME_END, sequence++, endPayload, 8);
+}
+
+// Returns the byte offset of the first row logged after the given "YYYY-MM-DD,HH:MM:SS" timestamp
+uint32_t findOffsetAfter(String since) {
+  File dataFile = SD.open(fileName, FILE_READ);
+  if (!dataFile) {
+    return 0;
+  }
+  uint32_t offset = dataFile.size();
+  while (dataFile.available()) {
+    uint32_t lineStart = dataFile.position();
+    String line = dataFile.readStringUntil('\n');
+    // Skip the header row
+    if (line.length() == 0 || !isDigit(line.charAt(0))) {
+      continue;
+    }
+    // Rows are appended in chronological order, and the timestamp format sorts lexicographically
+    if (line.substring(0, since.length()) > since) {
+      offset = lineStart;
+      break;
+    }
+  }
+  dataFile.close();
+  return offset;
+}
+
+// Computes the number of measurement rows and the CRC-32 of the whole data file
+bool summarizeDataFile(uint32_t* rows, uint32_t* crc) {
+  File dataFile = SD.open(fileName, FILE_READ);
+  if (!dataFile) {
+    return false;
+  }
+  *rows = 0;
+  *crc = 0xFFFFFFFF;
+  bool lineStart = true;
+  uint8_t buf[FRAME_PAYLOAD_SIZE];
+  while (dataFile.available()) {
+    int bytes = dataFile.read(buf, FRAME_PAYLOAD_SIZE);
+    if (bytes <= 0) break;
+    for (int i = 0; i < bytes; i++) {
+      // The header row doesn't start with a digit
+      if (lineStart && isDigit(buf[i])) {
+        (*rows)++;
+      }
+      lineStart = buf[i] == '\n';
+    }
+    *crc = crc32Update(*crc, buf, bytes);
+  }
+  *crc ^= 0xFFFFFFFF;
+  dataFile.close();
+  return true;
+}
+
+void sendDataFileSummary() {
+  uint32_t rows, crc;
+  if (!summarizeDataFile(&rows, &crc)) {
+    Serial.println("ERROR Failed to open data file");
+    return;
+  }
+  char summary[24];
+  snprintf(summary, sizeof(summary), "%lu,%08lx", (unsigned long)rows, (unsigned long)crc);
+  Serial.println(summary);
+}
+
+// Moves the data file to a "YYMMDDnn.CSV" archive and starts a new one,
+// only if its contents still match the checksum the computer verified
+void archiveDataFile(String expectedChecksum) {
+  uint32_t rows, crc;
+  if (!summarizeDataFile(&rows, &crc)) {
+    Serial.println("ERROR Failed to open data file");
+    return;
+  }
+  if (strtoul(expectedChecksum.c_str(), NULL, 16) != crc) {
+    Serial.println("ERROR Data file changed since it was collected");
+    return;
+  }
+
+  // The SD library only supports 8.3 file names
+  char archiveName[13];
+  int n = 0;
+  do {
+    snprintf(archiveName, sizeof(archiveName), "%02d%02d%02d%02d.CSV",
+             rtc.getYear(), rtc.getMonth(), rtc.getDay(), n++);
+  } while (SD.exists(archiveName) && n < 100);
+  if (SD.exists(archiveName)) {
+    Serial.println("ERROR Too many archives for today");
+    return;
+  }
+
+  // The SD library can't rename files, so copy the data file before removing it
+  File dataFile = SD.open(fileName, FILE_READ);
+  File archiveFile = SD.open(archiveName, FILE_WRITE);
+  if (!dataFile || !archiveFile) {
+    Serial.println("ERROR Failed to create archive file");
+    return;
+  }
+  uint8_t buf[FRAME_PAYLOAD_SIZE];
+  while (dataFile.available()) {
+    int bytes = dataFile.read(buf, FRAME_PAYLOAD_SIZE);
+    if (bytes <= 0) break;
+    archiveFile.write(buf, bytes);
+  }
+  dataFile.close();
+  archiveFile.close();
+
+  SD.remove(fileName);
+  dataFile = SD.open(fileName, FILE_WRITE);
+  if (!dataFile) {
+    Serial.println("ERROR Failed to recreate data file");
+    return;
+  }
+  dataFile.println(dataFileHeader);
+  dataFile.close();
+
+  Serial.println(archiveName);
+}
+
+// Will not finish until the computer stops sending keepalives for SYNC_IDLE_TIMEOUT
 void syncWithComputer() {
   //Serial.begin(9600);
   //int now = rtc.getSeconds();
 
-  while (Serial.available() > 0) {
+  if (Serial.available() == 0) {
+    return;
+  }
+
+  unsigned long lastReceived = millis();
+  while (millis() - lastReceived < SYNC_IDLE_TIMEOUT) {
+    if (Serial.available() == 0) {
+      delay(10);
+      continue;
+    }
+    lastReceived = millis();
+
     char rc = Serial.read();
     while (rc == 'p') {
       rc = Serial.read();
     }
-    if (rc == 'c') {
+    if (rc == 'f') {
+      Serial.print("BEGIN_F");
+      sendFramedMeasurements(0);
+    } else if (rc == 's') {
+      String since = Serial.readStringUntil('\n');
+      since.trim();
+      Serial.print("BEGIN_S");
+      sendFramedMeasurements(findOffsetAfter(since));
+    } else if (rc == 'c') {
       Serial.print("BEGIN_C");
       sendMeasurements();
     } else if (rc == 'l') {
       Serial.print("BEGIN_L");
       sendLastMeasurement();
+    } else if (rc == 't') {
+      Serial.print("BEGIN_T");
+      sendDeviceTime();
+    } else if (rc == 'T') {
+      String time = Serial.readStringUntil('\n');
+      time.trim();
+      setDeviceTime(time);
+      // Send back the time as read from the clock, to confirm it was applied
+      Serial.print("BEGIN_T");
+      sendDeviceTime();
+    } else if (rc == 'i') {
+      Serial.print("BEGIN_I");
+      Serial.println(loggingInterval);
+    } else if (rc == 'I') {
+      String interval = Serial.readStringUntil('\n');
+      interval.trim();
+      long value = interval.toInt();
+      if (value > 0) {
+        loggingInterval = value;
+        saveSettings();
+      }
+      Serial.print("BEGIN_I");
+      Serial.println(loggingInterval);
+    } else if (rc == 'z') {
+      Serial.print("BEGIN_Z");
+      Serial.println(sleepMode);
+    } else if (rc == 'Z') {
+      String mode = Serial.readStringUntil('\n');
+      mode.trim();
+      if (mode == "deep" || mode == "light" || mode == "off") {
+        sleepMode = mode;
+        saveSettings();
+      }
+      Serial.print("BEGIN_Z");
+      Serial.println(sleepMode);
+    } else if (rc == 'n') {
+      // Lets the desktop app identify boards whose USB adapter doesn't report the serial number
+      Serial.print("BEGIN_N");
+      printSerialNumber(Serial);
+      Serial.println();
+    } else if (rc == 'h') {
+      Serial.print("BEGIN_H");
+      sendDataFileSummary();
+    } else if (rc == 'a') {
+      String checksum = Serial.readStringUntil('\n');
+      checksum.trim();
+      Serial.print("BEGIN_A");
+      archiveDataFile(checksum);
+    } else if (rc == 'v') {
+      String interval = Serial.readStringUntil('\n');
+      interval.trim();
+      streamLiveSamples(interval.toInt());
     }
 
-    delay(100);
+    // Commands can take a while, the idle time starts after them
+    lastReceived = millis();
   }
 }
 
-void loop() {
-  syncWithComputer();
+// Sends a sample line prefixed with 'V' every interval, without logging it, until the computer sends 'x' or disconnects
+void streamLiveSamples(long interval) {
+  if (interval < MIN_LIVE_INTERVAL) {
+    interval = MIN_LIVE_INTERVAL;
+  }
+  Serial.print("BEGIN_V");
+  Serial.println(interval);
+
+  while (Serial) {
+    Sample sample = readSample();
+    Serial.print("V");
+    printSample(Serial, sample);
+
+    unsigned long start = millis();
+    while (millis() - start < (unsigned long) interval) {
+      if (Serial.available() > 0 && Serial.read() == 'x') {
+        Serial.println("END_V");
+        return;
+      }
+      delay(10);
+    }
+  }
+}
+
+Sample readSample() {
+  Sample sample;
 
   // Read sensor data
-  float temperature = ENV.readTemperature();
-  float humidity = ENV.readHumidity();
-  float lightLevel = ENV.readIlluminance(); 
+  sample.temperature = ENV.readTemperature();
+  sample.humidity = ENV.readHumidity();
+  sample.lightLevel = ENV.readIlluminance();
 
   // Read voltage
   for (int i = 0; i < sampleSize; i++) {
     int a = analogRead(A0);
     samples.add(a);
   }
-  float voltage = samples.getMedian() * 3.3 / 4095;
+  sample.voltage = samples.getMedian() * 3.3 / 4095;
   samples.clear();
   //Serial.print("Voltage= ");
-  //Serial.println(voltage, 3);
+  //Serial.println(sample.voltage, 3);
+
+  return sample;
+}
+
+void loop() {
+  syncWithComputer();
+
+  Sample sample = readSample();
 
   // Log data to SD card
-  logData(temperature, humidity, lightLevel, voltage);
+  logData(sample);
 
   // Enter sleep mode
-  LowPower.deepSleep(loggingInterval); 
+  if (sleepMode == "light") {
+    LowPower.sleep(loggingInterval);
+  } else if (sleepMode == "off") {
+    // Keep answering the desktop app while waiting for the next measurement
+    unsigned long start = millis();
+    while (millis() - start < loggingInterval) {
+      syncWithComputer();
+      delay(100);
+    }
+  } else {
+    LowPower.deepSleep(loggingInterval);
+  }
+}
+
+// Same format as the date and time columns of the data file
+void formatTimestamp(char* timestamp, size_t size) {
+  // The RTC only keeps the last two digits of the year, which existing data files have padded to four digits
+  snprintf(timestamp, size, "%04d-%02d-%02d,%02d:%02d:%02d",
+           rtc.getYear(), rtc.getMonth(), rtc.getDay(),
+           rtc.getHours(), rtc.getMinutes(), rtc.getSeconds());
+}
+
+void sendDeviceTime() {
+  char timestamp[20];
+  formatTimestamp(timestamp, sizeof(timestamp));
+  Serial.println(timestamp);
+}
+
+// Sets the clock from a "YYYY-MM-DD,HH:MM:SS" string
+void setDeviceTime(String time) {
+  int year, month, day, hours, minutes, seconds;
+  if (sscanf(time.c_str(), "%d-%d-%d,%d:%d:%d", &year, &month, &day, &hours, &minutes, &seconds) != 6) {
+    return;
+  }
+  rtc.setDate(day, month, year % 100);
+  rtc.setTime(hours, minutes, seconds);
+}
+
+void loadSettings() {
+  File settingsFile = SD.open(settingsFileName, FILE_READ);
+  if (!settingsFile) {
+    return;
+  }
+  long interval = settingsFile.readStringUntil('\n').toInt();
+  if (interval > 0) {
+    loggingInterval = interval;
+  }
+  String mode = settingsFile.readStringUntil('\n');
+  mode.trim();
+  if (mode == "deep" || mode == "light" || mode == "off") {
+    sleepMode = mode;
+  }
+  settingsFile.close();
 }
 
-void logData(float temperature, float humidity, float lightLevel, float voltage) {
+void saveSettings() {
+  SD.remove(settingsFileName);
+  File settingsFile = SD.open(settingsFileName, FILE_WRITE);
+  if (!settingsFile) {
+    return;
+  }
+  settingsFile.println(loggingInterval);
+  settingsFile.println(sleepMode);
+  settingsFile.close();
+}
+
+void logData(Sample sample) {
   File dataFile = SD.open(fileName, FILE_WRITE);
   if (dataFile) {
-    char timestamp[20];
-    snprintf(timestamp, sizeof(timestamp), "%04d-%02d-%02d,%02d:%02d:%02d",
-             rtc.getYear(), rtc.getMonth(), rtc.getDay(),
-             rtc.getHours(), rtc.getMinutes(), rtc.getSeconds());
-    dataFile.print(timestamp);
-    dataFile.print(",");
-    dataFile.print(temperature);
-    dataFile.print(",");
-    dataFile.print(humidity);
-    dataFile.print(",");
-    dataFile.print(lightLevel);
-    dataFile.print(",");
-
-    // Format voltage with three digits past the decimal point
-    String voltageStr = String(voltage, 3); // 3 digits past the decimal point
-    dataFile.println(voltageStr);
+    printSample(dataFile, sample);
     dataFile.close();
   } else {
     // Serial.println("Error opening file");
   }
 }
+
+// Prints the sample as a row of the data file, timestamped with the current time
+void printSample(Print& out, Sample sample) {
+  char timestamp[20];
+  formatTimestamp(timestamp, sizeof(timestamp));
+  out.print(timestamp);
+  out.print(",");
+  out.print(sample.temperature);
+  out.print(",");
+  out.print(sample.humidity);
+  out.print(",");
+  out.print(sample.lightLevel);
+  out.print(",");
+
+  // Format voltage with three digits past the decimal point
+  String voltageStr = String(sample.voltage, 3); // 3 digits past the decimal point
+  out.println(voltageStr);
+}
@@ -1,16 +1,8 @@
 use hdk::prelude::*;
 use living_power_integrity::*;
 
-#[derive(Serialize, Deserialize, Debug, SerializedBytes)]
-pub struct BpvDeviceInfo {
-    name: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct SetBpvDeviceInfoInput {
-    pub arduino_serial_number: String,
-    pub info: BpvDeviceInfo,
-}
+use crate::revisions::{get_all_revisions, get_latest_revision};
+use crate::roles::{claim_role, grant_role, GrantRoleInput};
 
 pub fn all_bpv_devices_path() -> Path {
     Path::from(format!("all_bpv_devices"))
@@ -26,24 +18,46 @@ pub fn bpv_device_hash(arduino_serial_number: String) -> ExternResult<EntryHash>
     path.path_entry_hash()
 }
 
+/// Saves the info of the device, as a new revision of its latest info if it already has one
+///
+/// The agent that registers a device becomes its owner
 #[hdk_extern]
-pub fn set_bpv_device_info(input: SetBpvDeviceInfoInput) -> ExternResult<()> {
-    let path = bpv_device_path(input.arduino_serial_number)?;
+pub fn set_bpv_device_info(bpv_device_info: BpvDeviceInfo) -> ExternResult<ActionHash> {
+    let path = bpv_device_path(bpv_device_info.arduino_serial_number.clone())?;
     path.ensure()?;
 
-    let bytes = SerializedBytes::try_from(input.info)
-        .map_err(|err| wasm_error!(WasmErrorInner::Guest(format!("{err:?}"))))?;
+    if let Some(previous_record) =
+        get_latest_bpv_device_info_for_bpv_device(bpv_device_info.arduino_serial_number.clone())?
+    {
+        return update_entry(
+            previous_record.action_address().clone(),
+            &EntryTypes::BpvDeviceInfo(bpv_device_info),
+        );
+    }
 
+    let arduino_serial_number = bpv_device_info.arduino_serial_number.clone();
+    let action_hash = create_entry(&EntryTypes::BpvDeviceInfo(bpv_device_info))?;
     create_link(
         path.path_entry_hash()?,
-        path.path_entry_hash()?,
+        action_hash.clone(),
         LinkTypes::BpvDeviceToBpvDeviceInfo,
-        bytes.bytes().to_vec(),
+        (),
     )?;
 
-    Ok(())
+    let role_grant_hash = grant_role(GrantRoleInput {
+        agent: agent_info()?.agent_latest_pubkey,
+        role: Role::DeviceOwner {
+            arduino_serial_number,
+        },
+    })?;
+    claim_role(role_grant_hash)?;
+
+    Ok(action_hash)
 }
 
+/// The links to the first revision of the infos of the device
+///
+/// Devices have only one, unless different agents registered them at the same time
 #[hdk_extern]
 pub fn get_bpv_device_info(arduino_serial_number: String) -> ExternResult<Vec<Link>> {
     let path = bpv_device_path(arduino_serial_number)?;
@@ -56,3 +70,66 @@ pub fn get_bpv_device_info(arduino_serial_number: String) -> ExternResult<Vec<Li
         .build(),
     )
 }
+
+#[hdk_extern]
+pub fn get_latest_bpv_device_info(
+    original_bpv_device_info_hash: ActionHash,
+) -> ExternResult<Option<Record>> {
+    get_latest_revision(original_bpv_device_info_hash)
+}
+
+#[hdk_extern]
+pub fn get_all_revisions_for_bpv_device_info(
+    original_bpv_device_info_hash: ActionHash,
+) -> ExternResult<Vec<Record>> {
+    get_all_revisions(original_bpv_device_info_hash)
+}
+
+/// The latest revision of the most recently linked info of the device
+#[hdk_extern]
+pub fn get_latest_bpv_device_info_for_bpv_device(
+    arduino_serial_number: String,
+) -> ExternResult<Option<Record>> {
+    let links = get_bpv_device_info(arduino_serial_number)?;
+
+    let Some(latest_link) = links.into_iter().max_by_key(|link| link.timestamp) else {
+        return Ok(None);
+    };
+    let Some(action_hash) = latest_link.target.into_action_hash() else {
+        return Ok(None);
+    };
+
+    get_latest_revision(action_hash)
+}
+
+/// Commits the photo so that it can be referenced from the `photo_hash` of a device info
+#[hdk_extern]
+pub fn create_bpv_device_photo(bpv_device_photo: BpvDevicePhoto) -> ExternResult<EntryHash> {
+    let entry_hash = hash_entry(&bpv_device_photo)?;
+    create_entry(&EntryTypes::BpvDevicePhoto(bpv_device_photo))?;
+    Ok(entry_hash)
+}
+
+#[hdk_extern]
+pub fn get_bpv_device_photo(photo_hash: EntryHash) -> ExternResult<Option<Record>> {
+    get(photo_hash, GetOptions::default())
+}
+
+#[hdk_extern]
+pub fn get_bpv_device_timezone(
+    arduino_serial_number: String,
+) -> ExternResult<Option<DeviceTimezone>> {
+    let Some(record) = get_latest_bpv_device_info_for_bpv_device(arduino_serial_number)? else {
+        return Ok(None);
+    };
+
+    let Some(info) = record
+        .entry()
+        .to_app_option::<BpvDeviceInfo>()
+        .map_err(|e| wasm_error!(e))?
+    else {
+        return Ok(None);
+    };
+
+    Ok(info.timezone)
+}
@@ -0,0 +1,47 @@
+use hdk::prelude::*;
+use living_power_integrity::*;
+
+use crate::bpv_device::bpv_device_hash;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddClockOffsetInput {
+    pub arduino_serial_number: String,
+    pub clock_offset: ClockOffset,
+}
+
+#[hdk_extern]
+pub fn add_clock_offset(input: AddClockOffsetInput) -> ExternResult<()> {
+    let base = bpv_device_hash(input.arduino_serial_number)?;
+    let tag = SerializedBytes::try_from(input.clock_offset)
+        .map_err(|err| wasm_error!(WasmErrorInner::Guest(format!("{err:?}"))))?;
+    create_link(
+        base.clone(),
+        base,
+        LinkTypes::BpvDeviceToClockOffsets,
+        tag.bytes().to_vec(),
+    )?;
+
+    Ok(())
+}
+
+/// All the clock offsets recorded for the given device, sorted by the time they were read
+#[hdk_extern]
+pub fn get_clock_offsets(arduino_serial_number: String) -> ExternResult<Vec<ClockOffset>> {
+    let base = bpv_device_hash(arduino_serial_number)?;
+    let links = get_links(
+        GetLinksInputBuilder::try_new(base, LinkTypes::BpvDeviceToClockOffsets)?.build(),
+    )?;
+
+    let mut clock_offsets = links
+        .into_iter()
+        .map(|link| {
+            ClockOffset::try_from(SerializedBytes::from(UnsafeBytes::from(
+                link.tag.into_inner(),
+            )))
+            .map_err(|err| wasm_error!(WasmErrorInner::Guest(format!("{err:?}"))))
+        })
+        .collect::<ExternResult<Vec<ClockOffset>>>()?;
+    clock_offsets.sort_by_key(|clock_offset| clock_offset.host_time);
+
+    Ok(clock_offsets)
+}
@@ -1,41 +1,42 @@
 use hdk::prelude::*;
-use living_power_integrity::LinkTypes;
+use living_power_integrity::*;
 
 use crate::bpv_device::bpv_device_hash;
+use crate::revisions::{get_all_revisions, get_latest_revision, get_latest_revisions};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SetExternalResistorValueInput {
-    pub arduino_serial_number: String,
-    pub previous_create_link_action_hash: Option<ActionHash>,
+    /// The latest revision of the value to correct, `None` to add a new one
+    pub previous_external_resistor_value_hash: Option<ActionHash>,
     pub external_resistor_value: ExternalResistorValue,
 }
 
-#[derive(Serialize, PartialEq, Eq, Deserialize, Debug, SerializedBytes)]
-pub struct ExternalResistorValue {
-    pub external_resistor_value_ohms: u64,
-    pub from: Timestamp,
-    pub to: Timestamp,
-}
-
 #[hdk_extern]
-pub fn set_external_resistor_value(input: SetExternalResistorValueInput) -> ExternResult<()> {
-    if let Some(action_hash) = input.previous_create_link_action_hash {
-        delete_link(action_hash)?;
+pub fn set_external_resistor_value(
+    input: SetExternalResistorValueInput,
+) -> ExternResult<ActionHash> {
+    if let Some(previous_action_hash) = input.previous_external_resistor_value_hash {
+        return update_entry(
+            previous_action_hash,
+            &EntryTypes::ExternalResistorValue(input.external_resistor_value),
+        );
     }
 
-    let base = bpv_device_hash(input.arduino_serial_number)?;
-    let tag = SerializedBytes::try_from(input.external_resistor_value)
-        .map_err(|err| wasm_error!(WasmErrorInner::Guest(format!("{err:?}"))))?;
+    let base = bpv_device_hash(input.external_resistor_value.arduino_serial_number.clone())?;
+    let action_hash = create_entry(&EntryTypes::ExternalResistorValue(
+        input.external_resistor_value,
+    ))?;
     create_link(
-        base.clone(),
         base,
+        action_hash.clone(),
         LinkTypes::BpvDeviceToExternalResistorValues,
-        tag.bytes().to_vec(),
+        (),
     )?;
 
-    Ok(())
+    Ok(action_hash)
 }
 
+/// The links to the first revision of each of the external resistor values of the device
 #[hdk_extern]
 pub fn get_all_external_resistor_values(arduino_serial_number: String) -> ExternResult<Vec<Link>> {
     let base = bpv_device_hash(arduino_serial_number)?;
@@ -43,3 +44,25 @@ pub fn get_all_external_resistor_values(arduino_serial_number: String) -> Extern
         GetLinksInputBuilder::try_new(base, LinkTypes::BpvDeviceToExternalResistorValues)?.build(),
     )
 }
+
+#[hdk_extern]
+pub fn get_latest_external_resistor_value(
+    original_external_resistor_value_hash: ActionHash,
+) -> ExternResult<Option<Record>> {
+    get_latest_revision(original_external_resistor_value_hash)
+}
+
+#[hdk_extern]
+pub fn get_all_revisions_for_external_resistor_value(
+    original_external_resistor_value_hash: ActionHash,
+) -> ExternResult<Vec<Record>> {
+    get_all_revisions(original_external_resistor_value_hash)
+}
+
+#[hdk_extern]
+pub fn get_latest_external_resistor_values_for_bpv_device(
+    arduino_serial_number: String,
+) -> ExternResult<Vec<Record>> {
+    let base = bpv_device_hash(arduino_serial_number)?;
+    get_latest_revisions(base, LinkTypes::BpvDeviceToExternalResistorValues)
+}
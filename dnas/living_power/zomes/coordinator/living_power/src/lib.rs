@@ -1,5 +1,5 @@
 use external_resistors::{
-    get_all_external_resistor_values, set_external_resistor_value, ExternalResistorValue,
+    get_latest_external_resistor_values_for_bpv_device, set_external_resistor_value,
     SetExternalResistorValueInput,
 };
 use hdk::prelude::*;
@@ -9,9 +9,12 @@ use living_power_integrity::*;
 
 pub mod all_bpv_devices;
 pub mod bpv_device;
+pub mod clock_offsets;
 pub mod external_resistors;
 pub mod measurement_collection;
-use bpv_device::{set_bpv_device_info, BpvDeviceInfo, SetBpvDeviceInfoInput};
+pub mod revisions;
+pub mod roles;
+use bpv_device::{get_latest_bpv_device_info_for_bpv_device, set_bpv_device_info};
 use measurement_collection::create_measurement_collections;
 
 #[hdk_extern]
@@ -72,8 +75,89 @@ where
     Ok(r)
 }
 
+/// How the device info was stored in the link tags before it was an entry
+///
+/// Only some versions added the `arduino_serial_number` of the device to the tag, so it's not read:
+/// the device is the one the old cell's links were listed for
+#[derive(Serialize, Deserialize, Debug, SerializedBytes)]
+struct LinkTagBpvDeviceInfo {
+    name: String,
+    #[serde(default)]
+    timezone: Option<DeviceTimezone>,
+}
+
+/// How the external resistor values were stored in the link tags before they were entries
+///
+/// Like `LinkTagBpvDeviceInfo`, tags with and without the `arduino_serial_number` of the device are accepted
+#[derive(Serialize, Deserialize, Debug, SerializedBytes)]
+struct LinkTagExternalResistorValue {
+    external_resistor_value_ohms: u64,
+    from: Timestamp,
+    to: Timestamp,
+}
+
+/// Reads the entry an old cell links to: from the link tag if the old cell stored it there,
+/// or else from the latest revision of the link target, with the given zome function of the old cell
+fn get_linked_entry_from_old_cell<T, E>(
+    old_cell: &CellId,
+    link: Link,
+    get_latest_fn_name: &str,
+    from_link_tag: impl FnOnce(T) -> E,
+) -> ExternResult<Option<E>>
+where
+    T: TryFrom<SerializedBytes, Error = SerializedBytesError>,
+    E: TryFrom<SerializedBytes, Error = SerializedBytesError>,
+{
+    if !link.tag.0.is_empty() {
+        let bytes = SerializedBytes::from(UnsafeBytes::from(link.tag.into_inner()));
+        let value = T::try_from(bytes).map_err(|err| {
+            wasm_error!(WasmErrorInner::Guest(format!(
+                "Error decoding link tag from old cell: {err:?}"
+            )))
+        })?;
+        return Ok(Some(from_link_tag(value)));
+    }
+
+    let Some(action_hash) = link.target.into_action_hash() else {
+        return Ok(None);
+    };
+    let record: Option<Record> = call_old_cell(old_cell.clone(), get_latest_fn_name, action_hash)?;
+    let Some(record) = record else {
+        return Ok(None);
+    };
+    record
+        .entry()
+        .to_app_option::<E>()
+        .map_err(|e| wasm_error!(e))
+}
+
+/// The start of the year 100, in microseconds since the UNIX epoch
+const YEAR_100_MICROS: i64 = -683_003 * 24 * 60 * 60 * 1_000_000;
+
+/// 2000 years are exactly 5 cycles of 400 years of the gregorian calendar, so adding them keeps the date and time
+const TWO_THOUSAND_YEARS_MICROS: i64 = 730_485 * 24 * 60 * 60 * 1_000_000;
+
+/// The real time clock of the BPV devices only keeps the last two digits of the year, which earlier versions
+/// of the app read from the data files as years of the first century, like 0024 for 2024
+fn from_rtc_year(timestamp: Timestamp) -> Timestamp {
+    match timestamp.as_micros() < YEAR_100_MICROS {
+        true => Timestamp::from_micros(timestamp.as_micros() + TWO_THOUSAND_YEARS_MICROS),
+        false => timestamp,
+    }
+}
+
+/// What the migration left behind because it doesn't pass the current validation rules
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct MigrationReport {
+    pub skipped_measurement_collections: usize,
+    pub skipped_measurements: usize,
+    pub skipped_external_resistor_values: usize,
+}
+
 #[hdk_extern]
-pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<()> {
+pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<MigrationReport> {
+    let mut report = MigrationReport::default();
+
     // Get all the BPVs
     let links: Vec<Link> = call_old_cell(old_cell.clone(), "get_all_bpv_devices", ())?;
 
@@ -92,26 +176,42 @@ pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<()> {
     // - Get the BPV info, and commit it
     // - Get all measurements, and commit them
     for arduino_serial_number in arduino_serial_numbers {
-        let mut links: Vec<Link> = call_old_cell(
+        let links: Vec<Link> = call_old_cell(
             old_cell.clone(),
             "get_bpv_device_info",
             arduino_serial_number.clone(),
         )?;
 
-        links.sort_by(|link_a, link_b| link_b.timestamp.cmp(&link_a.timestamp));
-
-        if let Some(link) = links.first() {
-            let bytes = SerializedBytes::from(UnsafeBytes::from(link.tag.clone().into_inner()));
-            let info = BpvDeviceInfo::try_from(bytes).map_err(|err| {
-                wasm_error!(WasmErrorInner::Guest(format!(
-                    "Error decoding BpvDeviceInfo from link tag {err:?}"
-                )))
-            })?;
+        if let Some(link) = links.into_iter().max_by_key(|link| link.timestamp) {
+            let info = get_linked_entry_from_old_cell(
+                &old_cell,
+                link,
+                "get_latest_bpv_device_info",
+                |tag: LinkTagBpvDeviceInfo| BpvDeviceInfo {
+                    arduino_serial_number: arduino_serial_number.clone(),
+                    name: tag.name,
+                    timezone: tag.timezone,
+                    location: None,
+                    site_description: None,
+                    species: None,
+                    electrode_material: None,
+                    installation_date: None,
+                    notes: None,
+                    photo_hash: None,
+                },
+            )?;
+            let current_info =
+                get_latest_bpv_device_info_for_bpv_device(arduino_serial_number.clone())?
+                    .map(|record| record.entry().to_app_option::<BpvDeviceInfo>())
+                    .transpose()
+                    .map_err(|e| wasm_error!(e))?
+                    .flatten();
 
-            set_bpv_device_info(SetBpvDeviceInfoInput {
-                info,
-                arduino_serial_number: arduino_serial_number.clone(),
-            })?;
+            if let Some(info) = info {
+                if current_info.as_ref() != Some(&info) {
+                    set_bpv_device_info(info)?;
+                }
+            }
         }
         let links: Vec<Link> = call_old_cell(
             old_cell.clone(),
@@ -127,7 +227,7 @@ pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<()> {
             let record: Record = call_old_cell(
                 old_cell.clone(),
                 "get_measurement_collection",
-                measurement_collection_hash,
+                measurement_collection_hash.clone(),
             )?;
 
             let Some(entry_hash) = record.signed_action().action().entry_hash() else {
@@ -142,7 +242,44 @@ pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<()> {
                 continue;
             };
 
-            let measurement_collection = MeasurementCollection::try_from(entry)?;
+            let mut measurement_collection = match MeasurementCollection::try_from(entry) {
+                Ok(measurement_collection) => measurement_collection,
+                Err(err) => {
+                    warn!("Skipping measurement collection {measurement_collection_hash} that can't be decoded: {err:?}");
+                    report.skipped_measurement_collections += 1;
+                    continue;
+                }
+            };
+
+            for measurement in &mut measurement_collection.measurements {
+                measurement.timestamp = from_rtc_year(measurement.timestamp);
+                measurement.raw_timestamp = measurement.raw_timestamp.map(from_rtc_year);
+            }
+
+            // Committing a single invalid measurement would make the whole migration fail
+            let now = sys_time()?;
+            let total_measurements = measurement_collection.measurements.len();
+            measurement_collection
+                .measurements
+                .retain(|measurement| match validate_measurement(measurement, now) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        warn!("Skipping measurement of collection {measurement_collection_hash}: {err}");
+                        false
+                    }
+                });
+            report.skipped_measurements +=
+                total_measurements - measurement_collection.measurements.len();
+
+            if measurement_collection.measurements.is_empty()
+                || measurement_collection
+                    .arduino_serial_number
+                    .trim()
+                    .is_empty()
+            {
+                report.skipped_measurement_collections += 1;
+                continue;
+            }
 
             create_measurement_collections(measurement_collection)?;
         }
@@ -151,41 +288,78 @@ pub fn migrate_from_old_cell(old_cell: CellId) -> ExternResult<()> {
             "get_all_external_resistor_values",
             arduino_serial_number.clone(),
         )?;
-        let new_links: Vec<Link> = get_all_external_resistor_values(arduino_serial_number.clone())?;
-
-        let link_to_external_resistor_value =
-            |link: Link| -> Result<ExternalResistorValue, SerializedBytesError> {
-                let tag = link.tag;
-                let bytes = SerializedBytes::from(UnsafeBytes::from(tag.into_inner()));
-                let value = ExternalResistorValue::try_from(bytes)?;
-                Ok(value)
-            };
+        let new_records =
+            get_latest_external_resistor_values_for_bpv_device(arduino_serial_number.clone())?;
 
-        let old_values: Vec<ExternalResistorValue> = old_links
-            .into_iter()
-            .filter_map(|link| link_to_external_resistor_value(link).ok())
-            .collect();
+        let mut old_values: Vec<ExternalResistorValue> = vec![];
+        for link in old_links {
+            match get_linked_entry_from_old_cell(
+                &old_cell,
+                link,
+                "get_latest_external_resistor_value",
+                |tag: LinkTagExternalResistorValue| ExternalResistorValue {
+                    arduino_serial_number: arduino_serial_number.clone(),
+                    external_resistor_value_ohms: tag.external_resistor_value_ohms,
+                    from: tag.from,
+                    to: tag.to,
+                },
+            ) {
+                Ok(Some(value)) => old_values.push(value),
+                Ok(None) => {
+                    warn!("Skipping an external resistor value of {arduino_serial_number} that was not found in the old cell");
+                    report.skipped_external_resistor_values += 1;
+                }
+                Err(err) => {
+                    warn!(
+                        "Skipping an external resistor value of {arduino_serial_number}: {err:?}"
+                    );
+                    report.skipped_external_resistor_values += 1;
+                }
+            }
+        }
 
-        let new_values: Vec<ExternalResistorValue> = new_links
+        let new_values: Vec<ExternalResistorValue> = new_records
             .into_iter()
-            .filter_map(|link| link_to_external_resistor_value(link).ok())
-            .collect();
-
-        let old_unexistant_values: Vec<ExternalResistorValue> = old_values
+            .map(|record| {
+                record
+                    .entry()
+                    .to_app_option::<ExternalResistorValue>()
+                    .map_err(|e| wasm_error!(e))
+            })
+            .collect::<ExternResult<Vec<Option<ExternalResistorValue>>>>()?
             .into_iter()
-            .filter(|value| new_values.iter().find(|v| v.eq(&value)).is_none())
+            .flatten()
             .collect();
 
-        for external_resistor_value in old_unexistant_values {
+        for external_resistor_value in old_values {
+            if new_values
+                .iter()
+                .any(|value| value.same_value(&external_resistor_value))
+            {
+                continue;
+            }
+            // Entered before their periods were validated, they would be rejected
+            if external_resistor_value.from >= external_resistor_value.to {
+                warn!("Skipping an external resistor value of {arduino_serial_number} whose period doesn't start before it ends: {external_resistor_value:?}");
+                report.skipped_external_resistor_values += 1;
+                continue;
+            }
+
             set_external_resistor_value(SetExternalResistorValueInput {
-                arduino_serial_number: arduino_serial_number.clone(),
-                previous_create_link_action_hash: None,
+                previous_external_resistor_value_hash: None,
                 external_resistor_value,
             })?;
         }
     }
 
-    Ok(())
+    if report.skipped_measurement_collections > 0
+        || report.skipped_measurements > 0
+        || report.skipped_external_resistor_values > 0
+    {
+        warn!("Migration from the old cell skipped invalid data: {report:?}");
+    }
+
+    Ok(report)
 }
 
 #[hdk_extern(infallible)]
@@ -1,24 +1,83 @@
+use std::collections::BTreeSet;
+
 use hdk::prelude::*;
 use living_power_integrity::*;
 
 use crate::bpv_device::bpv_device_hash;
 
-const BITS_PER_MEASUREMENT: usize = 39 + 64 + 32 + 32 + 32 + 32 + 32; // ActionHash + i64 + u32 + u32 + u32 + u32 + u32
+const BITS_PER_MEASUREMENT: usize = 39 + 64 + 32 + 32 + 32 + 32 + 32 + 72; // ActionHash + i64 + i32 + i32 + i32 + i32 + i32 + Option<i64>
 
+/// Commits the measurements in as many collections as needed to stay under the entry size limit,
+/// skipping the ones already committed for the device
 #[hdk_extern]
 pub fn create_measurement_collections(
     measurement_collection: MeasurementCollection,
 ) -> ExternResult<Vec<ActionHash>> {
-    let mut hashes: Vec<ActionHash> = Vec::new();
+    Ok(create_new_measurement_collections(measurement_collection)?.action_hashes)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatedMeasurementCollections {
+    pub action_hashes: Vec<ActionHash>,
+    /// Measurements that were not committed because the device already had one at their timestamp
+    pub skipped_measurements: usize,
+    /// Measurements that were not committed because the integrity zome would reject them,
+    /// like the ones from before 2000 or in the future
+    pub rejected_measurements: usize,
+}
+
+/// Drops the measurements whose timestamp as read from the device is already covered by a committed
+/// measurement of the device, or by an earlier one in the same collection, and commits the rest sorted by timestamp
+///
+/// Live measurements are only compared with other live ones, as the device doesn't log them in its data file.
+/// Committing the same measurements twice has no effect
+///
+/// Invalid measurements are dropped first, as committing a single one would make the whole call fail
+#[hdk_extern]
+pub fn create_new_measurement_collections(
+    measurement_collection: MeasurementCollection,
+) -> ExternResult<CreatedMeasurementCollections> {
+    let mut covered_timestamps = get_committed_timestamps(
+        measurement_collection.arduino_serial_number.clone(),
+        measurement_collection.live,
+    )?;
 
-    let chunks = measurement_collection
+    let now = sys_time()?;
+    let total_measurements = measurement_collection.measurements.len();
+    let valid_measurements: Vec<Measurement> = measurement_collection
         .measurements
-        .chunks(ENTRY_SIZE_LIMIT * 8 / BITS_PER_MEASUREMENT - 10);
+        .into_iter()
+        .filter(|measurement| match validate_measurement(measurement, now) {
+            Ok(()) => true,
+            Err(err) => {
+                warn!(
+                    "Skipping invalid measurement of {}: {err}",
+                    measurement_collection.arduino_serial_number
+                );
+                false
+            }
+        })
+        .collect();
+    let rejected_measurements = total_measurements - valid_measurements.len();
+
+    let mut new_measurements: Vec<Measurement> = valid_measurements
+        .into_iter()
+        .filter(|measurement| covered_timestamps.insert(device_timestamp(measurement)))
+        .collect();
+    // The integrity zome only accepts collections sorted by timestamp without duplicates
+    new_measurements.sort_by_key(|measurement| measurement.timestamp);
+    new_measurements.dedup_by_key(|measurement| measurement.timestamp);
+    let skipped_measurements = total_measurements - rejected_measurements - new_measurements.len();
+
+    let mut hashes: Vec<ActionHash> = Vec::new();
+
+    let chunks = new_measurements.chunks(ENTRY_SIZE_LIMIT * 8 / BITS_PER_MEASUREMENT - 10);
 
     for chunk in chunks {
         let measurement_collection = MeasurementCollection {
             arduino_serial_number: measurement_collection.arduino_serial_number.clone(),
             measurements: chunk.to_vec(),
+            live: measurement_collection.live,
         };
         let measurement_collection_hash = create_entry(&EntryTypes::MeasurementCollection(
             measurement_collection.clone(),
@@ -33,7 +92,11 @@ pub fn create_measurement_collections(
         hashes.push(measurement_collection_hash);
     }
 
-    Ok(hashes)
+    Ok(CreatedMeasurementCollections {
+        action_hashes: hashes,
+        skipped_measurements,
+        rejected_measurements,
+    })
 }
 
 #[hdk_extern]
@@ -160,3 +223,103 @@ pub fn get_deleted_measurement_collections_for_bpv_device(
         .filter(|(_link, deletes)| !deletes.is_empty())
         .collect())
 }
+
+/// The timestamp as read from the device of its last committed measurement
+///
+/// Incremental collections compare it with the timestamps in the data file of the device,
+/// so it must not include the clock drift correction, nor the live measurements that are not in that file
+#[hdk_extern]
+pub fn get_last_measurement_timestamp(
+    arduino_serial_number: String,
+) -> ExternResult<Option<Timestamp>> {
+    Ok(get_committed_timestamps(arduino_serial_number, false)?
+        .last()
+        .cloned())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CountCommittedMeasurementsInput {
+    pub arduino_serial_number: String,
+    /// Measurements as read from the device, before any clock drift correction
+    pub measurements: Vec<Measurement>,
+}
+
+/// Counts how many of the given measurements were committed from the data file of the device with the same values
+///
+/// A row that shares its timestamp with a committed one but has other values is not counted, as it was never committed
+#[hdk_extern]
+pub fn count_committed_measurements(input: CountCommittedMeasurementsInput) -> ExternResult<usize> {
+    let committed: BTreeSet<MeasurementIdentity> =
+        get_committed_measurements(input.arduino_serial_number, false)?
+            .iter()
+            .map(measurement_identity)
+            .collect();
+
+    Ok(input
+        .measurements
+        .iter()
+        .filter(|measurement| committed.contains(&measurement_identity(measurement)))
+        .count())
+}
+
+/// The device timestamp and the values of a measurement, which identify the row of the data file it was read from
+type MeasurementIdentity = (Timestamp, i32, i32, i32, i32);
+
+fn measurement_identity(measurement: &Measurement) -> MeasurementIdentity {
+    (
+        device_timestamp(measurement),
+        measurement.humidity_percentage,
+        measurement.temperature_celsius,
+        measurement.light_level_lux,
+        measurement.voltage_millivolts,
+    )
+}
+
+/// The timestamps as read from the device of the committed measurements of the device,
+/// either the live ones or the ones read from its data file
+fn get_committed_timestamps(
+    arduino_serial_number: String,
+    live: bool,
+) -> ExternResult<BTreeSet<Timestamp>> {
+    Ok(get_committed_measurements(arduino_serial_number, live)?
+        .iter()
+        .map(device_timestamp)
+        .collect())
+}
+
+/// The timestamp before any clock drift correction, which identifies the row of the data file
+fn device_timestamp(measurement: &Measurement) -> Timestamp {
+    measurement.raw_timestamp.unwrap_or(measurement.timestamp)
+}
+
+/// All the measurements in the measurement collections of the device that are still linked from it,
+/// either the live ones or the ones read from its data file
+fn get_committed_measurements(
+    arduino_serial_number: String,
+    live: bool,
+) -> ExternResult<Vec<Measurement>> {
+    let links = get_measurement_collections_for_bpv_device(arduino_serial_number)?;
+
+    let mut measurements: Vec<Measurement> = vec![];
+    for link in links {
+        let Some(action_hash) = link.target.into_action_hash() else {
+            continue;
+        };
+        let Some(record) = get(action_hash, GetOptions::default())? else {
+            continue;
+        };
+        let Some(measurement_collection) = record
+            .entry()
+            .to_app_option::<MeasurementCollection>()
+            .map_err(|e| wasm_error!(e))?
+        else {
+            continue;
+        };
+        if measurement_collection.live != live {
+            continue;
+        }
+        measurements.extend(measurement_collection.measurements);
+    }
+
+    Ok(measurements)
+}
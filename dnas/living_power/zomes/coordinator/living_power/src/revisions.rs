@@ -0,0 +1,49 @@
+use hdk::prelude::*;
+
+/// The record of the given action followed by its updates, always following the latest update of each revision
+pub fn get_all_revisions(original_action_hash: ActionHash) -> ExternResult<Vec<Record>> {
+    let mut revisions = vec![];
+    let mut next_action_hash = Some(original_action_hash);
+
+    while let Some(action_hash) = next_action_hash {
+        let Some(details) = get_details(action_hash, GetOptions::default())? else {
+            break;
+        };
+        let Details::Record(record_details) = details else {
+            return Err(wasm_error!(WasmErrorInner::Guest(
+                "Malformed get details response".to_string()
+            )));
+        };
+        next_action_hash = record_details
+            .updates
+            .into_iter()
+            .max_by_key(|update| update.action().timestamp())
+            .map(|update| update.hashed.hash);
+        revisions.push(record_details.record);
+    }
+
+    Ok(revisions)
+}
+
+pub fn get_latest_revision(original_action_hash: ActionHash) -> ExternResult<Option<Record>> {
+    Ok(get_all_revisions(original_action_hash)?.pop())
+}
+
+/// The latest revision of the entries linked from the base with the given link type
+pub fn get_latest_revisions(
+    base: impl Into<AnyLinkableHash>,
+    link_type: impl LinkTypeFilterExt,
+) -> ExternResult<Vec<Record>> {
+    let links = get_links(GetLinksInputBuilder::try_new(base, link_type)?.build())?;
+
+    let mut records = vec![];
+    for link in links {
+        let Some(action_hash) = link.target.into_action_hash() else {
+            continue;
+        };
+        if let Some(record) = get_latest_revision(action_hash)? {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
@@ -0,0 +1,80 @@
+use hdk::prelude::*;
+use living_power_integrity::*;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GrantRoleInput {
+    pub agent: AgentPubKey,
+    pub role: Role,
+}
+
+/// Grants the role to the agent, which holds it once it claims it with `claim_role`
+///
+/// Only admins can grant roles
+#[hdk_extern]
+pub fn grant_role(input: GrantRoleInput) -> ExternResult<ActionHash> {
+    let role_grant = RoleGrant {
+        agent: input.agent.clone(),
+        role: input.role,
+    };
+    let role_grant_hash = create_entry(&EntryTypes::RoleGrant(role_grant))?;
+    create_link(
+        input.agent,
+        role_grant_hash.clone(),
+        LinkTypes::AgentToRoleGrants,
+        (),
+    )?;
+    Ok(role_grant_hash)
+}
+
+/// Accepts a role granted to this agent, so that the validation of its deletes can find it
+#[hdk_extern]
+pub fn claim_role(role_grant_hash: ActionHash) -> ExternResult<ActionHash> {
+    create_entry(&EntryTypes::RoleClaim(RoleClaim { role_grant_hash }))
+}
+
+#[hdk_extern]
+pub fn get_role_grants_for_agent(agent: AgentPubKey) -> ExternResult<Vec<Link>> {
+    get_links(GetLinksInputBuilder::try_new(agent, LinkTypes::AgentToRoleGrants)?.build())
+}
+
+/// The roles this agent has claimed
+///
+/// The admins of the DNA properties are admins without claiming any role
+#[hdk_extern]
+pub fn get_my_roles() -> ExternResult<Vec<Role>> {
+    let my_pub_key = agent_info()?.agent_latest_pubkey;
+    let mut roles = vec![];
+    if dna_properties()?.admins.contains(&my_pub_key) {
+        roles.push(Role::Admin);
+    }
+
+    let role_claims = query(
+        ChainQueryFilter::new()
+            .entry_type(UnitEntryTypes::RoleClaim.try_into()?)
+            .include_entries(true),
+    )?;
+    for record in role_claims {
+        let Some(role_claim) = record
+            .entry()
+            .to_app_option::<RoleClaim>()
+            .map_err(|e| wasm_error!(e))?
+        else {
+            continue;
+        };
+        let Some(role_grant_record) = get(role_claim.role_grant_hash, GetOptions::default())?
+        else {
+            continue;
+        };
+        let Some(role_grant) = role_grant_record
+            .entry()
+            .to_app_option::<RoleGrant>()
+            .map_err(|e| wasm_error!(e))?
+        else {
+            continue;
+        };
+        if !roles.contains(&role_grant.role) {
+            roles.push(role_grant.role);
+        }
+    }
+    Ok(roles)
+}
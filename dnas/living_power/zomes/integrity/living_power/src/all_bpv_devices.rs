@@ -1,21 +1,95 @@
 use hdi::prelude::*;
 
+use crate::roles::validate_delete_authorship;
+
+const ALL_BPV_DEVICES_PATH: &str = "all_bpv_devices";
+
+/// The path entry hash of the device, which is the base of all its links
+pub fn bpv_device_hash(arduino_serial_number: &str) -> ExternResult<EntryHash> {
+    Path::from(format!("{ALL_BPV_DEVICES_PATH}.{arduino_serial_number}")).path_entry_hash()
+}
+
+/// Checks that the base of a link is the path entry of the device with the given serial number
+pub fn validate_bpv_device_base(
+    base_address: &AnyLinkableHash,
+    arduino_serial_number: &str,
+    what: &str,
+) -> ExternResult<ValidateCallbackResult> {
+    if arduino_serial_number.trim().is_empty() {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "The arduino serial number of {what} must not be empty"
+        )));
+    }
+    if *base_address != AnyLinkableHash::from(bpv_device_hash(arduino_serial_number)?) {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "The base of {what} must be the path of the device {arduino_serial_number}"
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+
+/// The links of the `all_bpv_devices.<serial>` paths: one from the root of the paths to `all_bpv_devices`,
+/// and one from `all_bpv_devices` to each device, tagged with the last component of the target path
 pub fn validate_create_link_all_bpv_devices(
     _action: CreateLink,
-    _base_address: AnyLinkableHash,
+    base_address: AnyLinkableHash,
     target_address: AnyLinkableHash,
-    _tag: LinkTag,
+    tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
-    // TODO: add the appropriate validation rules
+    let Ok(component) =
+        Component::try_from(SerializedBytes::from(UnsafeBytes::from(tag.into_inner())))
+    else {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The tag of a device path link must be a path component",
+        )));
+    };
+    let Ok(component) = String::try_from(&component) else {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The tag of a device path link must be a string component",
+        )));
+    };
+
+    let all_bpv_devices_hash =
+        AnyLinkableHash::from(Path::from(ALL_BPV_DEVICES_PATH).path_entry_hash()?);
+    if component == ALL_BPV_DEVICES_PATH {
+        if target_address != all_bpv_devices_hash {
+            return Ok(ValidateCallbackResult::Invalid(format!(
+                "A link tagged {ALL_BPV_DEVICES_PATH} must point to its path"
+            )));
+        }
+        return Ok(ValidateCallbackResult::Valid);
+    }
+
+    if base_address != all_bpv_devices_hash {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "The base of a device path link must be the {ALL_BPV_DEVICES_PATH} path"
+        )));
+    }
+    if component.trim().is_empty() {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The arduino serial number of a device must not be empty",
+        )));
+    }
+    if target_address != AnyLinkableHash::from(bpv_device_hash(&component)?) {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "A device path link must point to the path of the device {component}"
+        )));
+    }
     Ok(ValidateCallbackResult::Valid)
 }
+/// The target of the links is the device, except for the link from the root of the paths
 pub fn validate_delete_link_all_bpv_devices(
-    _action: DeleteLink,
-    _original_action: CreateLink,
+    action: DeleteLink,
+    original_action: CreateLink,
     _base: AnyLinkableHash,
-    _target: AnyLinkableHash,
+    target: AnyLinkableHash,
     _tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
-    // TODO: add the appropriate validation rules
-    Ok(ValidateCallbackResult::Valid)
+    validate_delete_authorship(
+        &action.author,
+        &action.prev_action,
+        &original_action.author,
+        Some(&target),
+        "a device",
+    )
 }
@@ -1,20 +1,215 @@
+use std::ops::RangeInclusive;
+
 use hdi::prelude::*;
 
+use crate::all_bpv_devices::{bpv_device_hash, validate_bpv_device_base};
+use crate::roles::validate_delete_authorship;
+
+/// The timezone the real time clock of a BPV device is set to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum DeviceTimezone {
+    /// IANA timezone name, like "Europe/Madrid"
+    Named { name: String },
+    /// Fixed offset from UTC, for devices whose clock doesn't follow daylight saving time
+    UtcOffset { offset_minutes: i32 },
+}
+
+/// Where a device is installed, in millionths of a degree
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GpsLocation {
+    pub latitude_microdegrees: i32,
+    pub longitude_microdegrees: i32,
+}
+
+const LATITUDE_RANGE: RangeInclusive<i32> = -90_000_000..=90_000_000;
+const LONGITUDE_RANGE: RangeInclusive<i32> = -180_000_000..=180_000_000;
+
+/// The largest photo of a device, as the data URL it's stored as, to stay well under the entry size limit
+const MAX_PHOTO_DATA_URL_LENGTH: usize = 2 * 1024 * 1024;
+
+/// The configuration of a device, its revisions form an update chain from the one linked from the device,
+/// which keeps the history of how the device was set up
+///
+/// All the fields besides the name are optional, as devices registered before they were added don't have them
+#[hdk_entry_helper]
+#[derive(Clone, PartialEq)]
+pub struct BpvDeviceInfo {
+    pub arduino_serial_number: String,
+    pub name: String,
+    /// Devices registered before the timezone was configurable have none,
+    /// their measurements are interpreted in the local timezone of the computer collecting them
+    #[serde(default)]
+    pub timezone: Option<DeviceTimezone>,
+    #[serde(default)]
+    pub location: Option<GpsLocation>,
+    #[serde(default)]
+    pub site_description: Option<String>,
+    /// The plant or microbial species growing in the device
+    #[serde(default)]
+    pub species: Option<String>,
+    #[serde(default)]
+    pub electrode_material: Option<String>,
+    #[serde(default)]
+    pub installation_date: Option<Timestamp>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// The entry hash of a `BpvDevicePhoto`
+    #[serde(default)]
+    pub photo_hash: Option<EntryHash>,
+}
+
+/// A photo of a device, kept apart from its info so that revisions of the info don't copy it
+#[hdk_entry_helper]
+#[derive(Clone, PartialEq)]
+pub struct BpvDevicePhoto {
+    /// The image encoded as a data URL, like "data:image/jpeg;base64,..."
+    pub data_url: String,
+}
+
+pub fn validate_create_bpv_device_info(
+    _action: EntryCreationAction,
+    bpv_device_info: BpvDeviceInfo,
+) -> ExternResult<ValidateCallbackResult> {
+    if bpv_device_info.arduino_serial_number.trim().is_empty() {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The arduino serial number of a device info must not be empty",
+        )));
+    }
+    if bpv_device_info.name.trim().is_empty() {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The name of a device must not be empty",
+        )));
+    }
+    if let Some(location) = &bpv_device_info.location {
+        if !LATITUDE_RANGE.contains(&location.latitude_microdegrees)
+            || !LONGITUDE_RANGE.contains(&location.longitude_microdegrees)
+        {
+            return Ok(ValidateCallbackResult::Invalid(String::from(
+                "The location of a device must be a valid latitude and longitude",
+            )));
+        }
+    }
+    if let Some(photo_hash) = bpv_device_info.photo_hash {
+        let entry = must_get_entry(photo_hash)?;
+        if BpvDevicePhoto::try_from(entry.into_content()).is_err() {
+            return Ok(ValidateCallbackResult::Invalid(String::from(
+                "The photo of a device must be a BpvDevicePhoto",
+            )));
+        }
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_bpv_device_info(
+    _action: Update,
+    bpv_device_info: BpvDeviceInfo,
+    _original_action: EntryCreationAction,
+    original_bpv_device_info: BpvDeviceInfo,
+) -> ExternResult<ValidateCallbackResult> {
+    if bpv_device_info.arduino_serial_number != original_bpv_device_info.arduino_serial_number {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The info of a device cannot be moved to another device",
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_delete_bpv_device_info(
+    action: Delete,
+    original_action: EntryCreationAction,
+    original_bpv_device_info: BpvDeviceInfo,
+) -> ExternResult<ValidateCallbackResult> {
+    let device_hash = AnyLinkableHash::from(bpv_device_hash(
+        &original_bpv_device_info.arduino_serial_number,
+    )?);
+    validate_delete_authorship(
+        &action.author,
+        &action.prev_action,
+        original_action.author(),
+        Some(&device_hash),
+        "a device info",
+    )
+}
+
+pub fn validate_create_bpv_device_photo(
+    _action: EntryCreationAction,
+    bpv_device_photo: BpvDevicePhoto,
+) -> ExternResult<ValidateCallbackResult> {
+    if !bpv_device_photo.data_url.starts_with("data:image/") {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The photo of a device must be an image data URL",
+        )));
+    }
+    if bpv_device_photo.data_url.len() > MAX_PHOTO_DATA_URL_LENGTH {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "The photo of a device must be smaller than {} MB",
+            MAX_PHOTO_DATA_URL_LENGTH / 1024 / 1024
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_bpv_device_photo(
+    _action: Update,
+    _bpv_device_photo: BpvDevicePhoto,
+    _original_action: EntryCreationAction,
+    _original_bpv_device_photo: BpvDevicePhoto,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Device photos cannot be updated, a new photo is set in a new revision of the device info",
+    )))
+}
+pub fn validate_delete_bpv_device_photo(
+    _action: Delete,
+    _original_action: EntryCreationAction,
+    _original_bpv_device_photo: BpvDevicePhoto,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Device photos cannot be deleted, as revisions of the device info reference them",
+    )))
+}
+
 pub fn validate_create_link_bpv_device_to_bpv_device_info(
     _action: CreateLink,
     base_address: AnyLinkableHash,
     target_address: AnyLinkableHash,
     _tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
-    Ok(ValidateCallbackResult::Valid)
+    let action_hash =
+        target_address
+            .into_action_hash()
+            .ok_or(wasm_error!(WasmErrorInner::Guest(
+                "No action hash associated with link".to_string()
+            )))?;
+    let record = must_get_valid_record(action_hash)?;
+    if !matches!(record.action(), Action::Create(_)) {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "A device info link must point to the first revision of the info",
+        )));
+    }
+    let bpv_device_info: crate::BpvDeviceInfo = record
+        .entry()
+        .to_app_option()
+        .map_err(|e| wasm_error!(e))?
+        .ok_or(wasm_error!(WasmErrorInner::Guest(
+            "Linked action must reference an entry".to_string()
+        )))?;
+    validate_bpv_device_base(
+        &base_address,
+        &bpv_device_info.arduino_serial_number,
+        "a device info link",
+    )
 }
 pub fn validate_delete_link_bpv_device_to_bpv_device_info(
-    _action: DeleteLink,
-    _original_action: CreateLink,
-    _base: AnyLinkableHash,
+    action: DeleteLink,
+    original_action: CreateLink,
+    base: AnyLinkableHash,
     _target: AnyLinkableHash,
     _tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
-    // TODO: add the appropriate validation rules
-    Ok(ValidateCallbackResult::Valid)
+    validate_delete_authorship(
+        &action.author,
+        &action.prev_action,
+        &original_action.author,
+        Some(&base),
+        "a device info link",
+    )
 }
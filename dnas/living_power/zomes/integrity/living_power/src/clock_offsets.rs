@@ -0,0 +1,47 @@
+use hdi::prelude::*;
+
+/// A reading of the real time clock of a BPV device, compared with the clock of the computer it was connected to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, SerializedBytes)]
+pub struct ClockOffset {
+    /// The time the device clock showed, converted with its timezone
+    pub device_time: Timestamp,
+    /// The time of the computer when the device clock was read
+    pub host_time: Timestamp,
+    /// Whether the device clock was set right before it was read, so the drift before doesn't carry over
+    #[serde(default)]
+    pub clock_set: bool,
+}
+
+impl ClockOffset {
+    /// How far ahead of the real time the device clock was, in microseconds
+    pub fn offset_micros(&self) -> i64 {
+        self.device_time.as_micros() - self.host_time.as_micros()
+    }
+}
+
+pub fn validate_create_link_bpv_device_to_clock_offsets(
+    _action: CreateLink,
+    _base_address: AnyLinkableHash,
+    _target_address: AnyLinkableHash,
+    tag: LinkTag,
+) -> ExternResult<ValidateCallbackResult> {
+    if let Err(err) =
+        ClockOffset::try_from(SerializedBytes::from(UnsafeBytes::from(tag.into_inner())))
+    {
+        return Ok(ValidateCallbackResult::Invalid(format!(
+            "The tag of a clock offset link must be a ClockOffset: {err:?}"
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_delete_link_bpv_device_to_clock_offsets(
+    _action: DeleteLink,
+    _original_action: CreateLink,
+    _base: AnyLinkableHash,
+    _target: AnyLinkableHash,
+    _tag: LinkTag,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Clock offsets cannot be deleted",
+    )))
+}
@@ -1,21 +1,123 @@
 use hdi::prelude::*;
 
+use crate::all_bpv_devices::{bpv_device_hash, validate_bpv_device_base};
+use crate::roles::validate_delete_authorship;
+
+/// The external resistor a device had connected during a period of time,
+/// corrections form an update chain from the one linked from the device
+#[hdk_entry_helper]
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExternalResistorValue {
+    pub arduino_serial_number: String,
+    pub external_resistor_value_ohms: u64,
+    pub from: Timestamp,
+    pub to: Timestamp,
+}
+
+impl ExternalResistorValue {
+    /// Whether both are the same resistor during the same period, regardless of the device they were set for
+    pub fn same_value(&self, other: &ExternalResistorValue) -> bool {
+        self.external_resistor_value_ohms == other.external_resistor_value_ohms
+            && self.from == other.from
+            && self.to == other.to
+    }
+}
+
+pub fn validate_create_external_resistor_value(
+    _action: EntryCreationAction,
+    external_resistor_value: ExternalResistorValue,
+) -> ExternResult<ValidateCallbackResult> {
+    if external_resistor_value
+        .arduino_serial_number
+        .trim()
+        .is_empty()
+    {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The arduino serial number of an external resistor value must not be empty",
+        )));
+    }
+    if external_resistor_value.from >= external_resistor_value.to {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "The period of an external resistor value must start before it ends",
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_external_resistor_value(
+    _action: Update,
+    external_resistor_value: ExternalResistorValue,
+    _original_action: EntryCreationAction,
+    original_external_resistor_value: ExternalResistorValue,
+) -> ExternResult<ValidateCallbackResult> {
+    if external_resistor_value.arduino_serial_number
+        != original_external_resistor_value.arduino_serial_number
+    {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "An external resistor value cannot be moved to another device",
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_delete_external_resistor_value(
+    action: Delete,
+    original_action: EntryCreationAction,
+    original_external_resistor_value: ExternalResistorValue,
+) -> ExternResult<ValidateCallbackResult> {
+    let device_hash = AnyLinkableHash::from(bpv_device_hash(
+        &original_external_resistor_value.arduino_serial_number,
+    )?);
+    validate_delete_authorship(
+        &action.author,
+        &action.prev_action,
+        original_action.author(),
+        Some(&device_hash),
+        "an external resistor value",
+    )
+}
+
 pub fn validate_create_link_bpv_device_to_external_resistor_values(
     _action: CreateLink,
     base_address: AnyLinkableHash,
     target_address: AnyLinkableHash,
     _tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
-    // TODO: add the appropriate validation rules
-    Ok(ValidateCallbackResult::Valid)
+    let action_hash =
+        target_address
+            .into_action_hash()
+            .ok_or(wasm_error!(WasmErrorInner::Guest(
+                "No action hash associated with link".to_string()
+            )))?;
+    let record = must_get_valid_record(action_hash)?;
+    if !matches!(record.action(), Action::Create(_)) {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "An external resistor value link must point to the first revision of the value",
+        )));
+    }
+    let external_resistor_value: crate::ExternalResistorValue = record
+        .entry()
+        .to_app_option()
+        .map_err(|e| wasm_error!(e))?
+        .ok_or(wasm_error!(WasmErrorInner::Guest(
+            "Linked action must reference an entry".to_string()
+        )))?;
+    validate_bpv_device_base(
+        &base_address,
+        &external_resistor_value.arduino_serial_number,
+        "an external resistor value link",
+    )
 }
 pub fn validate_delete_link_bpv_device_to_external_resistor_values(
-    _action: DeleteLink,
-    _original_action: CreateLink,
-    _base: AnyLinkableHash,
+    action: DeleteLink,
+    original_action: CreateLink,
+    base: AnyLinkableHash,
     _target: AnyLinkableHash,
     _tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
-    // TODO: add the appropriate validation rules
-    Ok(ValidateCallbackResult::Valid)
+    validate_delete_authorship(
+        &action.author,
+        &action.prev_action,
+        &original_action.author,
+        Some(&base),
+        "an external resistor value link",
+    )
 }
@@ -8,6 +8,10 @@ pub mod bpv_device_info;
 pub use bpv_device_info::*;
 pub mod all_bpv_devices;
 pub use all_bpv_devices::*;
+pub mod clock_offsets;
+pub use clock_offsets::*;
+pub mod roles;
+pub use roles::*;
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -15,6 +19,11 @@ pub use all_bpv_devices::*;
 #[unit_enum(UnitEntryTypes)]
 pub enum EntryTypes {
     MeasurementCollection(MeasurementCollection),
+    RoleGrant(RoleGrant),
+    RoleClaim(RoleClaim),
+    BpvDeviceInfo(BpvDeviceInfo),
+    ExternalResistorValue(ExternalResistorValue),
+    BpvDevicePhoto(BpvDevicePhoto),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -24,6 +33,8 @@ pub enum LinkTypes {
     BpvDeviceToBpvDeviceInfo,
     BpvDeviceToExternalResistorValues,
     BpvDeviceToMeasurementCollections,
+    BpvDeviceToClockOffsets,
+    AgentToRoleGrants,
 }
 #[hdk_extern]
 pub fn genesis_self_check(_data: GenesisSelfCheckData) -> ExternResult<ValidateCallbackResult> {
@@ -50,6 +61,26 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         measurement_collection,
                     )
                 }
+                EntryTypes::RoleGrant(role_grant) => {
+                    validate_create_role_grant(EntryCreationAction::Create(action), role_grant)
+                }
+                EntryTypes::RoleClaim(role_claim) => {
+                    validate_create_role_claim(EntryCreationAction::Create(action), role_claim)
+                }
+                EntryTypes::BpvDeviceInfo(bpv_device_info) => validate_create_bpv_device_info(
+                    EntryCreationAction::Create(action),
+                    bpv_device_info,
+                ),
+                EntryTypes::ExternalResistorValue(external_resistor_value) => {
+                    validate_create_external_resistor_value(
+                        EntryCreationAction::Create(action),
+                        external_resistor_value,
+                    )
+                }
+                EntryTypes::BpvDevicePhoto(bpv_device_photo) => validate_create_bpv_device_photo(
+                    EntryCreationAction::Create(action),
+                    bpv_device_photo,
+                ),
             },
             OpEntry::UpdateEntry {
                 app_entry, action, ..
@@ -60,6 +91,26 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         measurement_collection,
                     )
                 }
+                EntryTypes::RoleGrant(role_grant) => {
+                    validate_create_role_grant(EntryCreationAction::Update(action), role_grant)
+                }
+                EntryTypes::RoleClaim(role_claim) => {
+                    validate_create_role_claim(EntryCreationAction::Update(action), role_claim)
+                }
+                EntryTypes::BpvDeviceInfo(bpv_device_info) => validate_create_bpv_device_info(
+                    EntryCreationAction::Update(action),
+                    bpv_device_info,
+                ),
+                EntryTypes::ExternalResistorValue(external_resistor_value) => {
+                    validate_create_external_resistor_value(
+                        EntryCreationAction::Update(action),
+                        external_resistor_value,
+                    )
+                }
+                EntryTypes::BpvDevicePhoto(bpv_device_photo) => validate_create_bpv_device_photo(
+                    EntryCreationAction::Update(action),
+                    bpv_device_photo,
+                ),
             },
             _ => Ok(ValidateCallbackResult::Valid),
         },
@@ -96,6 +147,99 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                             original_measurement_collection,
                         )
                     }
+                    EntryTypes::BpvDeviceInfo(bpv_device_info) => {
+                        let original_app_entry =
+                            must_get_valid_record(action.clone().original_action_address)?;
+                        let original_bpv_device_info =
+                            match BpvDeviceInfo::try_from(original_app_entry) {
+                                Ok(entry) => entry,
+                                Err(e) => {
+                                    return Ok(ValidateCallbackResult::Invalid(format!(
+                                        "Expected to get BpvDeviceInfo from Record: {e:?}"
+                                    )));
+                                }
+                            };
+                        validate_update_bpv_device_info(
+                            action,
+                            bpv_device_info,
+                            original_create_action,
+                            original_bpv_device_info,
+                        )
+                    }
+                    EntryTypes::ExternalResistorValue(external_resistor_value) => {
+                        let original_app_entry =
+                            must_get_valid_record(action.clone().original_action_address)?;
+                        let original_external_resistor_value =
+                            match ExternalResistorValue::try_from(original_app_entry) {
+                                Ok(entry) => entry,
+                                Err(e) => {
+                                    return Ok(ValidateCallbackResult::Invalid(format!(
+                                        "Expected to get ExternalResistorValue from Record: {e:?}"
+                                    )));
+                                }
+                            };
+                        validate_update_external_resistor_value(
+                            action,
+                            external_resistor_value,
+                            original_create_action,
+                            original_external_resistor_value,
+                        )
+                    }
+                    EntryTypes::BpvDevicePhoto(bpv_device_photo) => {
+                        let original_app_entry =
+                            must_get_valid_record(action.clone().original_action_address)?;
+                        let original_bpv_device_photo =
+                            match BpvDevicePhoto::try_from(original_app_entry) {
+                                Ok(entry) => entry,
+                                Err(e) => {
+                                    return Ok(ValidateCallbackResult::Invalid(format!(
+                                        "Expected to get BpvDevicePhoto from Record: {e:?}"
+                                    )));
+                                }
+                            };
+                        validate_update_bpv_device_photo(
+                            action,
+                            bpv_device_photo,
+                            original_create_action,
+                            original_bpv_device_photo,
+                        )
+                    }
+                    EntryTypes::RoleGrant(role_grant) => {
+                        let original_app_entry =
+                            must_get_valid_record(action.clone().original_action_address)?;
+                        let original_role_grant = match RoleGrant::try_from(original_app_entry) {
+                            Ok(entry) => entry,
+                            Err(e) => {
+                                return Ok(ValidateCallbackResult::Invalid(format!(
+                                    "Expected to get RoleGrant from Record: {e:?}"
+                                )));
+                            }
+                        };
+                        validate_update_role_grant(
+                            action,
+                            role_grant,
+                            original_create_action,
+                            original_role_grant,
+                        )
+                    }
+                    EntryTypes::RoleClaim(role_claim) => {
+                        let original_app_entry =
+                            must_get_valid_record(action.clone().original_action_address)?;
+                        let original_role_claim = match RoleClaim::try_from(original_app_entry) {
+                            Ok(entry) => entry,
+                            Err(e) => {
+                                return Ok(ValidateCallbackResult::Invalid(format!(
+                                    "Expected to get RoleClaim from Record: {e:?}"
+                                )));
+                            }
+                        };
+                        validate_update_role_claim(
+                            action,
+                            role_claim,
+                            original_create_action,
+                            original_role_claim,
+                        )
+                    }
                 }
             }
             _ => Ok(ValidateCallbackResult::Valid),
@@ -147,6 +291,37 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         original_measurement_collection,
                     )
                 }
+                EntryTypes::BpvDeviceInfo(original_bpv_device_info) => {
+                    validate_delete_bpv_device_info(
+                        delete_entry.clone().action,
+                        original_action,
+                        original_bpv_device_info,
+                    )
+                }
+                EntryTypes::ExternalResistorValue(original_external_resistor_value) => {
+                    validate_delete_external_resistor_value(
+                        delete_entry.clone().action,
+                        original_action,
+                        original_external_resistor_value,
+                    )
+                }
+                EntryTypes::BpvDevicePhoto(original_bpv_device_photo) => {
+                    validate_delete_bpv_device_photo(
+                        delete_entry.clone().action,
+                        original_action,
+                        original_bpv_device_photo,
+                    )
+                }
+                EntryTypes::RoleGrant(original_role_grant) => validate_delete_role_grant(
+                    delete_entry.clone().action,
+                    original_action,
+                    original_role_grant,
+                ),
+                EntryTypes::RoleClaim(original_role_claim) => validate_delete_role_claim(
+                    delete_entry.clone().action,
+                    original_action,
+                    original_role_claim,
+                ),
             }
         }
         FlatOp::RegisterCreateLink {
@@ -183,6 +358,15 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     tag,
                 )
             }
+            LinkTypes::BpvDeviceToClockOffsets => validate_create_link_bpv_device_to_clock_offsets(
+                action,
+                base_address,
+                target_address,
+                tag,
+            ),
+            LinkTypes::AgentToRoleGrants => {
+                validate_create_link_agent_to_role_grants(action, base_address, target_address, tag)
+            }
         },
         FlatOp::RegisterDeleteLink {
             link_type,
@@ -226,6 +410,20 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                     tag,
                 )
             }
+            LinkTypes::BpvDeviceToClockOffsets => validate_delete_link_bpv_device_to_clock_offsets(
+                action,
+                original_action,
+                base_address,
+                target_address,
+                tag,
+            ),
+            LinkTypes::AgentToRoleGrants => validate_delete_link_agent_to_role_grants(
+                action,
+                original_action,
+                base_address,
+                target_address,
+                tag,
+            ),
         },
         FlatOp::StoreRecord(store_record) => match store_record {
             OpRecord::CreateEntry { app_entry, action } => match app_entry {
@@ -235,6 +433,26 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         measurement_collection,
                     )
                 }
+                EntryTypes::RoleGrant(role_grant) => {
+                    validate_create_role_grant(EntryCreationAction::Create(action), role_grant)
+                }
+                EntryTypes::RoleClaim(role_claim) => {
+                    validate_create_role_claim(EntryCreationAction::Create(action), role_claim)
+                }
+                EntryTypes::BpvDeviceInfo(bpv_device_info) => validate_create_bpv_device_info(
+                    EntryCreationAction::Create(action),
+                    bpv_device_info,
+                ),
+                EntryTypes::ExternalResistorValue(external_resistor_value) => {
+                    validate_create_external_resistor_value(
+                        EntryCreationAction::Create(action),
+                        external_resistor_value,
+                    )
+                }
+                EntryTypes::BpvDevicePhoto(bpv_device_photo) => validate_create_bpv_device_photo(
+                    EntryCreationAction::Create(action),
+                    bpv_device_photo,
+                ),
             },
             OpRecord::UpdateEntry {
                 original_action_hash,
@@ -288,6 +506,139 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                             Ok(result)
                         }
                     }
+                    EntryTypes::BpvDeviceInfo(bpv_device_info) => {
+                        let result = validate_create_bpv_device_info(
+                            EntryCreationAction::Update(action.clone()),
+                            bpv_device_info.clone(),
+                        )?;
+                        if let ValidateCallbackResult::Valid = result {
+                            let original_bpv_device_info: Option<BpvDeviceInfo> = original_record
+                                .entry()
+                                .to_app_option()
+                                .map_err(|e| wasm_error!(e))?;
+                            let Some(original_bpv_device_info) = original_bpv_device_info else {
+                                return Ok(ValidateCallbackResult::Invalid(
+                                    "The updated entry type must be the same as the original entry type"
+                                        .to_string(),
+                                ));
+                            };
+                            validate_update_bpv_device_info(
+                                action,
+                                bpv_device_info,
+                                original_action,
+                                original_bpv_device_info,
+                            )
+                        } else {
+                            Ok(result)
+                        }
+                    }
+                    EntryTypes::ExternalResistorValue(external_resistor_value) => {
+                        let result = validate_create_external_resistor_value(
+                            EntryCreationAction::Update(action.clone()),
+                            external_resistor_value.clone(),
+                        )?;
+                        if let ValidateCallbackResult::Valid = result {
+                            let original_external_resistor_value: Option<ExternalResistorValue> =
+                                original_record
+                                    .entry()
+                                    .to_app_option()
+                                    .map_err(|e| wasm_error!(e))?;
+                            let Some(original_external_resistor_value) =
+                                original_external_resistor_value
+                            else {
+                                return Ok(ValidateCallbackResult::Invalid(
+                                    "The updated entry type must be the same as the original entry type"
+                                        .to_string(),
+                                ));
+                            };
+                            validate_update_external_resistor_value(
+                                action,
+                                external_resistor_value,
+                                original_action,
+                                original_external_resistor_value,
+                            )
+                        } else {
+                            Ok(result)
+                        }
+                    }
+                    EntryTypes::BpvDevicePhoto(bpv_device_photo) => {
+                        let result = validate_create_bpv_device_photo(
+                            EntryCreationAction::Update(action.clone()),
+                            bpv_device_photo.clone(),
+                        )?;
+                        if let ValidateCallbackResult::Valid = result {
+                            let original_bpv_device_photo: Option<BpvDevicePhoto> = original_record
+                                .entry()
+                                .to_app_option()
+                                .map_err(|e| wasm_error!(e))?;
+                            let Some(original_bpv_device_photo) = original_bpv_device_photo else {
+                                return Ok(ValidateCallbackResult::Invalid(
+                                    "The updated entry type must be the same as the original entry type"
+                                        .to_string(),
+                                ));
+                            };
+                            validate_update_bpv_device_photo(
+                                action,
+                                bpv_device_photo,
+                                original_action,
+                                original_bpv_device_photo,
+                            )
+                        } else {
+                            Ok(result)
+                        }
+                    }
+                    EntryTypes::RoleGrant(role_grant) => {
+                        let result = validate_create_role_grant(
+                            EntryCreationAction::Update(action.clone()),
+                            role_grant.clone(),
+                        )?;
+                        if let ValidateCallbackResult::Valid = result {
+                            let original_role_grant: Option<RoleGrant> = original_record
+                                .entry()
+                                .to_app_option()
+                                .map_err(|e| wasm_error!(e))?;
+                            let Some(original_role_grant) = original_role_grant else {
+                                return Ok(ValidateCallbackResult::Invalid(
+                                    "The updated entry type must be the same as the original entry type"
+                                        .to_string(),
+                                ));
+                            };
+                            validate_update_role_grant(
+                                action,
+                                role_grant,
+                                original_action,
+                                original_role_grant,
+                            )
+                        } else {
+                            Ok(result)
+                        }
+                    }
+                    EntryTypes::RoleClaim(role_claim) => {
+                        let result = validate_create_role_claim(
+                            EntryCreationAction::Update(action.clone()),
+                            role_claim.clone(),
+                        )?;
+                        if let ValidateCallbackResult::Valid = result {
+                            let original_role_claim: Option<RoleClaim> = original_record
+                                .entry()
+                                .to_app_option()
+                                .map_err(|e| wasm_error!(e))?;
+                            let Some(original_role_claim) = original_role_claim else {
+                                return Ok(ValidateCallbackResult::Invalid(
+                                    "The updated entry type must be the same as the original entry type"
+                                        .to_string(),
+                                ));
+                            };
+                            validate_update_role_claim(
+                                action,
+                                role_claim,
+                                original_action,
+                                original_role_claim,
+                            )
+                        } else {
+                            Ok(result)
+                        }
+                    }
                 }
             }
             OpRecord::DeleteEntry {
@@ -344,6 +695,33 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                             original_measurement_collection,
                         )
                     }
+                    EntryTypes::BpvDeviceInfo(original_bpv_device_info) => {
+                        validate_delete_bpv_device_info(
+                            action,
+                            original_action,
+                            original_bpv_device_info,
+                        )
+                    }
+                    EntryTypes::ExternalResistorValue(original_external_resistor_value) => {
+                        validate_delete_external_resistor_value(
+                            action,
+                            original_action,
+                            original_external_resistor_value,
+                        )
+                    }
+                    EntryTypes::BpvDevicePhoto(original_bpv_device_photo) => {
+                        validate_delete_bpv_device_photo(
+                            action,
+                            original_action,
+                            original_bpv_device_photo,
+                        )
+                    }
+                    EntryTypes::RoleGrant(original_role_grant) => {
+                        validate_delete_role_grant(action, original_action, original_role_grant)
+                    }
+                    EntryTypes::RoleClaim(original_role_claim) => {
+                        validate_delete_role_claim(action, original_action, original_role_claim)
+                    }
                 }
             }
             OpRecord::CreateLink {
@@ -380,6 +758,20 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                         tag,
                     )
                 }
+                LinkTypes::BpvDeviceToClockOffsets => {
+                    validate_create_link_bpv_device_to_clock_offsets(
+                        action,
+                        base_address,
+                        target_address,
+                        tag,
+                    )
+                }
+                LinkTypes::AgentToRoleGrants => validate_create_link_agent_to_role_grants(
+                    action,
+                    base_address,
+                    target_address,
+                    tag,
+                ),
             },
             OpRecord::DeleteLink {
                 original_action_hash,
@@ -437,6 +829,22 @@ pub fn validate(op: Op) -> ExternResult<ValidateCallbackResult> {
                             create_link.tag,
                         )
                     }
+                    LinkTypes::BpvDeviceToClockOffsets => {
+                        validate_delete_link_bpv_device_to_clock_offsets(
+                            action,
+                            create_link.clone(),
+                            base_address,
+                            create_link.target_address,
+                            create_link.tag,
+                        )
+                    }
+                    LinkTypes::AgentToRoleGrants => validate_delete_link_agent_to_role_grants(
+                        action,
+                        create_link.clone(),
+                        base_address,
+                        create_link.target_address,
+                        create_link.tag,
+                    ),
                 }
             }
             OpRecord::CreatePrivateEntry { .. } => Ok(ValidateCallbackResult::Valid),
@@ -1,12 +1,21 @@
+use std::ops::RangeInclusive;
+
 use hdi::prelude::*;
 
+use crate::all_bpv_devices::{bpv_device_hash, validate_bpv_device_base};
+use crate::roles::validate_delete_authorship;
+
+/// All values are in thousandths of their unit, signed so that sub-zero temperatures can be stored
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Measurement {
     pub timestamp: Timestamp,
-    pub humidity_percentage: u32,
-    pub temperature_celsius: u32,
-    pub light_level_lux: u32,
-    pub voltage_millivolts: u32,
+    pub humidity_percentage: i32,
+    pub temperature_celsius: i32,
+    pub light_level_lux: i32,
+    pub voltage_millivolts: i32,
+    /// The timestamp read from the device clock, set when `timestamp` has been corrected for clock drift
+    #[serde(default)]
+    pub raw_timestamp: Option<Timestamp>,
 }
 
 #[hdk_entry_helper]
@@ -14,14 +23,127 @@ pub struct Measurement {
 pub struct MeasurementCollection {
     pub arduino_serial_number: String,
     pub measurements: Vec<Measurement>,
+    /// Whether the measurements were streamed live from the device, instead of read from its data file
+    #[serde(default)]
+    pub live: bool,
 }
 
+/// The real time clock of the BPV devices starts at 2000-01-01 00:00 when it hasn't been set,
+/// which is up to 14 hours earlier in UTC for the devices that are read in a timezone east of it
+const EARLIEST_MEASUREMENT_MICROS: i64 = 946_684_800_000_000 - 14 * 60 * 60 * 1_000_000;
+
+/// How far after the action a measurement can be, as devices set with the wrong timezone can be hours ahead
+const MAX_FUTURE_MEASUREMENT_MICROS: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// Plausible sensor readings, in thousandths of their unit
+const TEMPERATURE_RANGE: RangeInclusive<i32> = -100_000..=100_000;
+const HUMIDITY_RANGE: RangeInclusive<i32> = 0..=100_000;
+const LIGHT_LEVEL_RANGE: RangeInclusive<i32> = 0..=200_000_000;
+const VOLTAGE_RANGE: RangeInclusive<i32> = -10_000..=10_000;
+
 pub fn validate_create_measurement_collection(
-    _action: EntryCreationAction,
-    _measurement_collection: MeasurementCollection,
+    action: EntryCreationAction,
+    measurement_collection: MeasurementCollection,
 ) -> ExternResult<ValidateCallbackResult> {
+    if measurement_collection
+        .arduino_serial_number
+        .trim()
+        .is_empty()
+    {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "Measurement collections must have an arduino serial number",
+        )));
+    }
+    if measurement_collection.measurements.is_empty() {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "Measurement collections must have at least one measurement",
+        )));
+    }
+
+    for (index, measurement) in measurement_collection.measurements.iter().enumerate() {
+        if let Err(err) = validate_measurement(measurement, *action.timestamp()) {
+            return Ok(ValidateCallbackResult::Invalid(format!(
+                "Measurement {index} at {}: {err}",
+                measurement.timestamp
+            )));
+        }
+    }
+
+    for (index, pair) in measurement_collection.measurements.windows(2).enumerate() {
+        if pair[1].timestamp <= pair[0].timestamp {
+            return Ok(ValidateCallbackResult::Invalid(format!(
+                "The measurements must be sorted by timestamp without duplicates, but measurement {} at {} is not after measurement {index} at {}",
+                index + 1,
+                pair[1].timestamp,
+                pair[0].timestamp
+            )));
+        }
+    }
+
     Ok(ValidateCallbackResult::Valid)
 }
+
+/// Checks a measurement against the rules for the measurements of a collection created at the given time
+pub fn validate_measurement(
+    measurement: &Measurement,
+    collection_created_at: Timestamp,
+) -> Result<(), String> {
+    let timestamp = measurement.timestamp.as_micros();
+    let latest_micros = collection_created_at
+        .as_micros()
+        .saturating_add(MAX_FUTURE_MEASUREMENT_MICROS);
+    if timestamp < EARLIEST_MEASUREMENT_MICROS {
+        return Err(String::from("the timestamp is before the year 2000"));
+    }
+    if timestamp > latest_micros {
+        return Err(String::from(
+            "the timestamp is more than a day after the measurement collection was created",
+        ));
+    }
+
+    validate_range(
+        "temperature",
+        measurement.temperature_celsius,
+        TEMPERATURE_RANGE,
+        "°C",
+    )?;
+    validate_range(
+        "humidity",
+        measurement.humidity_percentage,
+        HUMIDITY_RANGE,
+        "%",
+    )?;
+    validate_range(
+        "light level",
+        measurement.light_level_lux,
+        LIGHT_LEVEL_RANGE,
+        "lux",
+    )?;
+    validate_range(
+        "voltage",
+        measurement.voltage_millivolts,
+        VOLTAGE_RANGE,
+        "V",
+    )
+}
+
+fn validate_range(
+    name: &str,
+    milli_units: i32,
+    range: RangeInclusive<i32>,
+    unit: &str,
+) -> Result<(), String> {
+    if range.contains(&milli_units) {
+        return Ok(());
+    }
+    let to_units = |milli_units: i32| milli_units as f64 / 1000.0;
+    Err(format!(
+        "the {name} of {} {unit} is outside of the plausible range of {} to {} {unit}",
+        to_units(milli_units),
+        to_units(*range.start()),
+        to_units(*range.end())
+    ))
+}
 pub fn validate_update_measurement_collection(
     _action: Update,
     _measurement_collection: MeasurementCollection,
@@ -33,11 +155,20 @@ pub fn validate_update_measurement_collection(
     )))
 }
 pub fn validate_delete_measurement_collection(
-    _action: Delete,
-    _original_action: EntryCreationAction,
-    _original_measurement_collection: MeasurementCollection,
+    action: Delete,
+    original_action: EntryCreationAction,
+    original_measurement_collection: MeasurementCollection,
 ) -> ExternResult<ValidateCallbackResult> {
-    Ok(ValidateCallbackResult::Valid)
+    let device_hash = AnyLinkableHash::from(bpv_device_hash(
+        &original_measurement_collection.arduino_serial_number,
+    )?);
+    validate_delete_authorship(
+        &action.author,
+        &action.prev_action,
+        original_action.author(),
+        Some(&device_hash),
+        "a measurement collection",
+    )
 }
 pub fn validate_create_link_bpv_device_to_measurement_collections(
     _action: CreateLink,
@@ -53,23 +184,31 @@ pub fn validate_create_link_bpv_device_to_measurement_collections(
                 "No action hash associated with link".to_string()
             )))?;
     let record = must_get_valid_record(action_hash)?;
-    let _measurement_collection: crate::MeasurementCollection = record
+    let measurement_collection: crate::MeasurementCollection = record
         .entry()
         .to_app_option()
         .map_err(|e| wasm_error!(e))?
         .ok_or(wasm_error!(WasmErrorInner::Guest(
             "Linked action must reference an entry".to_string()
         )))?;
-    // TODO: add the appropriate validation rules
-    Ok(ValidateCallbackResult::Valid)
+    validate_bpv_device_base(
+        &base_address,
+        &measurement_collection.arduino_serial_number,
+        "a measurement collection link",
+    )
 }
 pub fn validate_delete_link_bpv_device_to_measurement_collections(
-    _action: DeleteLink,
-    _original_action: CreateLink,
-    _base: AnyLinkableHash,
+    action: DeleteLink,
+    original_action: CreateLink,
+    base: AnyLinkableHash,
     _target: AnyLinkableHash,
     _tag: LinkTag,
 ) -> ExternResult<ValidateCallbackResult> {
-    // TODO: add the appropriate validation rules
-    Ok(ValidateCallbackResult::Valid)
+    validate_delete_authorship(
+        &action.author,
+        &action.prev_action,
+        &original_action.author,
+        Some(&base),
+        "a measurement collection link",
+    )
 }
@@ -0,0 +1,315 @@
+use hdi::prelude::*;
+
+use crate::{bpv_device_hash, BpvDeviceInfo, EntryTypes, UnitEntryTypes};
+
+/// The DNA properties of the network, set when the hApp is installed
+///
+/// The shipped hApp has none, so its networks have no admins and each device is owned by the agent that registered it
+#[derive(Serialize, Deserialize, Debug, Default, Clone, SerializedBytes)]
+pub struct DnaProperties {
+    /// Agents that can delete any data and grant roles to other agents
+    #[serde(default)]
+    pub admins: Vec<AgentPubKey>,
+}
+
+pub fn dna_properties() -> ExternResult<DnaProperties> {
+    let properties = dna_info()?.modifiers.properties;
+    // Networks created without properties have `null` ones
+    let properties: Option<DnaProperties> = decode(properties.bytes()).map_err(|err| {
+        wasm_error!(WasmErrorInner::Guest(format!(
+            "Malformed DNA properties: {err:?}"
+        )))
+    })?;
+    Ok(properties.unwrap_or_default())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum Role {
+    /// Can delete any data and grant roles, like the admins in the DNA properties
+    Admin,
+    /// Can delete any data of the device
+    DeviceOwner { arduino_serial_number: String },
+}
+
+/// Gives a role to an agent, only admins can create them, besides the owner role of a device for the agent that registered it
+///
+/// The agent holds the role once it commits a `RoleClaim` for the grant in its own source chain
+///
+/// Roles are permanent: validation can only see the source chain of the agent, not a later delete
+/// of its grant by an admin, so role grants, their links and role claims cannot be deleted
+#[hdk_entry_helper]
+#[derive(Clone, PartialEq)]
+pub struct RoleGrant {
+    pub agent: AgentPubKey,
+    pub role: Role,
+}
+
+/// Accepts a `RoleGrant` for the author, so that validation can find its roles in its source chain
+#[hdk_entry_helper]
+#[derive(Clone, PartialEq)]
+pub struct RoleClaim {
+    pub role_grant_hash: ActionHash,
+}
+
+/// The roles the agent had claimed in its source chain up to the given action
+pub fn claimed_roles(agent: AgentPubKey, chain_top: ActionHash) -> ExternResult<Vec<Role>> {
+    let role_claim_type: EntryType = UnitEntryTypes::RoleClaim.try_into()?;
+    let activity = must_get_agent_activity(agent.clone(), ChainFilter::new(chain_top))?;
+
+    let mut roles = vec![];
+    for activity in activity {
+        let Action::Create(create) = activity.action.action() else {
+            continue;
+        };
+        if create.entry_type != role_claim_type {
+            continue;
+        }
+        let Some(role_claim) = must_get_valid_record(activity.action.action_address().clone())?
+            .entry()
+            .to_app_option::<RoleClaim>()
+            .map_err(|e| wasm_error!(e))?
+        else {
+            continue;
+        };
+        let Some(role_grant) = must_get_valid_record(role_claim.role_grant_hash)?
+            .entry()
+            .to_app_option::<RoleGrant>()
+            .map_err(|e| wasm_error!(e))?
+        else {
+            continue;
+        };
+        // Already checked when the claim was validated
+        if role_grant.agent == agent {
+            roles.push(role_grant.role);
+        }
+    }
+    Ok(roles)
+}
+
+/// Whether the agent had registered the device, by creating the first revision of its info, before the given action
+pub fn registered_device(
+    agent: AgentPubKey,
+    chain_top: ActionHash,
+    arduino_serial_number: &str,
+) -> ExternResult<bool> {
+    let bpv_device_info_type: EntryType = UnitEntryTypes::BpvDeviceInfo.try_into()?;
+    let activity = must_get_agent_activity(agent, ChainFilter::new(chain_top))?;
+
+    for activity in activity {
+        let Action::Create(create) = activity.action.action() else {
+            continue;
+        };
+        if create.entry_type != bpv_device_info_type {
+            continue;
+        }
+        let Some(bpv_device_info) =
+            must_get_valid_record(activity.action.action_address().clone())?
+                .entry()
+                .to_app_option::<BpvDeviceInfo>()
+                .map_err(|e| wasm_error!(e))?
+        else {
+            continue;
+        };
+        if bpv_device_info.arduino_serial_number == arduino_serial_number {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Whether the agent is in the admins of the DNA properties, or had claimed an admin role before the given action
+pub fn is_admin(agent: &AgentPubKey, chain_top: &ActionHash) -> ExternResult<bool> {
+    if dna_properties()?.admins.contains(agent) {
+        return Ok(true);
+    }
+    Ok(claimed_roles(agent.clone(), chain_top.clone())?.contains(&Role::Admin))
+}
+
+/// Whether the agent can delete the data of another agent for the device with the given path entry hash
+///
+/// Admins can delete the data of every device, and device owners the data of their devices
+pub fn can_delete_device_data(
+    agent: &AgentPubKey,
+    chain_top: &ActionHash,
+    device_hash: &AnyLinkableHash,
+) -> ExternResult<bool> {
+    if dna_properties()?.admins.contains(agent) {
+        return Ok(true);
+    }
+    for role in claimed_roles(agent.clone(), chain_top.clone())? {
+        match role {
+            Role::Admin => return Ok(true),
+            Role::DeviceOwner {
+                arduino_serial_number,
+            } => {
+                if AnyLinkableHash::from(bpv_device_hash(&arduino_serial_number)?) == *device_hash {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Checks that the author of a delete is the author of the deleted data, or can delete the data of its device
+pub fn validate_delete_authorship(
+    author: &AgentPubKey,
+    chain_top: &ActionHash,
+    original_author: &AgentPubKey,
+    device_hash: Option<&AnyLinkableHash>,
+    what: &str,
+) -> ExternResult<ValidateCallbackResult> {
+    if author == original_author {
+        return Ok(ValidateCallbackResult::Valid);
+    }
+    let authorized = match device_hash {
+        Some(device_hash) => can_delete_device_data(author, chain_top, device_hash)?,
+        None => is_admin(author, chain_top)?,
+    };
+    if authorized {
+        return Ok(ValidateCallbackResult::Valid);
+    }
+    Ok(ValidateCallbackResult::Invalid(format!(
+        "Only its author, an owner of its device or an admin can delete {what}"
+    )))
+}
+
+pub fn validate_create_role_grant(
+    action: EntryCreationAction,
+    role_grant: RoleGrant,
+) -> ExternResult<ValidateCallbackResult> {
+    if let Role::DeviceOwner {
+        arduino_serial_number,
+    } = &role_grant.role
+    {
+        if arduino_serial_number.trim().is_empty() {
+            return Ok(ValidateCallbackResult::Invalid(String::from(
+                "Device owner roles must have an arduino serial number",
+            )));
+        }
+        // Validation can't see whether another agent registered the device first, only that the author did
+        if &role_grant.agent == action.author()
+            && registered_device(
+                action.author().clone(),
+                action.prev_action().clone(),
+                arduino_serial_number,
+            )?
+        {
+            return Ok(ValidateCallbackResult::Valid);
+        }
+    }
+    if !is_admin(action.author(), action.prev_action())? {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "Only admins can grant roles, besides the owner role of a device to the agent that registered it",
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_role_grant(
+    _action: Update,
+    _role_grant: RoleGrant,
+    _original_action: EntryCreationAction,
+    _original_role_grant: RoleGrant,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Role grants cannot be updated",
+    )))
+}
+pub fn validate_delete_role_grant(
+    _action: Delete,
+    _original_action: EntryCreationAction,
+    _original_role_grant: RoleGrant,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Role grants cannot be deleted",
+    )))
+}
+
+pub fn validate_create_role_claim(
+    action: EntryCreationAction,
+    role_claim: RoleClaim,
+) -> ExternResult<ValidateCallbackResult> {
+    let record = must_get_valid_record(role_claim.role_grant_hash)?;
+    let Some(EntryTypes::RoleGrant(role_grant)) = entry_types_from_record(&record)? else {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "A role claim must reference a role grant",
+        )));
+    };
+    if &role_grant.agent != action.author() {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "Agents can only claim the roles granted to them",
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_update_role_claim(
+    _action: Update,
+    _role_claim: RoleClaim,
+    _original_action: EntryCreationAction,
+    _original_role_claim: RoleClaim,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Role claims cannot be updated",
+    )))
+}
+pub fn validate_delete_role_claim(
+    _action: Delete,
+    _original_action: EntryCreationAction,
+    _original_role_claim: RoleClaim,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Role claims cannot be deleted",
+    )))
+}
+
+pub fn validate_create_link_agent_to_role_grants(
+    action: CreateLink,
+    base_address: AnyLinkableHash,
+    target_address: AnyLinkableHash,
+    _tag: LinkTag,
+) -> ExternResult<ValidateCallbackResult> {
+    let action_hash =
+        target_address
+            .into_action_hash()
+            .ok_or(wasm_error!(WasmErrorInner::Guest(
+                "No action hash associated with link".to_string()
+            )))?;
+    let record = must_get_valid_record(action_hash)?;
+    let Some(EntryTypes::RoleGrant(role_grant)) = entry_types_from_record(&record)? else {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "Linked action must reference a role grant",
+        )));
+    };
+    if AnyLinkableHash::from(role_grant.agent) != base_address {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "Role grants must be linked from the agent they are granted to",
+        )));
+    }
+    if record.action().author() != &action.author {
+        return Ok(ValidateCallbackResult::Invalid(String::from(
+            "Only the author of a role grant can link it",
+        )));
+    }
+    Ok(ValidateCallbackResult::Valid)
+}
+pub fn validate_delete_link_agent_to_role_grants(
+    _action: DeleteLink,
+    _original_action: CreateLink,
+    _base: AnyLinkableHash,
+    _target: AnyLinkableHash,
+    _tag: LinkTag,
+) -> ExternResult<ValidateCallbackResult> {
+    Ok(ValidateCallbackResult::Invalid(String::from(
+        "Role grant links cannot be deleted",
+    )))
+}
+
+fn entry_types_from_record(record: &Record) -> ExternResult<Option<EntryTypes>> {
+    let (Some(EntryType::App(app_entry_def)), Some(entry)) =
+        (record.action().entry_type(), record.entry().as_option())
+    else {
+        return Ok(None);
+    };
+    EntryTypes::deserialize_from_type(app_entry_def.zome_index, app_entry_def.entry_index, entry)
+}
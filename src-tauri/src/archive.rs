@@ -0,0 +1,255 @@
+use anyhow::anyhow;
+use holochain_types::prelude::ActionHash;
+use living_power_integrity::{
+    measurement_collection::Measurement, ClockOffset, MeasurementCollection,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+use crate::clock_drift::correct_clock_drift;
+use crate::collect_measurements::{
+    internal_collect_measurements, is_timeout, read_line_after_marker, CollectedMeasurements,
+};
+use crate::collection_progress::ActiveCollections;
+use crate::device_profiles::{DeviceProfile, DeviceProfileStore};
+use crate::device_settings::unsupported;
+use crate::rtc_timezone::rtc_timezone_for_device;
+use crate::serial_transport::{SerialConnection, SerialTransport};
+use crate::sessions::SessionManager;
+use crate::zome_calls::{call_living_power_zome, CreatedMeasurementCollections};
+
+/// Summarizing and copying a big data file on the SD card takes a while
+const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What the device reports about its `data.csv`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataFileSummary {
+    /// Number of measurement rows, without the header
+    pub rows: usize,
+    /// CRC-32 of the whole file
+    pub checksum: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchivedMeasurements {
+    pub collected: CollectedMeasurements,
+    /// Hashes of the measurement collections created for the measurements that weren't committed yet
+    pub action_hashes: Vec<ActionHash>,
+    /// Measurements that were already committed by an earlier collection
+    pub skipped_measurements: usize,
+    /// Measurements that were not committed because they are invalid, like the ones from before 2000 or in the future
+    pub rejected_measurements: usize,
+    /// Name of the file on the SD card the data file was moved to
+    pub archive_file_name: String,
+}
+
+/// Collects all the measurements of the device, commits the new ones, and once every row of the
+/// data file is verified to be committed, tells the device to move it to an archive and start a new one
+#[tauri::command]
+pub async fn collect_and_archive_measurements(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    active_collections: State<'_, ActiveCollections>,
+    device_profiles: State<'_, DeviceProfileStore>,
+    port_name: String,
+    arduino_serial_number: String,
+    correct_clock_drift: bool,
+) -> Result<ArchivedMeasurements, String> {
+    let profile = device_profiles.profile_for(Some(&arduino_serial_number));
+    internal_collect_and_archive_measurements(
+        &app,
+        &sessions,
+        &active_collections,
+        port_name,
+        &profile,
+        arduino_serial_number,
+        correct_clock_drift,
+    )
+    .await
+    .map_err(|err| err.to_string())
+}
+
+async fn internal_collect_and_archive_measurements(
+    app: &AppHandle,
+    sessions: &SessionManager,
+    active_collections: &ActiveCollections,
+    port_name: String,
+    profile: &DeviceProfile,
+    arduino_serial_number: String,
+    correct_drift: bool,
+) -> anyhow::Result<ArchivedMeasurements> {
+    let timezone = rtc_timezone_for_device(app, arduino_serial_number.clone()).await?;
+
+    // Only a collection of the whole file can be verified against the device
+    let control = active_collections.start(app, &port_name);
+    let result =
+        internal_collect_measurements(sessions, port_name.clone(), profile, &timezone, &control);
+    active_collections.finish(&port_name);
+    let collected = result?;
+
+    let summary = internal_get_data_file_summary(sessions, port_name.clone(), profile)?;
+    verify_collection(&collected, &summary)?;
+
+    let mut measurements = collected.measurements.clone();
+    if correct_drift {
+        let clock_offsets: Vec<ClockOffset> =
+            call_living_power_zome(app, "get_clock_offsets", arduino_serial_number.clone()).await?;
+        measurements = correct_clock_drift(measurements, &clock_offsets);
+    }
+
+    let created: CreatedMeasurementCollections = call_living_power_zome(
+        app,
+        "create_new_measurement_collections",
+        MeasurementCollection {
+            arduino_serial_number: arduino_serial_number.clone(),
+            measurements,
+            live: false,
+        },
+    )
+    .await?;
+
+    // Rows that share their timestamp with another one but have other values are never committed
+    let expected = summary.rows - collected.report.duplicate_lines;
+    let committed: usize = call_living_power_zome(
+        app,
+        "count_committed_measurements",
+        CountCommittedMeasurementsInput {
+            arduino_serial_number,
+            measurements: collected.measurements.clone(),
+        },
+    )
+    .await?;
+    if committed != expected {
+        return Err(anyhow!(
+            "Only {committed} of the {expected} measurements of the device are committed, the data file was not archived"
+        ));
+    }
+
+    let archive_file_name =
+        internal_archive_data_file(sessions, port_name, profile, summary.checksum)?;
+
+    Ok(ArchivedMeasurements {
+        collected,
+        action_hashes: created.action_hashes,
+        skipped_measurements: created.skipped_measurements,
+        rejected_measurements: created.rejected_measurements,
+        archive_file_name,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CountCommittedMeasurementsInput {
+    arduino_serial_number: String,
+    /// As read from the device, before any clock drift correction
+    measurements: Vec<Measurement>,
+}
+
+pub fn internal_get_data_file_summary(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+) -> anyhow::Result<DataFileSummary> {
+    let mut port = open(transport, &port_name, profile)?;
+    let line = send_command(
+        port.as_mut(),
+        profile,
+        "h",
+        "BEGIN_H",
+        "archiving the data file",
+    )?;
+
+    let invalid = || anyhow!("Invalid data file summary \"{line}\"");
+    let (rows, checksum) = line.split_once(',').ok_or_else(invalid)?;
+    Ok(DataFileSummary {
+        rows: rows.parse().map_err(|_| invalid())?,
+        checksum: u32::from_str_radix(checksum, 16).map_err(|_| invalid())?,
+    })
+}
+
+/// Checks that the collected measurements account for every row of the data file as the device reports it
+pub fn verify_collection(
+    collected: &CollectedMeasurements,
+    summary: &DataFileSummary,
+) -> anyhow::Result<()> {
+    if collected.cancelled {
+        return Err(anyhow!("The collection was cancelled"));
+    }
+    if collected.incomplete {
+        return Err(anyhow!(
+            "The collection was interrupted before all measurements were received"
+        ));
+    }
+    if collected.checksum != summary.checksum {
+        return Err(anyhow!(
+            "The collected data doesn't match the data file on the device, it may have logged a new measurement during the collection"
+        ));
+    }
+    if !collected.report.rejected_rows.is_empty() {
+        return Err(anyhow!(
+            "{} rows of the data file could not be read",
+            collected.report.rejected_rows.len()
+        ));
+    }
+    let rows = collected.measurements.len() + collected.report.duplicate_lines;
+    if rows != summary.rows {
+        return Err(anyhow!(
+            "Collected {rows} rows but the device reports {} rows in its data file",
+            summary.rows
+        ));
+    }
+
+    Ok(())
+}
+
+/// Tells the device to move its data file to an archive, if it still has the given checksum
+///
+/// Returns the name of the archive file
+pub fn internal_archive_data_file(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    checksum: u32,
+) -> anyhow::Result<String> {
+    let mut port = open(transport, &port_name, profile)?;
+    let command = profile.command('a', &format!("{checksum:08x}"));
+    send_command(
+        port.as_mut(),
+        profile,
+        &command,
+        "BEGIN_A",
+        "archiving the data file",
+    )
+}
+
+fn open(
+    transport: &dyn SerialTransport,
+    port_name: &str,
+    profile: &DeviceProfile,
+) -> anyhow::Result<Box<dyn SerialConnection>> {
+    transport.open(port_name, profile.baud_rate, ARCHIVE_TIMEOUT)
+}
+
+/// Sends the command and returns the line the device answers with after the marker,
+/// or the reason it gives if it answers with "ERROR"
+fn send_command(
+    port: &mut dyn SerialConnection,
+    profile: &DeviceProfile,
+    command: &str,
+    marker: &str,
+    action: &str,
+) -> anyhow::Result<String> {
+    if !profile.supports_framed_protocol() {
+        return Err(unsupported(action));
+    }
+    port.write_all(command.as_bytes())?;
+    let line = match read_line_after_marker(port, marker) {
+        Err(err) if is_timeout(&err) => return Err(unsupported(action)),
+        result => result?,
+    };
+
+    match line.strip_prefix("ERROR ") {
+        Some(reason) => Err(anyhow!("Error {action}: {reason}")),
+        None => Ok(line),
+    }
+}
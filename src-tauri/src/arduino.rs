@@ -1,22 +1,347 @@
-use serialport::{available_ports, SerialPortInfo};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serialport::{SerialPortInfo, SerialPortType, UsbPortInfo};
+use tauri::{AppHandle, Manager, State};
+
+use crate::collect_measurements::read_line_after_marker;
+use crate::device_profiles::DeviceProfileStore;
+use crate::serial_transport::{PortBusy, SerialTransport};
+use crate::sessions::SessionManager;
+
+/// Boards in deep sleep don't answer, so don't hold the port for long
+const PROBE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// How long to wait before probing again a board that didn't answer
+const PROBE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+const CONFIG_FILE_NAME: &str = "arduino_identification.json";
+
+/// A USB vendor and product id pair, `pid: None` matches every product of the vendor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: Option<u16>,
+}
+
+impl UsbId {
+    fn matches(&self, usb_port: &UsbPortInfo) -> bool {
+        let pid_matches = match self.pid {
+            Some(pid) => pid == usb_port.pid,
+            None => true,
+        };
+        self.vid == usb_port.vid && pid_matches
+    }
+}
+
+/// How to tell which serial ports are BPV devices
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ArduinoIdentification {
+    /// Ports with these USB ids are considered Arduinos, even if they don't report a product string
+    pub usb_ids: Vec<UsbId>,
+    /// Whether to ask the board for its serial number over the port when its USB adapter doesn't report it
+    pub probe_serial_number: bool,
+}
+
+impl Default for ArduinoIdentification {
+    fn default() -> Self {
+        Self {
+            usb_ids: vec![
+                // Arduino
+                UsbId {
+                    vid: 0x2341,
+                    pid: None,
+                },
+                // Arduino.org
+                UsbId {
+                    vid: 0x2A03,
+                    pid: None,
+                },
+                // CH340
+                UsbId {
+                    vid: 0x1A86,
+                    pid: Some(0x7523),
+                },
+            ],
+            probe_serial_number: true,
+        }
+    }
+}
+
+/// A serial port identified as an Arduino
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConnectedArduino {
+    #[serde(flatten)]
+    pub serial_port_info: SerialPortInfo,
+    /// The serial number of the board, `None` if it couldn't be read
+    pub arduino_serial_number: Option<String>,
+}
+
+/// Keeps the identification config and the serial numbers already read from each port
+#[derive(Default)]
+pub struct ArduinoIdentifier {
+    identification: Mutex<ArduinoIdentification>,
+    /// Probing opens the port, so only do it once per connected port unless the board didn't answer
+    probed_serial_numbers: Mutex<HashMap<String, Probe>>,
+}
+
+struct Probe {
+    serial_number: Option<String>,
+    probed_at: Instant,
+}
+
+impl ArduinoIdentifier {
+    /// Loads the identification config from the app config dir, falling back to the default one
+    pub fn load(app: &AppHandle) -> Self {
+        let identification = match config_path(app).and_then(|path| {
+            let contents = std::fs::read_to_string(path)?;
+            Ok(serde_json::from_str(&contents)?)
+        }) {
+            Ok(identification) => identification,
+            Err(err) => {
+                log::info!("Using the default Arduino identification: {err:?}");
+                ArduinoIdentification::default()
+            }
+        };
+
+        Self::new(identification)
+    }
+
+    pub fn new(identification: ArduinoIdentification) -> Self {
+        Self {
+            identification: Mutex::new(identification),
+            probed_serial_numbers: Mutex::default(),
+        }
+    }
+
+    pub fn identification(&self) -> ArduinoIdentification {
+        self.identification
+            .lock()
+            .expect("Arduino identification poisoned")
+            .clone()
+    }
+
+    fn set_identification(&self, identification: ArduinoIdentification) {
+        *self
+            .identification
+            .lock()
+            .expect("Arduino identification poisoned") = identification;
+        self.probed_serial_numbers().clear();
+    }
+
+    fn probed_serial_numbers(&self) -> MutexGuard<'_, HashMap<String, Probe>> {
+        self.probed_serial_numbers
+            .lock()
+            .expect("Probed serial numbers poisoned")
+    }
+}
+
+#[tauri::command]
+pub fn list_connected_arduinos(
+    sessions: State<'_, SessionManager>,
+    arduino_identifier: State<'_, ArduinoIdentifier>,
+    device_profiles: State<'_, DeviceProfileStore>,
+) -> Result<Vec<ConnectedArduino>, String> {
+    internal_list_connected_arduinos(
+        &*sessions,
+        &arduino_identifier,
+        &device_profiles.probe_baud_rates(),
+    )
+    .map_err(|err| err.to_string())
+}
 
 #[tauri::command]
-pub fn list_connected_arduinos() -> Result<Vec<SerialPortInfo>, String> {
-    internal_list_connected_arduinos().map_err(|err| err.to_string())
+pub fn get_arduino_identification(
+    arduino_identifier: State<'_, ArduinoIdentifier>,
+) -> ArduinoIdentification {
+    arduino_identifier.identification()
 }
-fn internal_list_connected_arduinos() -> anyhow::Result<Vec<SerialPortInfo>> {
-    let available_ports = available_ports()?;
 
-    let connected_arduinos: Vec<SerialPortInfo> = available_ports
+#[tauri::command]
+pub fn set_arduino_identification(
+    app: AppHandle,
+    arduino_identifier: State<'_, ArduinoIdentifier>,
+    identification: ArduinoIdentification,
+) -> Result<(), String> {
+    let save = || -> anyhow::Result<()> {
+        let path = config_path(&app)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&identification)?)?;
+        Ok(())
+    };
+    save().map_err(|err| err.to_string())?;
+
+    arduino_identifier.set_identification(identification);
+    Ok(())
+}
+
+/// Boards whose USB adapter doesn't report a serial number are probed at each of `probe_baud_rates` until one answers
+pub fn internal_list_connected_arduinos(
+    transport: &dyn SerialTransport,
+    arduino_identifier: &ArduinoIdentifier,
+    probe_baud_rates: &[u32],
+) -> anyhow::Result<Vec<ConnectedArduino>> {
+    let identification = arduino_identifier.identification();
+    let available_ports = transport.available_ports()?;
+
+    let arduino_ports: Vec<(SerialPortInfo, Option<String>)> = available_ports
+        .iter()
+        .filter_map(|port| {
+            let SerialPortType::UsbPort(usb_port) = &port.port_type else {
+                return None;
+            };
+            if !is_arduino(usb_port, &identification) {
+                return None;
+            }
+            Some((port.clone(), usb_port.serial_number.clone()))
+        })
+        .collect();
+
+    let ports_to_probe: Vec<String> = {
+        let mut probed_serial_numbers = arduino_identifier.probed_serial_numbers();
+        // Forget the ports that were disconnected, another board may be plugged in with the same name
+        probed_serial_numbers.retain(|port_name, _| {
+            available_ports
+                .iter()
+                .any(|port| &port.port_name == port_name)
+        });
+
+        arduino_ports
+            .iter()
+            .filter(|(_, usb_serial_number)| {
+                usb_serial_number.is_none() && identification.probe_serial_number
+            })
+            .map(|(port, _)| port.port_name.clone())
+            .filter(|port_name| match probed_serial_numbers.get(port_name) {
+                None => true,
+                Some(probe) => {
+                    probe.serial_number.is_none()
+                        && probe.probed_at.elapsed() >= PROBE_RETRY_INTERVAL
+                }
+            })
+            .collect()
+    };
+
+    // Probing opens the port, so it's done without holding the lock
+    for port_name in ports_to_probe {
+        if let Some(probe) = probe_serial_number(transport, &port_name, probe_baud_rates) {
+            arduino_identifier
+                .probed_serial_numbers()
+                .insert(port_name, probe);
+        }
+    }
+
+    let probed_serial_numbers = arduino_identifier.probed_serial_numbers();
+    let connected_arduinos: Vec<ConnectedArduino> = arduino_ports
         .into_iter()
-        .filter(|port| match &port.port_type {
-            serialport::SerialPortType::UsbPort(usb_port) => match &usb_port.product {
-                Some(product) => product.contains("Arduino"),
-                _ => false,
-            },
-            _ => false,
+        .map(|(port, usb_serial_number)| {
+            let arduino_serial_number =
+                match (usb_serial_number, identification.probe_serial_number) {
+                    (Some(serial_number), _) => Some(serial_number),
+                    (None, false) => None,
+                    (None, true) => probed_serial_numbers
+                        .get(&port.port_name)
+                        .and_then(|probe| probe.serial_number.clone()),
+                };
+
+            ConnectedArduino {
+                serial_port_info: port,
+                arduino_serial_number,
+            }
         })
         .collect();
 
     Ok(connected_arduinos)
 }
+
+/// The serial number of the board connected to the given port, read from its USB adapter or probed
+pub fn arduino_serial_number_for_port(
+    transport: &dyn SerialTransport,
+    arduino_identifier: &ArduinoIdentifier,
+    probe_baud_rates: &[u32],
+    port_name: &str,
+) -> anyhow::Result<Option<String>> {
+    Ok(
+        internal_list_connected_arduinos(transport, arduino_identifier, probe_baud_rates)?
+            .into_iter()
+            .find(|arduino| arduino.serial_port_info.port_name == port_name)
+            .and_then(|arduino| arduino.arduino_serial_number),
+    )
+}
+
+/// Same as `arduino_serial_number_for_port`, with the serial transport and identification of the app
+///
+/// Listing the boards may probe them, so commands resolve the serial number once and pass it along
+pub fn connected_arduino_serial_number(
+    app: &AppHandle,
+    port_name: &str,
+) -> anyhow::Result<Option<String>> {
+    let sessions = app.state::<SessionManager>();
+    let arduino_identifier = app.state::<ArduinoIdentifier>();
+    let device_profiles = app.state::<DeviceProfileStore>();
+    arduino_serial_number_for_port(
+        &*sessions,
+        &arduino_identifier,
+        &device_profiles.probe_baud_rates(),
+        port_name,
+    )
+}
+
+fn is_arduino(usb_port: &UsbPortInfo, identification: &ArduinoIdentification) -> bool {
+    let product_is_arduino = usb_port
+        .product
+        .as_ref()
+        .is_some_and(|product| product.contains("Arduino"));
+
+    product_is_arduino
+        || identification
+            .usb_ids
+            .iter()
+            .any(|usb_id| usb_id.matches(usb_port))
+}
+
+/// Asks the board for its serial number, at each baud rate until it answers
+///
+/// Returns `None` if another command is using the port, to probe it again the next time the boards are listed
+fn probe_serial_number(
+    transport: &dyn SerialTransport,
+    port_name: &str,
+    baud_rates: &[u32],
+) -> Option<Probe> {
+    let probe = |baud_rate: u32| -> anyhow::Result<String> {
+        let mut port = transport.try_open(port_name, baud_rate, PROBE_TIMEOUT)?;
+        port.write_all(b"n")?;
+        read_line_after_marker(port.as_mut(), "BEGIN_N")
+    };
+
+    let mut serial_number = None;
+    for &baud_rate in baud_rates {
+        match probe(baud_rate) {
+            Ok(probed) if !probed.is_empty() => {
+                serial_number = Some(probed);
+                break;
+            }
+            Ok(_) => {}
+            Err(err) if err.downcast_ref::<PortBusy>().is_some() => return None,
+            Err(err) => {
+                log::info!(
+                    "Could not read the serial number of the board at {port_name} at {baud_rate} baud: {err:?}"
+                );
+            }
+        }
+    }
+
+    Some(Probe {
+        serial_number,
+        probed_at: Instant::now(),
+    })
+}
+
+fn config_path(app: &AppHandle) -> anyhow::Result<PathBuf> {
+    Ok(app.path().app_config_dir()?.join(CONFIG_FILE_NAME))
+}
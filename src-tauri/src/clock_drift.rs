@@ -0,0 +1,225 @@
+use anyhow::anyhow;
+use chrono::{NaiveDateTime, Utc};
+use holochain_types::prelude::Timestamp;
+use living_power_integrity::{measurement_collection::Measurement, ClockOffset};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::collect_measurements::{is_timeout, read_line_after_marker};
+use crate::device_profiles::DeviceProfile;
+use crate::rtc_timezone::{from_rtc_year, RtcTimezone};
+use crate::serial_transport::{SerialConnection, SerialTransport};
+use crate::zome_calls::call_living_power_zome;
+
+/// The device clock has second precision, so clock offsets read closer than this don't tell its drift rate
+const MIN_DRIFT_SPAN: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How far beyond the recorded clock offsets the drift rate is extrapolated
+const MAX_EXTRAPOLATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Asks the device for the current time of its real time clock
+///
+/// Returns `None` if the firmware doesn't support reading the clock
+///
+/// Firmware that predates the 't' command never answers it, so this only waits for the command timeout of the profile
+pub fn read_device_clock(
+    port: &mut dyn SerialConnection,
+    profile: &DeviceProfile,
+    timezone: &RtcTimezone,
+) -> anyhow::Result<Option<ClockOffset>> {
+    if !profile.supports_framed_protocol() {
+        return Ok(None);
+    }
+    port.set_timeout(profile.command_timeout())?;
+    port.write_all(b"t")?;
+
+    match read_line_after_marker(port, "BEGIN_T") {
+        Ok(line) => Ok(Some(parse_device_clock(&line, timezone)?)),
+        Err(err) if is_timeout(&err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Parses the time sent by the device after "BEGIN_T", comparing it with the current time of this computer
+pub(crate) fn parse_device_clock(
+    line: &str,
+    timezone: &RtcTimezone,
+) -> anyhow::Result<ClockOffset> {
+    let host_time = Timestamp::from_micros(Utc::now().timestamp_micros());
+    let date_time = NaiveDateTime::parse_from_str(line, "%Y-%m-%d,%H:%M:%S")
+        .map_err(|_| anyhow!("Invalid device time \"{line}\""))?;
+
+    Ok(ClockOffset {
+        device_time: timezone.to_timestamp(&from_rtc_year(date_time))?,
+        host_time,
+        clock_set: false,
+    })
+}
+
+pub fn internal_read_device_clock(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    timezone: &RtcTimezone,
+) -> anyhow::Result<Option<ClockOffset>> {
+    let mut port = transport.open(&port_name, profile.baud_rate, profile.command_timeout())?;
+    read_device_clock(port.as_mut(), profile, timezone)
+}
+
+/// Reads the clock of the device connected to the given port, and records its offset in the DHT
+pub async fn record_clock_offset(
+    app: &AppHandle,
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    arduino_serial_number: String,
+    timezone: &RtcTimezone,
+) -> anyhow::Result<Option<ClockOffset>> {
+    let Some(clock_offset) = internal_read_device_clock(transport, port_name, profile, timezone)?
+    else {
+        return Ok(None);
+    };
+    save_clock_offset(app, arduino_serial_number, clock_offset.clone()).await?;
+
+    Ok(Some(clock_offset))
+}
+
+pub async fn save_clock_offset(
+    app: &AppHandle,
+    arduino_serial_number: String,
+    clock_offset: ClockOffset,
+) -> anyhow::Result<()> {
+    call_living_power_zome(
+        app,
+        "add_clock_offset",
+        AddClockOffsetInput {
+            arduino_serial_number,
+            clock_offset,
+        },
+    )
+    .await
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AddClockOffsetInput {
+    arduino_serial_number: String,
+    clock_offset: ClockOffset,
+}
+
+#[tauri::command]
+pub async fn correct_measurements_clock_drift(
+    app: AppHandle,
+    arduino_serial_number: String,
+    measurements: Vec<Measurement>,
+) -> Result<Vec<Measurement>, String> {
+    let clock_offsets: Vec<ClockOffset> =
+        call_living_power_zome(&app, "get_clock_offsets", arduino_serial_number)
+            .await
+            .map_err(|err| err.to_string())?;
+    Ok(correct_clock_drift(measurements, &clock_offsets))
+}
+
+/// Moves the timestamps of the measurements to the real time they were taken at,
+/// assuming the device clock drifts linearly between the recorded clock offsets
+///
+/// The timestamps read from the device are kept in `raw_timestamp`, so correcting twice has no further effect
+pub fn correct_clock_drift(
+    measurements: Vec<Measurement>,
+    clock_offsets: &[ClockOffset],
+) -> Vec<Measurement> {
+    let segments = clock_segments(clock_offsets);
+
+    measurements
+        .into_iter()
+        .map(|mut measurement| {
+            let raw_timestamp = measurement.raw_timestamp.unwrap_or(measurement.timestamp);
+            let offset = segment_at(raw_timestamp, &segments)
+                .and_then(|segment| offset_at(raw_timestamp, segment));
+            if let Some(offset) = offset {
+                measurement.timestamp = Timestamp::from_micros(raw_timestamp.as_micros() - offset);
+                measurement.raw_timestamp = Some(raw_timestamp);
+            }
+            measurement
+        })
+        .collect()
+}
+
+/// Splits the clock offsets at every time the device clock was set, as its drift starts over from there
+///
+/// Offsets read too soon after the previous one of their segment are left out
+fn clock_segments(clock_offsets: &[ClockOffset]) -> Vec<Vec<ClockOffset>> {
+    let mut clock_offsets = clock_offsets.to_vec();
+    clock_offsets.sort_by_key(|clock_offset| clock_offset.host_time);
+
+    let mut segments: Vec<Vec<ClockOffset>> = vec![];
+    for clock_offset in clock_offsets {
+        match segments.last_mut() {
+            Some(segment) if !clock_offset.clock_set => {
+                let previous = segment.last().expect("Segments are never empty");
+                let span = clock_offset.host_time.as_micros() - previous.host_time.as_micros();
+                if span >= MIN_DRIFT_SPAN.as_micros() as i64 {
+                    segment.push(clock_offset);
+                }
+            }
+            _ => segments.push(vec![clock_offset]),
+        }
+    }
+    segments
+}
+
+/// The segment of clock offsets in effect when the device clock showed the given time
+///
+/// After the clock is set back it shows some times twice, those are taken as being from the latest segment
+fn segment_at(device_time: Timestamp, segments: &[Vec<ClockOffset>]) -> Option<&[ClockOffset]> {
+    let starts_before = |index: usize| index == 0 || segments[index][0].device_time <= device_time;
+    let ends_after = |index: usize| match segments.get(index + 1) {
+        None => true,
+        Some(next) => {
+            // What the clock showed right before it was set again
+            let last = segments[index].last().expect("Segments are never empty");
+            let end = next[0].host_time.as_micros() + last.offset_micros();
+            device_time.as_micros() < end
+        }
+    };
+
+    let index = (0..segments.len())
+        .rev()
+        .find(|index| starts_before(*index) && ends_after(*index))
+        .or_else(|| {
+            (0..segments.len())
+                .rev()
+                .find(|index| starts_before(*index))
+        })?;
+    Some(&segments[index])
+}
+
+/// Interpolates the offset of the device clock at the given device time, in microseconds
+///
+/// Outside of the recorded range the drift rate of the closest two offsets is extrapolated up to `MAX_EXTRAPOLATION`,
+/// and with a single recorded offset it's taken as constant
+fn offset_at(device_time: Timestamp, clock_offsets: &[ClockOffset]) -> Option<i64> {
+    let (before, after) = match clock_offsets {
+        [] => return None,
+        [clock_offset] => return Some(clock_offset.offset_micros()),
+        _ => {
+            let next = clock_offsets
+                .iter()
+                .position(|clock_offset| clock_offset.device_time > device_time)
+                .unwrap_or(clock_offsets.len())
+                .clamp(1, clock_offsets.len() - 1);
+            (&clock_offsets[next - 1], &clock_offsets[next])
+        }
+    };
+
+    let span = (after.device_time.as_micros() - before.device_time.as_micros()) as i128;
+    if span <= 0 {
+        return Some(before.offset_micros());
+    }
+    let max_extrapolation = MAX_EXTRAPOLATION.as_micros() as i128;
+    let elapsed = ((device_time.as_micros() - before.device_time.as_micros()) as i128)
+        .clamp(-max_extrapolation, span + max_extrapolation);
+    let drift = (after.offset_micros() - before.offset_micros()) as i128;
+
+    Some(before.offset_micros() + (drift * elapsed / span) as i64)
+}
@@ -1,168 +1,711 @@
 use anyhow::anyhow;
-use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
 use holochain_types::prelude::Timestamp;
-use living_power_integrity::measurement_collection::Measurement;
-use once_cell::sync::Lazy;
-use regex::Regex;
-use std::time::Duration;
+use living_power_integrity::{measurement_collection::Measurement, ClockOffset};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
 
-static MEASUREMENT_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(
-            r"(?<year>\d\d\d\d)-(?<month>\d\d)-(?<day>\d\d),(?<hours>\d\d):(?<minutes>\d\d):(?<seconds>\d\d),(?<temperature>[\d\.]+),(?<humidity>[\d\.]+),(?<lightlevel>[\d\.]+),(?<voltage>[\d\.]+)\s*$",
-    ).unwrap()
-});
+use crate::arduino::connected_arduino_serial_number;
+use crate::clock_drift::{internal_read_device_clock, read_device_clock, record_clock_offset};
+use crate::collection_progress::{ActiveCollections, CollectionControl, CollectionProgress};
+use crate::device_profiles::{DeviceProfile, DeviceProfileStore};
+use crate::frame::{Crc32, FrameDecoder, FrameKind};
+use crate::measurements_parser::{line_to_measurement, MeasurementsParser, ParseReport};
+use crate::rtc_timezone::{
+    format_rtc_date_time, rtc_timezone_for_device, rtc_timezone_for_serial_number, RtcTimezone,
+};
+use crate::serial_transport::{SerialConnection, SerialTransport};
+use crate::sessions::SessionManager;
+use crate::zome_calls::call_living_power_zome;
+
+/// How many times an interrupted transfer is resumed before giving up
+const MAX_RESUME_ATTEMPTS: u32 = 3;
+
+/// Wait before the first resume attempt, doubled for each following one
+const RESUME_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Minimum time between two progress reports
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Length of the date and time at the start of the rows, like "0024-05-01,10:00:00"
+const DEVICE_DATE_TIME_LEN: usize = 19;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CollectedMeasurements {
+    pub measurements: Vec<Measurement>,
+    pub report: ParseReport,
+    /// Whether the collection was cancelled before the transfer finished
+    pub cancelled: bool,
+    /// Whether the transfer was interrupted and couldn't be resumed, so only part of the measurements were collected
+    pub incomplete: bool,
+    /// CRC-32 of all the bytes received, to compare with the data file on the device
+    pub checksum: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LastMeasurement {
+    pub measurement: Option<Measurement>,
+    /// `None` if the firmware doesn't support reading its clock
+    pub clock_offset: Option<ClockOffset>,
+}
 
 #[tauri::command]
-pub async fn get_last_measurement(port_name: String) -> Result<Option<Measurement>, String> {
-    internal_get_last_measurement(port_name).map_err(|err| err.to_string())
+pub async fn get_last_measurement(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    device_profiles: State<'_, DeviceProfileStore>,
+    port_name: String,
+) -> Result<LastMeasurement, String> {
+    let arduino_serial_number =
+        connected_arduino_serial_number(&app, &port_name).map_err(|err| err.to_string())?;
+    let profile = device_profiles.profile_for(arduino_serial_number.as_deref());
+    let timezone = rtc_timezone_for_serial_number(&app, arduino_serial_number)
+        .await
+        .map_err(|err| err.to_string())?;
+    internal_get_last_measurement(&*sessions, port_name, &profile, &timezone)
+        .map_err(|err| err.to_string())
+}
+
+pub fn internal_get_last_measurement(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    timezone: &RtcTimezone,
+) -> anyhow::Result<LastMeasurement> {
+    let mut port = transport.open(&port_name, profile.baud_rate, profile.response_timeout())?;
+
+    port.write_all(b"l")?;
+
+    let mut received = read_until_marker(port.as_mut(), "BEGIN_L")?;
+    while !received.contains(&b'\n') {
+        let mut read_buffer: Vec<u8> = vec![0; 4096];
+        let n = port.read(&mut read_buffer)?;
+        received.extend_from_slice(&read_buffer[..n]);
+    }
+
+    let received = String::from_utf8(received)?;
+    // Older firmware prints the marker twice
+    let line = received
+        .trim_start_matches("BEGIN_L")
+        .lines()
+        .next()
+        .unwrap_or("")
+        .trim();
+
+    let measurement = match line.contains("None") {
+        true => None,
+        false => Some(line_to_measurement(line, timezone)?),
+    };
+    let clock_offset = read_device_clock(port.as_mut(), profile, timezone)?;
+
+    Ok(LastMeasurement {
+        measurement,
+        clock_offset,
+    })
+}
+
+#[tauri::command]
+pub async fn collect_measurements(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    active_collections: State<'_, ActiveCollections>,
+    device_profiles: State<'_, DeviceProfileStore>,
+    port_name: String,
+) -> Result<CollectedMeasurements, String> {
+    let arduino_serial_number =
+        connected_arduino_serial_number(&app, &port_name).map_err(|err| err.to_string())?;
+    let profile = device_profiles.profile_for(arduino_serial_number.as_deref());
+    let timezone = rtc_timezone_for_serial_number(&app, arduino_serial_number)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let control = active_collections.start(&app, &port_name);
+    let result =
+        internal_collect_measurements(&*sessions, port_name.clone(), &profile, &timezone, &control);
+    active_collections.finish(&port_name);
+    result.map_err(|err| err.to_string())
+}
+
+pub fn internal_collect_measurements(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    timezone: &RtcTimezone,
+    control: &CollectionControl,
+) -> anyhow::Result<CollectedMeasurements> {
+    let transfer = Transfer::new(port_name, profile, timezone, control);
+    collect_with_resume(transport, transfer)
+}
+
+#[tauri::command]
+pub async fn collect_new_measurements(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    active_collections: State<'_, ActiveCollections>,
+    device_profiles: State<'_, DeviceProfileStore>,
+    port_name: String,
+    arduino_serial_number: String,
+) -> Result<CollectedMeasurements, String> {
+    let last_timestamp: Option<Timestamp> = call_living_power_zome(
+        &app,
+        "get_last_measurement_timestamp",
+        arduino_serial_number.clone(),
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+    let timezone = rtc_timezone_for_device(&app, arduino_serial_number.clone())
+        .await
+        .map_err(|err| err.to_string())?;
+    let profile = device_profiles.profile_for(Some(&arduino_serial_number));
+
+    let control = active_collections.start(&app, &port_name);
+    let result = match last_timestamp {
+        Some(since) => internal_collect_measurements_since(
+            &*sessions,
+            port_name.clone(),
+            &profile,
+            since,
+            &timezone,
+            &control,
+        ),
+        None => internal_collect_measurements(
+            &*sessions,
+            port_name.clone(),
+            &profile,
+            &timezone,
+            &control,
+        ),
+    };
+    active_collections.finish(&port_name);
+    let collected = result.map_err(|err| err.to_string())?;
+
+    // Keep track of the drift of the device clock, but don't lose the collected measurements over it
+    if let Err(err) = record_clock_offset(
+        &app,
+        &*sessions,
+        port_name,
+        &profile,
+        arduino_serial_number,
+        &timezone,
+    )
+    .await
+    {
+        log::warn!("Failed to record the clock offset of the device: {err:?}");
+    }
+
+    Ok(collected)
 }
-fn internal_get_last_measurement(port_name: String) -> anyhow::Result<Option<Measurement>> {
-    let baud_rate: u32 = 9600;
 
-    let mut port = serialport::new(port_name, baud_rate)
-        .timeout(Duration::from_millis(5000))
-        .open()?;
+/// Collects only the measurements logged after the given timestamp
+///
+/// Falls back to collecting the whole data file if the firmware doesn't support the 's' command,
+/// or if the device clock reads earlier than the timestamp
+pub fn internal_collect_measurements_since(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    since: Timestamp,
+    timezone: &RtcTimezone,
+    control: &CollectionControl,
+) -> anyhow::Result<CollectedMeasurements> {
+    // After its clock was reset, the device logs rows dated before the ones already collected
+    let device_clock = internal_read_device_clock(transport, port_name.clone(), profile, timezone)?;
+    if let Some(clock_offset) = device_clock.filter(|clock_offset| clock_offset.device_time < since)
+    {
+        log::warn!(
+            "The clock of the device in {port_name} reads {:?}, before the last collected measurement, collecting all measurements",
+            clock_offset.device_time
+        );
+        return internal_collect_measurements(transport, port_name, profile, timezone, control);
+    }
 
-    let mut write_buffer: Vec<u8> = vec![0; 1];
-    write_buffer[0] = b'l';
+    let mut transfer = Transfer::new(port_name, profile, timezone, control);
+    transfer.since = Some(format_device_datetime(since, timezone)?);
+    let mut collected = collect_with_resume(transport, transfer)?;
 
-    let n = 1; // How many bytes to write to serial port.
+    // The device compares timestamps with second precision, so filter again here
+    collected
+        .measurements
+        .retain(|measurement| measurement.timestamp > since);
 
-    // Write to serial port
-    port.write(&write_buffer[..n])?; // blocks
+    Ok(collected)
+}
 
+/// Runs the transfer, reconnecting and resuming it from the last fully received row if the connection breaks
+///
+/// If it can't be resumed, the rows received so far are kept and the collection is marked as incomplete
+fn collect_with_resume(
+    transport: &dyn SerialTransport,
+    mut transfer: Transfer,
+) -> anyhow::Result<CollectedMeasurements> {
+    let mut attempts: u32 = 0;
     loop {
-        let mut read_buffer: Vec<u8> = vec![0; 7];
+        let err = match run_transfer(transport, &mut transfer) {
+            Ok(true) => return transfer.finish(Outcome::Completed),
+            Ok(false) => return transfer.finish(Outcome::Cancelled),
+            Err(err) => err,
+        };
 
-        let _n = port.read(&mut read_buffer)?;
-        let buf = String::from_utf8(read_buffer)?;
-        if buf.contains("BEGIN_L") {
-            break;
-        } else {
-            std::thread::sleep(Duration::from_millis(10));
+        if !is_io_error(&err) || transfer.control.is_cancelled() {
+            return Err(err);
         }
+        if attempts == MAX_RESUME_ATTEMPTS {
+            if transfer.parser.rows() == 0 {
+                return Err(err);
+            }
+            log::error!(
+                "Failed to resume the collection from {}, keeping the {} rows received: {err:?}",
+                transfer.port_name,
+                transfer.parser.rows()
+            );
+            return transfer.finish(Outcome::Interrupted);
+        }
+
+        log::warn!(
+            "Collection from {} interrupted, resuming it ({}/{MAX_RESUME_ATTEMPTS}): {err:?}",
+            transfer.port_name,
+            attempts + 1
+        );
+        std::thread::sleep(RESUME_BACKOFF * 2u32.pow(attempts));
+        attempts += 1;
+        transfer.discard_partial_row();
     }
+}
 
-    let mut result = String::from("");
-    let mut end = false;
-    while !end {
-        let mut read_buffer: Vec<u8> = vec![0; 4096];
+/// Opens the port and receives the data file, or the rows after the resume point of the transfer
+///
+/// Returns false if the transfer was cancelled before it completed
+fn run_transfer(transport: &dyn SerialTransport, transfer: &mut Transfer) -> anyhow::Result<bool> {
+    let profile = transfer.profile;
+    let mut port = transport.open(
+        &transfer.port_name,
+        profile.baud_rate,
+        profile.command_timeout(),
+    )?;
+
+    if let (true, Some(since)) = (transfer.supports_since, transfer.resume_point()) {
+        let command = profile.command('s', &since);
+        port.write_all(command.as_bytes())?;
+
+        match read_until_marker(port.as_mut(), "BEGIN_S") {
+            Ok(leftover) => {
+                port.set_timeout(profile.transfer_timeout())?;
+                return read_framed_transfer(port.as_mut(), leftover, transfer);
+            }
+            Err(err) if is_timeout(&err) => {
+                log::warn!(
+                    "Device does not support incremental collection, falling back to collecting all measurements"
+                );
+                transfer.supports_since = false;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    // Without a resume point, everything is received again from the start
+    transfer.restart();
+
+    if !transfer.supports_framed_protocol {
+        port.set_timeout(profile.transfer_timeout())?;
+        port.write_all(b"c")?;
+        let leftover = read_until_marker(port.as_mut(), "BEGIN_C")?;
+        return read_legacy_transfer(port.as_mut(), leftover, transfer);
+    }
+
+    // Firmware that supports the framed protocol answers 'f' with "BEGIN_F",
+    // older firmware ignores it and we fall back to the legacy 'c' command
+    port.write_all(b"f")?;
+
+    match read_until_marker(port.as_mut(), "BEGIN_F") {
+        Ok(leftover) => {
+            port.set_timeout(profile.transfer_timeout())?;
+            read_framed_transfer(port.as_mut(), leftover, transfer)
+        }
+        Err(err) if is_timeout(&err) => {
+            log::warn!(
+                "Device did not answer the framed protocol, falling back to the legacy transfer"
+            );
+            transfer.supports_since = false;
+            transfer.supports_framed_protocol = false;
+            port.set_timeout(profile.transfer_timeout())?;
+            port.write_all(b"c")?;
+            let leftover = read_until_marker(port.as_mut(), "BEGIN_C")?;
+            read_legacy_transfer(port.as_mut(), leftover, transfer)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Formats the timestamp the same way `printSample` in `bpv.ino` writes it to the data file,
+/// as the device compares it with the rows as text
+fn format_device_datetime(timestamp: Timestamp, timezone: &RtcTimezone) -> anyhow::Result<String> {
+    let date_time = timezone.to_device_date_time(timestamp)?;
+    Ok(format_rtc_date_time(&date_time))
+}
+
+/// Whether the error comes from the connection to the device, as opposed to what it sent
+fn is_io_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some()
+        || err.downcast_ref::<serialport::Error>().is_some()
+}
 
+pub(crate) fn is_timeout(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|err| err.kind() == std::io::ErrorKind::TimedOut)
+}
+
+/// Reads from the port until the given marker is found, and returns the bytes that came after it
+pub(crate) fn read_until_marker(
+    port: &mut dyn SerialConnection,
+    marker: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let marker = marker.as_bytes();
+    let mut received: Vec<u8> = vec![];
+
+    loop {
+        let mut read_buffer: Vec<u8> = vec![0; 64];
         let n = port.read(&mut read_buffer)?;
-        let buf = String::from_utf8(read_buffer)?;
-        if buf.contains("None") {
-            return Ok(None);
-        } else if buf.contains('\n') {
-            end = true;
+        received.extend_from_slice(&read_buffer[..n]);
+
+        if let Some(position) = received
+            .windows(marker.len())
+            .position(|window| window == marker)
+        {
+            return Ok(received.split_off(position + marker.len()));
         }
-        result.push_str(&buf[..n].trim());
     }
+}
 
-    let measurement = line_to_measurement(result.as_str())?;
-    Ok(Some(measurement))
+/// Reads the line that follows the given marker, without its line ending
+pub(crate) fn read_line_after_marker(
+    port: &mut dyn SerialConnection,
+    marker: &str,
+) -> anyhow::Result<String> {
+    let mut received = read_until_marker(port, marker)?;
+    while !received.contains(&b'\n') {
+        let mut read_buffer: Vec<u8> = vec![0; 64];
+        let n = port.read(&mut read_buffer)?;
+        received.extend_from_slice(&read_buffer[..n]);
+    }
+
+    let received = String::from_utf8(received)?;
+    Ok(received.lines().next().unwrap_or("").trim().to_string())
 }
 
-#[tauri::command]
-pub async fn collect_measurements(port_name: String) -> Result<Vec<Measurement>, String> {
-    internal_collect_measurements(port_name).map_err(|err| err.to_string())
+/// Parses the rows of a transfer as they arrive, and reports its progress
+struct Transfer<'a> {
+    port_name: String,
+    profile: &'a DeviceProfile,
+    control: &'a CollectionControl,
+    parser: MeasurementsParser,
+    started_at: Instant,
+    last_report: Instant,
+    total_bytes: Option<usize>,
+    bytes_received: usize,
+    /// Covers only complete rows, so that it still matches the data file after resuming
+    checksum: Crc32,
+    /// The bytes received after the last newline
+    partial_row: Vec<u8>,
+    /// The device date and time at the start of the last complete row received, parsed or rejected
+    last_row_date_time: Option<String>,
+    /// Only the rows logged after this device date and time are requested
+    since: Option<String>,
+    /// Start from the command set of the profile, and are cleared when the firmware doesn't answer
+    /// the corresponding command, so that it's not sent again when resuming
+    supports_since: bool,
+    supports_framed_protocol: bool,
+}
+
+enum Outcome {
+    Completed,
+    Cancelled,
+    /// The connection broke and the transfer couldn't be resumed
+    Interrupted,
 }
 
-fn internal_collect_measurements(port_name: String) -> anyhow::Result<Vec<Measurement>> {
-    let baud_rate: u32 = 9600;
+impl<'a> Transfer<'a> {
+    fn new(
+        port_name: String,
+        profile: &'a DeviceProfile,
+        timezone: &RtcTimezone,
+        control: &'a CollectionControl,
+    ) -> Self {
+        Self {
+            port_name,
+            profile,
+            control,
+            parser: MeasurementsParser::new(timezone.clone()),
+            started_at: Instant::now(),
+            last_report: Instant::now(),
+            total_bytes: None,
+            bytes_received: 0,
+            checksum: Crc32::new(),
+            partial_row: vec![],
+            last_row_date_time: None,
+            since: None,
+            supports_since: profile.supports_framed_protocol(),
+            supports_framed_protocol: profile.supports_framed_protocol(),
+        }
+    }
+
+    fn receive(&mut self, bytes: &[u8]) {
+        self.bytes_received += bytes.len();
+        self.partial_row.extend_from_slice(bytes);
+        if let Some(end) = self.partial_row.iter().rposition(|byte| *byte == b'\n') {
+            let complete_rows = &self.partial_row[..=end];
+            self.checksum.update(complete_rows);
+            // The device skips the lines that don't start with a digit when looking for the resume point
+            if let Some(row) = complete_rows
+                .rsplit(|byte| *byte == b'\n')
+                .find(|line| line.first().is_some_and(u8::is_ascii_digit))
+            {
+                let date_time = &row[..row.len().min(DEVICE_DATE_TIME_LEN)];
+                self.last_row_date_time =
+                    Some(String::from_utf8_lossy(date_time).trim().to_string());
+            }
+            self.partial_row.drain(..=end);
+        }
+        self.parser.push(bytes);
+
+        if self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.last_report = Instant::now();
+
+        let estimated_remaining_ms = match self.total_bytes {
+            Some(total_bytes) if self.bytes_received > 0 => {
+                let elapsed_ms = self.started_at.elapsed().as_millis() as u64;
+                let remaining_bytes = total_bytes.saturating_sub(self.bytes_received) as u64;
+                Some(elapsed_ms * remaining_bytes / self.bytes_received as u64)
+            }
+            _ => None,
+        };
+
+        self.control.report(CollectionProgress {
+            port_name: self.port_name.clone(),
+            bytes_received: self.bytes_received,
+            total_bytes: self.total_bytes,
+            rows_received: self.parser.rows(),
+            parse_errors: self.parser.parse_errors(),
+            estimated_remaining_ms,
+        });
+    }
+
+    /// The device date and time of the last fully received row, to request only the rows after it
+    ///
+    /// Rows the parser rejected count too, as they are already in the checksum and the byte count
+    fn resume_point(&self) -> Option<String> {
+        self.last_row_date_time
+            .clone()
+            .or_else(|| self.since.clone())
+    }
+
+    /// Drops the bytes of the row that was being received when the connection broke
+    fn discard_partial_row(&mut self) {
+        self.bytes_received -= self.partial_row.len();
+        self.partial_row.clear();
+        self.parser.discard_partial_line();
+    }
+
+    /// Forgets everything received so far, to receive the data file again from the start
+    fn restart(&mut self) {
+        self.parser = MeasurementsParser::new(self.parser.timezone().clone());
+        self.total_bytes = None;
+        self.bytes_received = 0;
+        self.checksum = Crc32::new();
+        self.partial_row.clear();
+        self.last_row_date_time = None;
+    }
 
-    let mut port = serialport::new(port_name, baud_rate)
-        .timeout(Duration::from_millis(50000))
-        .open()?;
+    /// Stops the transfer, keeping the rows received so far if it didn't complete
+    fn finish(mut self, outcome: Outcome) -> anyhow::Result<CollectedMeasurements> {
+        self.report();
 
-    let mut write_buffer: Vec<u8> = vec![0; 1];
-    write_buffer[0] = b'c';
+        let parsed = match outcome {
+            Outcome::Completed => {
+                self.checksum.update(&self.partial_row);
+                self.parser.finish()?
+            }
+            Outcome::Cancelled | Outcome::Interrupted => self.parser.finish_incomplete()?,
+        };
 
-    let n = 1; // How many bytes to write to serial port.
+        Ok(CollectedMeasurements {
+            measurements: parsed.measurements,
+            report: parsed.report,
+            cancelled: matches!(outcome, Outcome::Cancelled),
+            incomplete: matches!(outcome, Outcome::Interrupted),
+            checksum: self.checksum.finish(),
+        })
+    }
+}
 
-    // Write to serial port
-    port.write(&write_buffer[..n])?; // blocks
+/// Reads the data file sent with the legacy "BEGIN_C" ... "EndOfFile" framing
+///
+/// Returns false if the transfer was cancelled before it completed
+fn read_legacy_transfer(
+    port: &mut dyn SerialConnection,
+    leftover: Vec<u8>,
+    transfer: &mut Transfer,
+) -> anyhow::Result<bool> {
+    let marker = b"EndOfFile";
+    let mut pending = leftover;
 
     loop {
-        let mut read_buffer: Vec<u8> = vec![0; 7];
+        if let Some(position) = pending
+            .windows(marker.len())
+            .position(|window| window == marker)
+        {
+            transfer.receive(&pending[..position]);
+            return Ok(true);
+        }
+
+        // Hold back enough bytes to find the marker if it's split across two reads
+        let safe_len = pending.len().saturating_sub(marker.len() - 1);
+        transfer.receive(&pending[..safe_len]);
+        pending.drain(..safe_len);
 
-        let _n = port.read(&mut read_buffer)?;
-        let buf = String::from_utf8(read_buffer)?;
-        if buf.contains("BEGIN_C") {
-            break;
-        } else {
-            std::thread::sleep(Duration::from_millis(10));
+        if transfer.control.is_cancelled() {
+            drain_legacy_transfer(port, pending);
+            return Ok(false);
         }
+
+        let mut read_buffer: Vec<u8> = vec![0; 4096];
+        let n = port.read(&mut read_buffer)?;
+        pending.extend_from_slice(&read_buffer[..n]);
     }
+}
 
-    let mut result = String::from("");
+/// Reads and drops the rest of a cancelled legacy transfer, up to its "EndOfFile" marker
+///
+/// The device keeps sending the data file after the transfer is cancelled, and the connection
+/// is lent to the next command afterwards, so it must not be left halfway through it
+fn drain_legacy_transfer(port: &mut dyn SerialConnection, leftover: Vec<u8>) {
+    let marker = b"EndOfFile";
+    let mut pending = leftover;
 
-    let mut end = false;
-    while !end {
-        let mut read_buffer: Vec<u8> = vec![0; 4096];
+    loop {
+        if pending.windows(marker.len()).any(|window| window == marker) {
+            return;
+        }
+        pending.drain(..pending.len().saturating_sub(marker.len() - 1));
 
-        let n = port.read(&mut read_buffer)?;
-        let buf = String::from_utf8(read_buffer)?;
-        if buf.contains("EndOfFile") {
-            end = true;
+        let mut read_buffer: Vec<u8> = vec![0; 4096];
+        match port.read(&mut read_buffer) {
+            Ok(n) => pending.extend_from_slice(&read_buffer[..n]),
+            Err(err) => {
+                log::warn!("Failed to receive the rest of a cancelled transfer: {err:?}");
+                port.discard();
+                return;
+            }
         }
-        result.push_str(&buf[..n]);
     }
-
-    parse_csv_file_contents(result)
 }
 
-pub fn parse_csv_file_contents(contents: String) -> anyhow::Result<Vec<Measurement>> {
-    let split = contents.split('\n');
+/// Reads the data file sent as a sequence of checksummed frames
+///
+/// The transfer is rejected if any frame is lost or corrupted.
+/// Returns false if the transfer was cancelled before it completed
+fn read_framed_transfer(
+    port: &mut dyn SerialConnection,
+    leftover: Vec<u8>,
+    transfer: &mut Transfer,
+) -> anyhow::Result<bool> {
+    let mut decoder = FrameDecoder::new();
+    decoder.push(&leftover);
+
+    let mut expected_sequence: u16 = 0;
+    let mut began = false;
+    let mut data_frames: u32 = 0;
+    // When resuming, the device only sends what comes after the rows already received
+    let resumed_at = transfer.bytes_received;
 
-    let mut measurements: Vec<Measurement> = vec![];
-    for line in split {
-        // Check whether this line is the title row of the csv
-        if !line.contains("Date") {
-            match line_to_measurement(line) {
-                Ok(measurement) => measurements.push(measurement),
-                Err(err) => log::warn!("Error reading the measurement line \"{line}\": {err:?}"),
-            };
+    loop {
+        if transfer.control.is_cancelled() {
+            drain_framed_transfer(port, decoder);
+            return Ok(false);
+        }
+
+        let frame = match decoder.next_frame() {
+            Some(frame) => frame?,
+            None => {
+                let mut read_buffer: Vec<u8> = vec![0; 4096];
+                let n = port.read(&mut read_buffer)?;
+                decoder.push(&read_buffer[..n]);
+                continue;
+            }
+        };
+
+        if frame.sequence != expected_sequence {
+            return Err(anyhow!(
+                "Lost frame {expected_sequence} of the transfer, received frame {} instead",
+                frame.sequence
+            ));
+        }
+        expected_sequence = expected_sequence.wrapping_add(1);
+
+        match (began, frame.kind) {
+            (_, FrameKind::Error) => {
+                return Err(anyhow!(
+                    "The device aborted the transfer: {}",
+                    String::from_utf8_lossy(&frame.payload)
+                ))
+            }
+            (false, FrameKind::Begin) => {
+                began = true;
+                transfer.total_bytes = Some(resumed_at + read_u32(&frame.payload, 0)? as usize);
+            }
+            (false, kind) => return Err(anyhow!("Expected a Begin frame, got {kind:?}")),
+            (_, FrameKind::Data) => {
+                data_frames += 1;
+                transfer.receive(&frame.payload);
+            }
+            (_, FrameKind::End) => {
+                let sent_frames = read_u32(&frame.payload, 0)?;
+                let sent_bytes = read_u32(&frame.payload, 4)?;
+                let received_bytes = transfer.bytes_received - resumed_at;
+                if sent_frames != data_frames || sent_bytes as usize != received_bytes {
+                    return Err(anyhow!(
+                        "Incomplete transfer: the device sent {sent_frames} frames ({sent_bytes} bytes), but {data_frames} frames ({received_bytes} bytes) were received"
+                    ));
+                }
+                return Ok(true);
+            }
+            (_, FrameKind::Begin) => return Err(anyhow!("Unexpected Begin frame")),
         }
     }
+}
 
-    Ok(measurements)
+/// Reads and drops the frames of a cancelled transfer, up to its End frame
+///
+/// Same as `drain_legacy_transfer`, for the framed protocol
+fn drain_framed_transfer(port: &mut dyn SerialConnection, mut decoder: FrameDecoder) {
+    loop {
+        match decoder.next_frame() {
+            Some(Ok(frame)) if matches!(frame.kind, FrameKind::End | FrameKind::Error) => return,
+            // Corrupted frames don't matter anymore, only where the transfer ends
+            Some(_) => continue,
+            None => {
+                let mut read_buffer: Vec<u8> = vec![0; 4096];
+                match port.read(&mut read_buffer) {
+                    Ok(n) => decoder.push(&read_buffer[..n]),
+                    Err(err) => {
+                        log::warn!("Failed to receive the rest of a cancelled transfer: {err:?}");
+                        port.discard();
+                        return;
+                    }
+                }
+            }
+        }
+    }
 }
 
-fn line_to_measurement(line: &str) -> anyhow::Result<Measurement> {
-    let Some(caps) = MEASUREMENT_REGEX.captures(line) else {
-        return Err(anyhow!("Invalid measurement line"));
-    };
-    let date_time: NaiveDateTime = NaiveDate::from_ymd_opt(
-        caps["year"].parse::<i32>()?,
-        caps["month"].parse()?,
-        caps["day"].parse()?,
-    )
-    .unwrap()
-    .and_hms_opt(
-        caps["hours"].parse()?,
-        caps["minutes"].parse()?,
-        caps["seconds"].parse()?,
-    )
-    .unwrap();
-    let timestamp = Timestamp::from_micros(
-        Local
-            .from_local_datetime(&date_time)
-            .single()
-            .expect("Can't convert DateTime to local timezone")
-            .timestamp_micros(),
-    );
-
-    let temperature: f32 = caps["temperature"].parse()?;
-    let humidity: f32 = caps["humidity"].parse()?;
-    let lightlevel: f32 = caps["lightlevel"].parse()?;
-    let voltage: f32 = caps["voltage"].parse()?;
-
-    let measurement = Measurement {
-        timestamp,
-        humidity_percentage: (humidity * 1000.0) as u32,
-        temperature_celsius: (temperature * 1000.0) as u32,
-        light_level_lux: (lightlevel * 1000.0) as u32,
-        voltage_millivolts: (voltage * 1000.0) as u32,
-    };
-    Ok(measurement)
+/// Reads a little endian u32 at the given offset of a frame's payload
+fn read_u32(payload: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let bytes = payload
+        .get(offset..offset + 4)
+        .ok_or(anyhow!("Malformed frame payload"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
 }
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+pub const COLLECTION_PROGRESS_EVENT: &str = "collect-measurements-progress";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CollectionProgress {
+    pub port_name: String,
+    pub bytes_received: usize,
+    /// Only known when the device uses the framed protocol
+    pub total_bytes: Option<usize>,
+    pub rows_received: usize,
+    pub parse_errors: usize,
+    pub estimated_remaining_ms: Option<u64>,
+}
+
+/// Lets the caller of a collection follow its progress and cancel it
+#[derive(Clone, Default)]
+pub struct CollectionControl {
+    cancelled: Arc<AtomicBool>,
+    on_progress: Option<Arc<dyn Fn(CollectionProgress) + Send + Sync>>,
+}
+
+impl CollectionControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(CollectionProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+
+    /// Stops the collection at the next received chunk, keeping the rows parsed so far
+    ///
+    /// The rest of the transfer is still received and dropped, so that the device is done sending it
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn report(&self, progress: CollectionProgress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+    }
+}
+
+/// The collections that are currently in progress, by port name
+#[derive(Default)]
+pub struct ActiveCollections(Mutex<HashMap<String, CollectionControl>>);
+
+impl ActiveCollections {
+    /// Registers a new collection for the given port, emitting its progress as tauri events
+    pub fn start(&self, app: &AppHandle, port_name: &str) -> CollectionControl {
+        let app = app.clone();
+        let control = CollectionControl::new().on_progress(move |progress| {
+            if let Err(err) = app.emit(COLLECTION_PROGRESS_EVENT, progress) {
+                log::error!("Failed to emit collection progress: {err:?}");
+            }
+        });
+        self.lock().insert(port_name.to_string(), control.clone());
+        control
+    }
+
+    pub fn finish(&self, port_name: &str) {
+        self.lock().remove(port_name);
+    }
+
+    /// Returns whether there was a collection in progress for the given port
+    pub fn cancel(&self, port_name: &str) -> bool {
+        match self.lock().get(port_name) {
+            Some(control) => {
+                control.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CollectionControl>> {
+        self.0.lock().expect("Active collections poisoned")
+    }
+}
+
+#[tauri::command]
+pub fn cancel_collection(
+    active_collections: State<'_, ActiveCollections>,
+    port_name: String,
+) -> Result<(), String> {
+    if !active_collections.cancel(&port_name) {
+        return Err(format!(
+            "There is no collection in progress for {port_name}"
+        ));
+    }
+    Ok(())
+}
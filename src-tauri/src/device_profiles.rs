@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::arduino::connected_arduino_serial_number;
+
+const CONFIG_FILE_NAME: &str = "device_profiles.json";
+
+const DEFAULT_PROFILE_NAME: &str = "default";
+
+/// What terminates the commands that take an argument, like 's' or 'I'
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// The commands the firmware of the device understands
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandSet {
+    /// The framed protocol and the settings commands, falling back to the legacy commands if the device doesn't answer them
+    Framed,
+    /// Only 'c' and 'l', for firmware that predates the framed protocol, so that no time is lost waiting for the newer commands
+    Legacy,
+}
+
+/// How to talk to a BPV device over its serial port
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeviceProfile {
+    pub name: String,
+    pub baud_rate: u32,
+    /// How long to wait for the answer to a quick command, like reading a setting, before considering it unsupported
+    pub command_timeout_ms: u64,
+    /// How long to wait for the last measurement, which the device reads from the end of its data file
+    pub response_timeout_ms: u64,
+    /// How long to wait for each chunk of a data file transfer
+    pub transfer_timeout_ms: u64,
+    pub line_ending: LineEnding,
+    pub command_set: CommandSet,
+}
+
+impl Default for DeviceProfile {
+    fn default() -> Self {
+        Self {
+            name: String::from(DEFAULT_PROFILE_NAME),
+            baud_rate: 9600,
+            command_timeout_ms: 2000,
+            response_timeout_ms: 5000,
+            transfer_timeout_ms: 50000,
+            line_ending: LineEnding::Lf,
+            command_set: CommandSet::Framed,
+        }
+    }
+}
+
+impl DeviceProfile {
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_millis(self.command_timeout_ms)
+    }
+
+    pub fn response_timeout(&self) -> Duration {
+        Duration::from_millis(self.response_timeout_ms)
+    }
+
+    pub fn transfer_timeout(&self) -> Duration {
+        Duration::from_millis(self.transfer_timeout_ms)
+    }
+
+    /// Formats a command that takes an argument, terminated by the line ending of the profile
+    pub fn command(&self, command: char, argument: &str) -> String {
+        format!("{command}{argument}{}", self.line_ending.as_str())
+    }
+
+    pub fn supports_framed_protocol(&self) -> bool {
+        self.command_set == CommandSet::Framed
+    }
+}
+
+/// The device profiles, and which one to use for each device
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DeviceProfiles {
+    pub profiles: Vec<DeviceProfile>,
+    /// Name of the profile to use for each `arduino_serial_number`, the others use the "default" profile
+    pub assignments: BTreeMap<String, String>,
+}
+
+impl Default for DeviceProfiles {
+    fn default() -> Self {
+        Self {
+            profiles: vec![DeviceProfile::default()],
+            assignments: BTreeMap::new(),
+        }
+    }
+}
+
+impl DeviceProfiles {
+    /// The profile assigned to the given device, or the "default" profile if it has none
+    pub fn profile_for(&self, arduino_serial_number: Option<&str>) -> DeviceProfile {
+        let name = arduino_serial_number
+            .and_then(|serial_number| self.assignments.get(serial_number))
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_PROFILE_NAME);
+
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The baud rates to ask an unidentified board for its serial number at
+    ///
+    /// Its profile isn't known before its serial number is, so the one of the "default" profile comes first, then the ones of the other profiles
+    pub fn probe_baud_rates(&self) -> Vec<u32> {
+        let mut baud_rates = vec![self.profile_for(None).baud_rate];
+        for profile in &self.profiles {
+            if !baud_rates.contains(&profile.baud_rate) {
+                baud_rates.push(profile.baud_rate);
+            }
+        }
+        baud_rates
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for profile in &self.profiles {
+            if profile.baud_rate == 0 {
+                return Err(anyhow!(
+                    "The baud rate of the profile \"{}\" must be greater than 0",
+                    profile.name
+                ));
+            }
+            if self
+                .profiles
+                .iter()
+                .filter(|p| p.name == profile.name)
+                .count()
+                > 1
+            {
+                return Err(anyhow!(
+                    "There is more than one profile named \"{}\"",
+                    profile.name
+                ));
+            }
+        }
+        for (arduino_serial_number, name) in &self.assignments {
+            if !self.profiles.iter().any(|profile| &profile.name == name) {
+                return Err(anyhow!(
+                    "The device {arduino_serial_number} is assigned the unknown profile \"{name}\""
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the device profiles stored in the app config dir
+#[derive(Default)]
+pub struct DeviceProfileStore {
+    profiles: Mutex<DeviceProfiles>,
+}
+
+impl DeviceProfileStore {
+    /// Loads the device profiles from the app config dir, falling back to the default ones
+    pub fn load(app: &AppHandle) -> Self {
+        let profiles = match config_path(app).and_then(|path| {
+            let contents = std::fs::read_to_string(path)?;
+            let profiles: DeviceProfiles = serde_json::from_str(&contents)?;
+            profiles.validate()?;
+            Ok(profiles)
+        }) {
+            Ok(profiles) => profiles,
+            Err(err) => {
+                log::info!("Using the default device profiles: {err:?}");
+                DeviceProfiles::default()
+            }
+        };
+
+        Self::new(profiles)
+    }
+
+    pub fn new(profiles: DeviceProfiles) -> Self {
+        Self {
+            profiles: Mutex::new(profiles),
+        }
+    }
+
+    pub fn profiles(&self) -> DeviceProfiles {
+        self.profiles
+            .lock()
+            .expect("Device profiles poisoned")
+            .clone()
+    }
+
+    /// Same as `DeviceProfiles::profile_for`, with the current profiles
+    pub fn profile_for(&self, arduino_serial_number: Option<&str>) -> DeviceProfile {
+        self.profiles
+            .lock()
+            .expect("Device profiles poisoned")
+            .profile_for(arduino_serial_number)
+    }
+
+    /// Same as `DeviceProfiles::probe_baud_rates`, with the current profiles
+    pub fn probe_baud_rates(&self) -> Vec<u32> {
+        self.profiles
+            .lock()
+            .expect("Device profiles poisoned")
+            .probe_baud_rates()
+    }
+
+    fn set_profiles(&self, profiles: DeviceProfiles) {
+        *self.profiles.lock().expect("Device profiles poisoned") = profiles;
+    }
+}
+
+/// Same as `DeviceProfileStore::profile_for`, for the device connected to the given port
+pub fn device_profile_for_port(app: &AppHandle, port_name: &str) -> DeviceProfile {
+    let arduino_serial_number = match connected_arduino_serial_number(app, port_name) {
+        Ok(arduino_serial_number) => arduino_serial_number,
+        Err(err) => {
+            log::warn!("Failed to list the connected Arduinos: {err:?}");
+            None
+        }
+    };
+
+    app.state::<DeviceProfileStore>()
+        .profile_for(arduino_serial_number.as_deref())
+}
+
+#[tauri::command]
+pub fn get_device_profiles(device_profiles: State<'_, DeviceProfileStore>) -> DeviceProfiles {
+    device_profiles.profiles()
+}
+
+#[tauri::command]
+pub fn set_device_profiles(
+    app: AppHandle,
+    device_profiles: State<'_, DeviceProfileStore>,
+    profiles: DeviceProfiles,
+) -> Result<(), String> {
+    let save = || -> anyhow::Result<()> {
+        profiles.validate()?;
+        let path = config_path(&app)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&profiles)?)?;
+        Ok(())
+    };
+    save().map_err(|err| err.to_string())?;
+
+    device_profiles.set_profiles(profiles);
+    Ok(())
+}
+
+fn config_path(app: &AppHandle) -> anyhow::Result<PathBuf> {
+    Ok(app.path().app_config_dir()?.join(CONFIG_FILE_NAME))
+}
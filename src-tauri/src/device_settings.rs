@@ -0,0 +1,303 @@
+use anyhow::anyhow;
+use chrono::Utc;
+use holochain_types::prelude::Timestamp;
+use living_power_integrity::ClockOffset;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+use crate::arduino::connected_arduino_serial_number;
+use crate::clock_drift::{parse_device_clock, read_device_clock, save_clock_offset};
+use crate::collect_measurements::{is_timeout, read_line_after_marker};
+use crate::device_profiles::{device_profile_for_port, DeviceProfile, DeviceProfileStore};
+use crate::rtc_timezone::{rtc_timezone_for_device, rtc_timezone_for_serial_number, RtcTimezone};
+use crate::serial_transport::{SerialConnection, SerialTransport};
+use crate::sessions::SessionManager;
+
+/// The device clock only has second precision, and setting it takes a moment
+const MAX_CLOCK_ERROR: Duration = Duration::from_secs(2);
+
+/// How the device waits between two measurements
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepMode {
+    /// Uses the least power, but the device only answers right after taking a measurement
+    Deep,
+    /// Uses more power than deep sleep, but wakes up faster
+    Light,
+    /// Keeps the serial port available at all times
+    Off,
+}
+
+impl SleepMode {
+    /// The name used by `bpv.ino` for this mode
+    fn as_device_str(&self) -> &'static str {
+        match self {
+            SleepMode::Deep => "deep",
+            SleepMode::Light => "light",
+            SleepMode::Off => "off",
+        }
+    }
+
+    fn from_device_str(mode: &str) -> anyhow::Result<Self> {
+        match mode {
+            "deep" => Ok(SleepMode::Deep),
+            "light" => Ok(SleepMode::Light),
+            "off" => Ok(SleepMode::Off),
+            _ => Err(anyhow!("Unknown sleep mode \"{mode}\"")),
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_device_time(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    device_profiles: State<'_, DeviceProfileStore>,
+    port_name: String,
+) -> Result<ClockOffset, String> {
+    let arduino_serial_number =
+        connected_arduino_serial_number(&app, &port_name).map_err(|err| err.to_string())?;
+    let profile = device_profiles.profile_for(arduino_serial_number.as_deref());
+    let timezone = rtc_timezone_for_serial_number(&app, arduino_serial_number)
+        .await
+        .map_err(|err| err.to_string())?;
+    internal_get_device_time(&*sessions, port_name, &profile, &timezone)
+        .map_err(|err| err.to_string())
+}
+
+pub fn internal_get_device_time(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    timezone: &RtcTimezone,
+) -> anyhow::Result<ClockOffset> {
+    let mut port = open(transport, &port_name, profile)?;
+    read_device_clock(port.as_mut(), profile, timezone)?.ok_or(unsupported("reading the time"))
+}
+
+/// Sets the device clock to the current time, and records it as a clock offset to measure its drift from
+#[tauri::command]
+pub async fn set_device_time(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    device_profiles: State<'_, DeviceProfileStore>,
+    port_name: String,
+    arduino_serial_number: String,
+) -> Result<ClockOffset, String> {
+    let timezone = rtc_timezone_for_device(&app, arduino_serial_number.clone())
+        .await
+        .map_err(|err| err.to_string())?;
+    let clock_offset = internal_set_device_time(
+        &*sessions,
+        port_name,
+        &device_profiles.profile_for(Some(&arduino_serial_number)),
+        &timezone,
+        Timestamp::from_micros(Utc::now().timestamp_micros()),
+    )
+    .map_err(|err| err.to_string())?;
+
+    save_clock_offset(&app, arduino_serial_number, clock_offset.clone())
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(clock_offset)
+}
+
+pub fn internal_set_device_time(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    timezone: &RtcTimezone,
+    time: Timestamp,
+) -> anyhow::Result<ClockOffset> {
+    let mut port = open(transport, &port_name, profile)?;
+
+    let date_time = timezone.to_device_date_time(time)?;
+    let command = profile.command('T', &date_time.format("%Y-%m-%d,%H:%M:%S").to_string());
+    let line = send_setting(
+        port.as_mut(),
+        profile,
+        &command,
+        "BEGIN_T",
+        "setting the time",
+    )?;
+
+    let mut clock_offset = parse_device_clock(&line, timezone)?;
+    clock_offset.clock_set = true;
+    let error = clock_offset.device_time.as_micros() - time.as_micros();
+    if error.unsigned_abs() > MAX_CLOCK_ERROR.as_micros() as u64 {
+        return Err(anyhow!(
+            "The device did not apply the new time: it reads {line}"
+        ));
+    }
+
+    Ok(clock_offset)
+}
+
+/// The time between two measurements, in milliseconds
+#[tauri::command]
+pub async fn get_logging_interval(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    port_name: String,
+) -> Result<u32, String> {
+    let profile = device_profile_for_port(&app, &port_name);
+    internal_get_logging_interval(&*sessions, port_name, &profile).map_err(|err| err.to_string())
+}
+
+pub fn internal_get_logging_interval(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+) -> anyhow::Result<u32> {
+    let mut port = open(transport, &port_name, profile)?;
+    let line = send_setting(
+        port.as_mut(),
+        profile,
+        "i",
+        "BEGIN_I",
+        "reading the logging interval",
+    )?;
+    parse_logging_interval(&line)
+}
+
+#[tauri::command]
+pub async fn set_logging_interval(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    port_name: String,
+    logging_interval_ms: u32,
+) -> Result<u32, String> {
+    let profile = device_profile_for_port(&app, &port_name);
+    internal_set_logging_interval(&*sessions, port_name, &profile, logging_interval_ms)
+        .map_err(|err| err.to_string())
+}
+
+pub fn internal_set_logging_interval(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    logging_interval_ms: u32,
+) -> anyhow::Result<u32> {
+    if logging_interval_ms == 0 {
+        return Err(anyhow!("The logging interval must be greater than 0"));
+    }
+
+    let mut port = open(transport, &port_name, profile)?;
+    let command = profile.command('I', &logging_interval_ms.to_string());
+    let line = send_setting(
+        port.as_mut(),
+        profile,
+        &command,
+        "BEGIN_I",
+        "setting the logging interval",
+    )?;
+
+    let applied = parse_logging_interval(&line)?;
+    if applied != logging_interval_ms {
+        return Err(anyhow!(
+            "The device did not apply the new logging interval: it is still {applied} ms"
+        ));
+    }
+    Ok(applied)
+}
+
+#[tauri::command]
+pub async fn get_sleep_mode(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    port_name: String,
+) -> Result<SleepMode, String> {
+    let profile = device_profile_for_port(&app, &port_name);
+    internal_get_sleep_mode(&*sessions, port_name, &profile).map_err(|err| err.to_string())
+}
+
+pub fn internal_get_sleep_mode(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+) -> anyhow::Result<SleepMode> {
+    let mut port = open(transport, &port_name, profile)?;
+    let line = send_setting(
+        port.as_mut(),
+        profile,
+        "z",
+        "BEGIN_Z",
+        "reading the sleep mode",
+    )?;
+    SleepMode::from_device_str(&line)
+}
+
+#[tauri::command]
+pub async fn set_sleep_mode(
+    app: AppHandle,
+    sessions: State<'_, SessionManager>,
+    port_name: String,
+    sleep_mode: SleepMode,
+) -> Result<SleepMode, String> {
+    let profile = device_profile_for_port(&app, &port_name);
+    internal_set_sleep_mode(&*sessions, port_name, &profile, sleep_mode)
+        .map_err(|err| err.to_string())
+}
+
+pub fn internal_set_sleep_mode(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    sleep_mode: SleepMode,
+) -> anyhow::Result<SleepMode> {
+    let mut port = open(transport, &port_name, profile)?;
+    let command = profile.command('Z', sleep_mode.as_device_str());
+    let line = send_setting(
+        port.as_mut(),
+        profile,
+        &command,
+        "BEGIN_Z",
+        "setting the sleep mode",
+    )?;
+
+    let applied = SleepMode::from_device_str(&line)?;
+    if applied != sleep_mode {
+        return Err(anyhow!(
+            "The device did not apply the new sleep mode: it is still {line}"
+        ));
+    }
+    Ok(applied)
+}
+
+/// Firmware that predates the settings commands never answers them, so this only waits for the command timeout of the profile
+fn open(
+    transport: &dyn SerialTransport,
+    port_name: &str,
+    profile: &DeviceProfile,
+) -> anyhow::Result<Box<dyn SerialConnection>> {
+    transport.open(port_name, profile.baud_rate, profile.command_timeout())
+}
+
+/// Sends the command and returns the line the device answers with after the marker
+fn send_setting(
+    port: &mut dyn SerialConnection,
+    profile: &DeviceProfile,
+    command: &str,
+    marker: &str,
+    action: &str,
+) -> anyhow::Result<String> {
+    if !profile.supports_framed_protocol() {
+        return Err(unsupported(action));
+    }
+    port.write_all(command.as_bytes())?;
+    match read_line_after_marker(port, marker) {
+        Err(err) if is_timeout(&err) => Err(unsupported(action)),
+        result => result,
+    }
+}
+
+fn parse_logging_interval(line: &str) -> anyhow::Result<u32> {
+    line.parse()
+        .map_err(|_| anyhow!("Invalid logging interval \"{line}\""))
+}
+
+pub(crate) fn unsupported(action: &str) -> anyhow::Error {
+    anyhow!("The firmware of the device does not support {action}, update it and try again")
+}
@@ -0,0 +1,213 @@
+use std::fmt::Display;
+
+/// Bytes that mark the start of every frame sent by the BPV firmware
+pub const FRAME_MAGIC: [u8; 2] = [0xB9, 0x50];
+
+/// Version of the framed protocol implemented by this app and by `bpv.ino`
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Maximum payload size the firmware sends in a single frame
+pub const MAX_PAYLOAD_LEN: usize = 512;
+
+// Magic (2) + version (1) + kind (1) + sequence (2) + payload length (2)
+const HEADER_LEN: usize = 8;
+const CRC_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FrameKind {
+    /// Starts a transfer, its payload is the total size of the transferred file as a little endian u32
+    Begin = 0x01,
+    /// A chunk of the transferred file
+    Data = 0x02,
+    /// Ends a transfer, its payload is the number of data frames and the number of bytes sent, as little endian u32s
+    End = 0x03,
+    /// The device couldn't complete the transfer, its payload is a human readable reason
+    Error = 0x04,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = FrameError;
+
+    fn try_from(value: u8) -> Result<Self, FrameError> {
+        match value {
+            0x01 => Ok(FrameKind::Begin),
+            0x02 => Ok(FrameKind::Data),
+            0x03 => Ok(FrameKind::End),
+            0x04 => Ok(FrameKind::Error),
+            _ => Err(FrameError::UnknownKind(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub sequence: u16,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    UnsupportedVersion(u8),
+    UnknownKind(u8),
+    PayloadTooLarge(usize),
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported frame protocol version {version}")
+            }
+            FrameError::UnknownKind(kind) => write!(f, "Unknown frame kind {kind:#04x}"),
+            FrameError::PayloadTooLarge(len) => write!(
+                f,
+                "Frame payload of {len} bytes exceeds the maximum of {MAX_PAYLOAD_LEN} bytes"
+            ),
+            FrameError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Frame checksum mismatch: expected {expected:#010x}, got {actual:#010x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+pub fn encode_frame(frame: &Frame) -> Result<Vec<u8>, FrameError> {
+    if frame.payload.len() > MAX_PAYLOAD_LEN {
+        return Err(FrameError::PayloadTooLarge(frame.payload.len()));
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + frame.payload.len() + CRC_LEN);
+    bytes.extend_from_slice(&FRAME_MAGIC);
+    bytes.push(PROTOCOL_VERSION);
+    bytes.push(frame.kind as u8);
+    bytes.extend_from_slice(&frame.sequence.to_le_bytes());
+    bytes.extend_from_slice(&(frame.payload.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&frame.payload);
+
+    // The checksum covers everything but the magic bytes
+    let crc = crc32(&bytes[FRAME_MAGIC.len()..]);
+    bytes.extend_from_slice(&crc.to_le_bytes());
+
+    Ok(bytes)
+}
+
+/// Incrementally decodes the frames found in the bytes read from a serial port
+///
+/// Bytes that precede a frame's magic (e.g. debug output from the firmware) are skipped
+#[derive(Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete frame, or `None` if more bytes are needed
+    ///
+    /// After an error the offending frame is discarded, so it's safe to keep calling this
+    pub fn next_frame(&mut self) -> Option<Result<Frame, FrameError>> {
+        let start = self
+            .buffer
+            .windows(FRAME_MAGIC.len())
+            .position(|window| window == FRAME_MAGIC);
+        let Some(start) = start else {
+            // Keep the last byte around in case it's the first half of the magic
+            let keep_from = self.buffer.len().saturating_sub(FRAME_MAGIC.len() - 1);
+            self.buffer.drain(..keep_from);
+            return None;
+        };
+        self.buffer.drain(..start);
+
+        if self.buffer.len() < HEADER_LEN {
+            return None;
+        }
+
+        let version = self.buffer[2];
+        if version != PROTOCOL_VERSION {
+            self.buffer.drain(..FRAME_MAGIC.len());
+            return Some(Err(FrameError::UnsupportedVersion(version)));
+        }
+        let payload_len = u16::from_le_bytes([self.buffer[6], self.buffer[7]]) as usize;
+        if payload_len > MAX_PAYLOAD_LEN {
+            self.buffer.drain(..FRAME_MAGIC.len());
+            return Some(Err(FrameError::PayloadTooLarge(payload_len)));
+        }
+
+        let frame_len = HEADER_LEN + payload_len + CRC_LEN;
+        if self.buffer.len() < frame_len {
+            return None;
+        }
+
+        let frame_bytes: Vec<u8> = self.buffer.drain(..frame_len).collect();
+        let crc_start = HEADER_LEN + payload_len;
+        let expected = u32::from_le_bytes([
+            frame_bytes[crc_start],
+            frame_bytes[crc_start + 1],
+            frame_bytes[crc_start + 2],
+            frame_bytes[crc_start + 3],
+        ]);
+        let actual = crc32(&frame_bytes[FRAME_MAGIC.len()..crc_start]);
+        if expected != actual {
+            return Some(Err(FrameError::ChecksumMismatch { expected, actual }));
+        }
+
+        let kind = match FrameKind::try_from(frame_bytes[3]) {
+            Ok(kind) => kind,
+            Err(err) => return Some(Err(err)),
+        };
+
+        Some(Ok(Frame {
+            kind,
+            sequence: u16::from_le_bytes([frame_bytes[4], frame_bytes[5]]),
+            payload: frame_bytes[HEADER_LEN..crc_start].to_vec(),
+        }))
+    }
+}
+
+/// CRC-32 (IEEE 802.3), the same one computed by `crc32Update` in `bpv.ino`
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// Computes the same checksum as `crc32` over bytes that arrive in chunks
+#[derive(Clone, Copy, Debug)]
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xEDB8_8320 & mask);
+            }
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
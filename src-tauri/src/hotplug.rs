@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::arduino::{internal_list_connected_arduinos, ArduinoIdentifier, ConnectedArduino};
+use crate::device_profiles::DeviceProfileStore;
+use crate::serial_transport::SerialTransport;
+use crate::sessions::SessionManager;
+
+pub const ARDUINO_CONNECTED_EVENT: &str = "arduino-connected";
+pub const ARDUINO_DISCONNECTED_EVENT: &str = "arduino-disconnected";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum HotplugEvent {
+    Connected(ConnectedArduino),
+    Disconnected(ConnectedArduino),
+}
+
+/// Keeps the last list of connected Arduinos to find out which ones appeared or disappeared
+#[derive(Default)]
+pub struct HotplugWatcher {
+    connected_arduinos: Vec<ConnectedArduino>,
+}
+
+impl HotplugWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lists the connected Arduinos again, and returns the changes since the last poll
+    pub fn poll(
+        &mut self,
+        transport: &dyn SerialTransport,
+        arduino_identifier: &ArduinoIdentifier,
+        probe_baud_rates: &[u32],
+    ) -> anyhow::Result<Vec<HotplugEvent>> {
+        let connected_arduinos =
+            internal_list_connected_arduinos(transport, arduino_identifier, probe_baud_rates)?;
+
+        let mut events: Vec<HotplugEvent> = self
+            .connected_arduinos
+            .iter()
+            .filter(|arduino| !connected_arduinos.contains(arduino))
+            .cloned()
+            .map(HotplugEvent::Disconnected)
+            .collect();
+        events.extend(
+            connected_arduinos
+                .iter()
+                .filter(|arduino| !self.connected_arduinos.contains(arduino))
+                .cloned()
+                .map(HotplugEvent::Connected),
+        );
+
+        self.connected_arduinos = connected_arduinos;
+        Ok(events)
+    }
+}
+
+/// Watches the serial ports in a background thread, emitting a tauri event each time an Arduino is connected or disconnected
+///
+/// The connected Arduinos are kept alive by the [`SessionManager`] until they are disconnected
+///
+/// A port whose serial number changes, like after a probe that succeeded late, is reported as a disconnection and a new connection
+pub fn spawn_hotplug_watcher(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut watcher = HotplugWatcher::new();
+        loop {
+            let arduino_identifier = app.state::<ArduinoIdentifier>();
+            let sessions = app.state::<SessionManager>();
+            let device_profiles = app.state::<DeviceProfileStore>();
+            match watcher.poll(
+                &*sessions,
+                &arduino_identifier,
+                &device_profiles.probe_baud_rates(),
+            ) {
+                Ok(events) => {
+                    for event in events {
+                        let result = match event {
+                            HotplugEvent::Connected(arduino) => {
+                                let profile = device_profiles
+                                    .profile_for(arduino.arduino_serial_number.as_deref());
+                                sessions.keep_alive(
+                                    &arduino.serial_port_info.port_name,
+                                    profile.baud_rate,
+                                );
+                                app.emit(ARDUINO_CONNECTED_EVENT, arduino)
+                            }
+                            HotplugEvent::Disconnected(arduino) => {
+                                sessions.close(&arduino.serial_port_info.port_name);
+                                app.emit(ARDUINO_DISCONNECTED_EVENT, arduino)
+                            }
+                        };
+                        if let Err(err) = result {
+                            log::error!("Failed to emit hotplug event: {err:?}");
+                        }
+                    }
+                }
+                Err(err) => log::warn!("Failed to list the connected Arduinos: {err:?}"),
+            }
+
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
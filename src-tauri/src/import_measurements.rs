@@ -0,0 +1,211 @@
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use holochain_types::prelude::ActionHash;
+use living_power_integrity::{measurement_collection::Measurement, MeasurementCollection};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::measurements_parser::{
+    header_columns, header_row, parse_mapped_csv_file_contents, ColumnMapping, HeaderColumn,
+    ParseReport,
+};
+use crate::rtc_timezone::{rtc_timezone_for_device, RtcTimezone};
+use crate::zome_calls::{call_living_power_zome, CreatedMeasurementCollections};
+
+/// How many measurements the preview includes, the rest are only counted
+const PREVIEW_MEASUREMENTS: usize = 20;
+
+/// The result of parsing one of the CSV files of an import
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedFile {
+    pub path: PathBuf,
+    /// The columns of the first header row of the file, empty if it has none
+    pub header: Vec<HeaderColumn>,
+    pub measurements: usize,
+    pub report: ParseReport,
+    /// Why the file couldn't be parsed at all, like an unreadable file or a header with missing columns
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParsedImport {
+    pub files: Vec<ImportedFile>,
+    /// The measurements of all the files sorted by timestamp, without the ones repeated across files
+    pub measurements: Vec<Measurement>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportPreview {
+    pub files: Vec<ImportedFile>,
+    /// The first measurements that would be imported
+    pub measurements: Vec<Measurement>,
+    pub total_measurements: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedMeasurements {
+    pub files: Vec<ImportedFile>,
+    pub imported_measurements: usize,
+    /// Measurements that were not imported because the device already had one at their timestamp
+    pub skipped_measurements: usize,
+    /// Measurements that were not imported because they are invalid, like the ones from before 2000 or in the future
+    pub rejected_measurements: usize,
+    pub action_hashes: Vec<ActionHash>,
+}
+
+/// The path itself if it's a file, or the CSV files inside it and its subdirectories in path order
+pub fn csv_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return match path.exists() {
+            true => Ok(vec![path.to_path_buf()]),
+            false => Err(anyhow!("{path:?} does not exist")),
+        };
+    }
+
+    let mut files = vec![];
+    for entry in read_dir(path)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let entry_path = entry.path();
+        if entry_path.is_dir() {
+            files.extend(csv_files(&entry_path)?);
+        } else if entry_path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+        {
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Parses each CSV file of the path separately, so an invalid file doesn't prevent importing the others
+pub fn internal_parse_import(
+    path: &Path,
+    timezone: &RtcTimezone,
+    column_mapping: &ColumnMapping,
+) -> anyhow::Result<ParsedImport> {
+    let files = csv_files(path)?;
+    if files.is_empty() {
+        return Err(anyhow!("There are no CSV files in {path:?}"));
+    }
+
+    let mut parsed_import = ParsedImport {
+        files: vec![],
+        measurements: vec![],
+    };
+    for file in files {
+        let mut imported_file = ImportedFile {
+            path: file.clone(),
+            header: vec![],
+            measurements: 0,
+            report: ParseReport::default(),
+            error: None,
+        };
+
+        let result = std::fs::read(&file)
+            .map_err(|err| anyhow!("Could not read {file:?}: {err}"))
+            .and_then(|contents| {
+                let contents = String::from_utf8_lossy(&contents).to_string();
+                if let Some(header) = header_row(&contents) {
+                    imported_file.header = header_columns(header, column_mapping);
+                }
+                parse_mapped_csv_file_contents(contents, timezone.clone(), column_mapping.clone())
+            });
+        match result {
+            Ok(parsed) => {
+                imported_file.measurements = parsed.measurements.len();
+                imported_file.report = parsed.report;
+                parsed_import.measurements.extend(parsed.measurements);
+            }
+            Err(err) => imported_file.error = Some(err.to_string()),
+        }
+        parsed_import.files.push(imported_file);
+    }
+
+    parsed_import
+        .measurements
+        .sort_by_key(|measurement| measurement.timestamp);
+    parsed_import.measurements.dedup();
+
+    Ok(parsed_import)
+}
+
+async fn parse_import(
+    app: &AppHandle,
+    path: &Path,
+    arduino_serial_number: String,
+    column_mapping: &ColumnMapping,
+) -> anyhow::Result<ParsedImport> {
+    let timezone = rtc_timezone_for_device(app, arduino_serial_number).await?;
+    internal_parse_import(path, &timezone, column_mapping)
+}
+
+/// Parses the CSV file, or the CSV files of the directory, without importing them
+///
+/// Dates and times are read in the timezone of the given device
+#[tauri::command]
+pub async fn preview_measurements_import(
+    app: AppHandle,
+    path: PathBuf,
+    arduino_serial_number: String,
+    column_mapping: ColumnMapping,
+) -> Result<ImportPreview, String> {
+    let parsed_import = parse_import(&app, &path, arduino_serial_number, &column_mapping)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(ImportPreview {
+        files: parsed_import.files,
+        total_measurements: parsed_import.measurements.len(),
+        measurements: parsed_import
+            .measurements
+            .into_iter()
+            .take(PREVIEW_MEASUREMENTS)
+            .collect(),
+    })
+}
+
+/// Parses the CSV file, or the CSV files of the directory, and saves their measurements as new measurement collections of the device
+#[tauri::command]
+pub async fn import_measurements(
+    app: AppHandle,
+    path: PathBuf,
+    arduino_serial_number: String,
+    column_mapping: ColumnMapping,
+) -> Result<ImportedMeasurements, String> {
+    let parsed_import = parse_import(&app, &path, arduino_serial_number.clone(), &column_mapping)
+        .await
+        .map_err(|err| err.to_string())?;
+    if parsed_import.measurements.is_empty() {
+        return Err(format!("There are no measurements to import in {path:?}"));
+    }
+
+    let total_measurements = parsed_import.measurements.len();
+    let created: CreatedMeasurementCollections = call_living_power_zome(
+        &app,
+        "create_new_measurement_collections",
+        MeasurementCollection {
+            arduino_serial_number,
+            measurements: parsed_import.measurements,
+            live: false,
+        },
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+
+    Ok(ImportedMeasurements {
+        files: parsed_import.files,
+        imported_measurements: total_measurements
+            - created.skipped_measurements
+            - created.rejected_measurements,
+        skipped_measurements: created.skipped_measurements,
+        rejected_measurements: created.rejected_measurements,
+        action_hashes: created.action_hashes,
+    })
+}
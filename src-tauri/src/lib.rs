@@ -1,8 +1,7 @@
 use std::path::PathBuf;
-use std::{collections::HashMap, time::Duration};
+use std::collections::HashMap;
 
 use anyhow::anyhow;
-use serialport::available_ports;
 use tauri::menu::{Menu, MenuItem, PredefinedMenuItem, Submenu};
 use tauri::{AppHandle, Manager};
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
@@ -13,10 +12,31 @@ use holochain_types::prelude::{AppBundle, ExternIO};
 use lair_keystore::dependencies::sodoken::{BufRead, BufWrite};
 use tauri_plugin_holochain::{HolochainExt, HolochainPluginConfig, WANNetworkConfig};
 
-mod arduino;
-mod collect_measurements;
+use arduino::ArduinoIdentifier;
+use collection_progress::ActiveCollections;
+use device_profiles::DeviceProfileStore;
+use live_measurements::LiveStreams;
+use serial_transport::SerialportTransport;
+use sessions::SessionManager;
+
+pub mod arduino;
+pub mod archive;
+pub mod clock_drift;
+pub mod collect_measurements;
+pub mod collection_progress;
+pub mod device_profiles;
+pub mod device_settings;
+pub mod frame;
+pub mod hotplug;
+pub mod import_measurements;
+pub mod live_measurements;
 mod macos;
-mod sdcards;
+pub mod measurements_parser;
+pub mod rtc_timezone;
+pub mod sdcards;
+pub mod serial_transport;
+pub mod sessions;
+mod zome_calls;
 
 // const PRODUCTION_SIGNAL_URL: &'static str = "wss://signal.holo.host";
 // const PRODUCTION_BOOTSTRAP_URL: &'static str = "https://bootstrap.holo.host";
@@ -49,13 +69,34 @@ pub fn run() {
             vec_to_locked(vec![]).expect("Can't build passphrase"),
             HolochainPluginConfig::new(holochain_dir(), wan_network_config()),
         ))
+        .manage(ActiveCollections::default())
+        .manage(LiveStreams::default())
+        .manage(SessionManager::new(SerialportTransport))
         .invoke_handler(tauri::generate_handler![
             macos::should_be_moved_to_applications_directory,
             arduino::list_connected_arduinos,
+            arduino::get_arduino_identification,
+            arduino::set_arduino_identification,
+            archive::collect_and_archive_measurements,
             collect_measurements::collect_measurements,
+            collect_measurements::collect_new_measurements,
             collect_measurements::get_last_measurement,
+            device_profiles::get_device_profiles,
+            device_profiles::set_device_profiles,
+            device_settings::get_device_time,
+            device_settings::set_device_time,
+            device_settings::get_logging_interval,
+            device_settings::set_logging_interval,
+            device_settings::get_sleep_mode,
+            device_settings::set_sleep_mode,
+            collection_progress::cancel_collection,
+            live_measurements::start_live_measurements,
+            live_measurements::stop_live_measurements,
+            clock_drift::correct_measurements_clock_drift,
             sdcards::list_measurements_sdcards,
-            sdcards::collect_measurements_from_sdcard
+            sdcards::collect_measurements_from_sdcard,
+            import_measurements::preview_measurements_import,
+            import_measurements::import_measurements
         ])
         .menu(|handle| {
             Menu::with_items(
@@ -85,6 +126,11 @@ pub fn run() {
             )
         })
         .setup(|app| {
+            app.manage(ArduinoIdentifier::load(app.handle()));
+            app.manage(DeviceProfileStore::load(app.handle()));
+            hotplug::spawn_hotplug_watcher(app.handle().clone());
+            sessions::spawn_keepalives(app.handle().clone());
+
             #[cfg(not(mobile))]
             app.handle()
                 .plugin(tauri_plugin_updater::Builder::new().build())?;
@@ -135,36 +181,6 @@ pub fn run() {
                     .inner_size(1400.0, 1000.0)
                     .build()?;
 
-                // Keep sending data to the arduinos so that they know they are still
-                // connected to a computer and they don't go into deepSleep mode
-                tauri::async_runtime::spawn(async move {
-                    loop {
-                        let baud_rate: u32 = 9600;
-
-                        let Ok(ports) = available_ports() else {
-                            continue;
-                        };
-
-                        for port in ports {
-                            let Ok(mut port) = serialport::new(port.port_name, baud_rate).open()
-                            else {
-                                continue;
-                            };
-
-                            let mut write_buffer: Vec<u8> = vec![0; 1];
-                            write_buffer[0] = b'p';
-
-                            let n = 1; // How many bytes to write to serial port.
-
-                            // Write to serial port
-                            let Ok(_) = port.write(&write_buffer[..n]) else {
-                                continue;
-                            }; // blocks
-                        }
-
-                        std::thread::sleep(Duration::from_millis(50));
-                    }
-                });
                 Ok(())
             });
             result?;
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use holochain_types::prelude::ActionHash;
+use living_power_integrity::{measurement_collection::Measurement, MeasurementCollection};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::collect_measurements::{is_timeout, read_until_marker};
+use crate::device_profiles::{DeviceProfile, DeviceProfileStore};
+use crate::device_settings::unsupported;
+use crate::measurements_parser::line_to_measurement;
+use crate::rtc_timezone::{rtc_timezone_for_device, RtcTimezone};
+use crate::serial_transport::{SerialConnection, SerialTransport};
+use crate::sessions::SessionManager;
+use crate::zome_calls::{call_living_power_zome, CreatedMeasurementCollections};
+
+pub const LIVE_MEASUREMENT_EVENT: &str = "live-measurement";
+pub const LIVE_MEASUREMENTS_ENDED_EVENT: &str = "live-measurements-ended";
+
+/// How often to check whether the stream was stopped while waiting for the next sample
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LiveMeasurement {
+    pub port_name: String,
+    pub measurement: Measurement,
+}
+
+/// Emitted when a stream ends without being stopped, like when the device is disconnected
+#[derive(Serialize, Clone, Debug)]
+pub struct LiveMeasurementsEnded {
+    pub port_name: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LiveRecording {
+    pub measurements: Vec<Measurement>,
+    /// The interval confirmed by the device, which may be longer than the requested one
+    pub interval_ms: u32,
+    /// Whether the stream ended because the connection to the device broke, instead of being stopped
+    pub interrupted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoppedLiveMeasurements {
+    pub recording: LiveRecording,
+    /// Hashes of the measurement collections created for the recording, if it was persisted
+    pub action_hashes: Vec<ActionHash>,
+    /// Samples that were not saved because the device already had a measurement at their timestamp
+    pub skipped_measurements: usize,
+    /// Samples that were not saved because they are invalid, like the ones read while the clock of the device was reset
+    pub rejected_measurements: usize,
+}
+
+/// Lets the caller of a live stream receive its samples as they arrive and stop it
+#[derive(Clone, Default)]
+pub struct LiveControl {
+    stopped: Arc<AtomicBool>,
+    on_measurement: Option<Arc<dyn Fn(Measurement) + Send + Sync>>,
+}
+
+impl LiveControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_measurement(
+        mut self,
+        on_measurement: impl Fn(Measurement) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_measurement = Some(Arc::new(on_measurement));
+        self
+    }
+
+    /// Stops the stream once the current read finishes, keeping the samples received so far
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    fn report(&self, measurement: Measurement) {
+        if let Some(on_measurement) = &self.on_measurement {
+            on_measurement(measurement);
+        }
+    }
+}
+
+/// Asks the device to send a sample every `interval_ms`, until the stream is stopped or the device stops answering
+///
+/// The samples are not logged to the data file of the device
+pub fn internal_stream_live_measurements(
+    transport: &dyn SerialTransport,
+    port_name: String,
+    profile: &DeviceProfile,
+    timezone: &RtcTimezone,
+    interval_ms: u32,
+    control: &LiveControl,
+) -> anyhow::Result<LiveRecording> {
+    if !profile.supports_framed_protocol() {
+        return Err(unsupported("live measurements"));
+    }
+
+    let mut port = transport.open(&port_name, profile.baud_rate, profile.command_timeout())?;
+    port.write_all(profile.command('v', &interval_ms.to_string()).as_bytes())?;
+    let mut received = match read_until_marker(port.as_mut(), "BEGIN_V") {
+        Err(err) if is_timeout(&err) => return Err(unsupported("live measurements")),
+        result => result?,
+    };
+
+    let mut recording = LiveRecording {
+        measurements: vec![],
+        interval_ms,
+        interrupted: false,
+    };
+    // The first line is the interval confirmed by the device
+    let mut confirmed = false;
+    let mut last_line_at = Instant::now();
+    port.set_timeout(STOP_POLL_INTERVAL)?;
+
+    loop {
+        while let Some(end) = received.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = received.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            last_line_at = Instant::now();
+
+            if !confirmed {
+                recording.interval_ms = line
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid live measurements interval \"{line}\""))?;
+                confirmed = true;
+                continue;
+            }
+            let Some(row) = line.strip_prefix('V') else {
+                continue;
+            };
+            match line_to_measurement(row, timezone) {
+                Ok(measurement) => {
+                    control.report(measurement.clone());
+                    recording.measurements.push(measurement);
+                }
+                Err(err) => log::warn!("Error reading the live measurement \"{row}\": {err:?}"),
+            }
+        }
+
+        if control.is_stopped() {
+            stop_stream(port.as_mut(), profile);
+            return Ok(recording);
+        }
+
+        let sample_timeout =
+            Duration::from_millis(recording.interval_ms as u64) + profile.command_timeout();
+        if last_line_at.elapsed() > sample_timeout {
+            return interrupted(
+                recording,
+                anyhow!("The device stopped sending live measurements"),
+            );
+        }
+
+        let mut read_buffer: Vec<u8> = vec![0; 64];
+        match port.read(&mut read_buffer) {
+            Ok(n) => received.extend_from_slice(&read_buffer[..n]),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                ) => {}
+            Err(err) => return interrupted(recording, err.into()),
+        }
+    }
+}
+
+/// Keeps the samples received so far if there are any
+fn interrupted(mut recording: LiveRecording, err: anyhow::Error) -> anyhow::Result<LiveRecording> {
+    if recording.measurements.is_empty() {
+        return Err(err);
+    }
+    log::warn!(
+        "Live measurements interrupted after {} samples: {err:?}",
+        recording.measurements.len()
+    );
+    recording.interrupted = true;
+    Ok(recording)
+}
+
+/// Tells the device to stop streaming, the samples sent in the meantime are discarded
+fn stop_stream(port: &mut dyn SerialConnection, profile: &DeviceProfile) {
+    let mut stop = || -> anyhow::Result<()> {
+        port.set_timeout(profile.command_timeout())?;
+        port.write_all(b"x")?;
+        read_until_marker(port, "END_V")?;
+        Ok(())
+    };
+    if let Err(err) = stop() {
+        log::warn!("The device did not confirm the end of the live measurements: {err:?}");
+    }
+}
+
+struct LiveStream {
+    control: LiveControl,
+    arduino_serial_number: String,
+    handle: JoinHandle<anyhow::Result<LiveRecording>>,
+}
+
+impl LiveStream {
+    /// Stops the stream and waits for its recording
+    fn stop(self, port_name: &str) -> anyhow::Result<(String, LiveRecording)> {
+        self.control.stop();
+        let recording = self
+            .handle
+            .join()
+            .map_err(|_| anyhow!("The live measurements of {port_name} panicked"))??;
+
+        Ok((self.arduino_serial_number, recording))
+    }
+}
+
+/// The live streams in progress, by port name, including the ones that ended until they are stopped or started again
+#[derive(Default)]
+pub struct LiveStreams(Mutex<HashMap<String, LiveStream>>);
+
+impl LiveStreams {
+    /// Starts streaming from the given port in a background thread, emitting each sample as a tauri event
+    fn start(
+        &self,
+        app: &AppHandle,
+        port_name: String,
+        arduino_serial_number: String,
+        profile: DeviceProfile,
+        timezone: RtcTimezone,
+        interval_ms: u32,
+    ) -> anyhow::Result<()> {
+        let mut streams = self.lock();
+        if let Some(stream) = streams.get(&port_name) {
+            if !stream.handle.is_finished() {
+                return Err(anyhow!(
+                    "There are already live measurements in progress for {port_name}"
+                ));
+            }
+            log::warn!(
+                "Discarding the live measurements of {port_name} that ended without being stopped"
+            );
+            streams.remove(&port_name);
+        }
+
+        let emitter = app.clone();
+        let sample_port_name = port_name.clone();
+        let control = LiveControl::new().on_measurement(move |measurement| {
+            let live_measurement = LiveMeasurement {
+                port_name: sample_port_name.clone(),
+                measurement,
+            };
+            if let Err(err) = emitter.emit(LIVE_MEASUREMENT_EVENT, live_measurement) {
+                log::error!("Failed to emit live measurement: {err:?}");
+            }
+        });
+
+        let app = app.clone();
+        let thread_control = control.clone();
+        let thread_port_name = port_name.clone();
+        let handle = std::thread::spawn(move || {
+            let sessions = app.state::<SessionManager>();
+            let result = internal_stream_live_measurements(
+                &*sessions,
+                thread_port_name.clone(),
+                &profile,
+                &timezone,
+                interval_ms,
+                &thread_control,
+            );
+
+            let error = match &result {
+                Err(err) => Some(err.to_string()),
+                Ok(recording) if recording.interrupted => {
+                    Some(String::from("The connection to the device was lost"))
+                }
+                Ok(_) => None,
+            };
+            if let Some(error) = error {
+                let ended = LiveMeasurementsEnded {
+                    port_name: thread_port_name,
+                    error,
+                };
+                if let Err(err) = app.emit(LIVE_MEASUREMENTS_ENDED_EVENT, ended) {
+                    log::error!("Failed to emit the end of the live measurements: {err:?}");
+                }
+            }
+            result
+        });
+
+        streams.insert(
+            port_name,
+            LiveStream {
+                control,
+                arduino_serial_number,
+                handle,
+            },
+        );
+        Ok(())
+    }
+
+    /// Takes the stream of the given port, to stop it
+    fn take(&self, port_name: &str) -> anyhow::Result<LiveStream> {
+        self.lock().remove(port_name).ok_or(anyhow!(
+            "There are no live measurements in progress for {port_name}"
+        ))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, LiveStream>> {
+        self.0.lock().expect("Live streams poisoned")
+    }
+}
+
+/// Starts streaming the sensor readings of the device, emitting a "live-measurement" event for each sample
+///
+/// The port stays busy until `stop_live_measurements` is called
+#[tauri::command]
+pub async fn start_live_measurements(
+    app: AppHandle,
+    device_profiles: State<'_, DeviceProfileStore>,
+    live_streams: State<'_, LiveStreams>,
+    port_name: String,
+    arduino_serial_number: String,
+    interval_ms: u32,
+) -> Result<(), String> {
+    let timezone = rtc_timezone_for_device(&app, arduino_serial_number.clone())
+        .await
+        .map_err(|err| err.to_string())?;
+    let profile = device_profiles.profile_for(Some(&arduino_serial_number));
+
+    live_streams
+        .start(
+            &app,
+            port_name,
+            arduino_serial_number,
+            profile,
+            timezone,
+            interval_ms,
+        )
+        .map_err(|err| err.to_string())
+}
+
+/// Stops the live stream of the given port, and saves its samples as a new measurement collection if `persist` is set
+#[tauri::command]
+pub async fn stop_live_measurements(
+    app: AppHandle,
+    live_streams: State<'_, LiveStreams>,
+    port_name: String,
+    persist: bool,
+) -> Result<StoppedLiveMeasurements, String> {
+    let stream = live_streams
+        .take(&port_name)
+        .map_err(|err| err.to_string())?;
+    // Waiting for the thread to finish blocks until the device confirms the end of the stream
+    let (arduino_serial_number, recording) =
+        tauri::async_runtime::spawn_blocking(move || stream.stop(&port_name))
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| err.to_string())?;
+
+    let created = match persist && !recording.measurements.is_empty() {
+        false => CreatedMeasurementCollections {
+            action_hashes: vec![],
+            skipped_measurements: 0,
+            rejected_measurements: 0,
+        },
+        true => call_living_power_zome(
+            &app,
+            "create_new_measurement_collections",
+            MeasurementCollection {
+                arduino_serial_number,
+                measurements: recording.measurements.clone(),
+                live: true,
+            },
+        )
+        .await
+        .map_err(|err| err.to_string())?,
+    };
+
+    Ok(StoppedLiveMeasurements {
+        recording,
+        action_hashes: created.action_hashes,
+        skipped_measurements: created.skipped_measurements,
+        rejected_measurements: created.rejected_measurements,
+    })
+}
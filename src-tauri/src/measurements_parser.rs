@@ -0,0 +1,518 @@
+use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use living_power_integrity::measurement_collection::Measurement;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::rtc_timezone::{from_rtc_year, RtcTimezone};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RejectedRow {
+    /// 1-based line number in the parsed file
+    pub line_number: usize,
+    pub raw: String,
+    pub reason: String,
+}
+
+/// Summary of the lines that didn't end up as measurements
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ParseReport {
+    pub rejected_rows: Vec<RejectedRow>,
+    pub header_lines: usize,
+    pub blank_lines: usize,
+    pub duplicate_lines: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParsedMeasurements {
+    pub measurements: Vec<Measurement>,
+    pub report: ParseReport,
+}
+
+/// Renames the header cells of a CSV file to column names the parser understands, like "Temp_F" to "Temperature (F)"
+///
+/// Header cells mapped to an empty name are ignored, and the ones not in the mapping are kept as they are
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ColumnMapping(pub BTreeMap<String, String>);
+
+impl ColumnMapping {
+    fn apply<'a>(&'a self, header_cell: &'a str) -> &'a str {
+        self.0
+            .get(clean_cell(header_cell))
+            .map_or(header_cell, |name| name.as_str())
+    }
+
+    fn apply_to_header(&self, header: &str) -> String {
+        header
+            .split(',')
+            .map(|cell| self.apply(cell))
+            .collect::<Vec<&str>>()
+            .join(",")
+    }
+}
+
+/// How a header cell was understood, to let the user map the columns that weren't
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HeaderColumn {
+    /// The header cell as it is in the file
+    pub header: String,
+    /// The canonical name of the column it holds after applying the mapping, `None` if it's ignored
+    pub column: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The first line of the file if it's a header row
+pub fn header_row(contents: &str) -> Option<&str> {
+    let first_line = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+    is_header_row(first_line).then_some(first_line)
+}
+
+/// Describes each cell of a header row, even if it's missing some of the required columns
+pub fn header_columns(header: &str, column_mapping: &ColumnMapping) -> Vec<HeaderColumn> {
+    header
+        .split(',')
+        .map(|cell| {
+            let (column, error) = match parse_column_name(column_mapping.apply(cell)) {
+                Ok(column) => (column.map(|column| column.name().to_string()), None),
+                Err(err) => (None, Some(err.to_string())),
+            };
+            HeaderColumn {
+                header: clean_cell(cell).to_string(),
+                column,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Incrementally parses the rows of a measurements CSV file as its bytes arrive
+#[derive(Default)]
+pub struct MeasurementsParser {
+    pending_line: Vec<u8>,
+    line_number: usize,
+    layout: CsvLayout,
+    timezone: RtcTimezone,
+    column_mapping: ColumnMapping,
+    /// Set when the first line of the file looks like a header row but couldn't be understood,
+    /// every row after it is rejected
+    layout_error: Option<String>,
+    seen_lines: HashSet<String>,
+    measurements: Vec<Measurement>,
+    report: ParseReport,
+}
+
+impl MeasurementsParser {
+    /// Dates and times in the rows are interpreted in the given timezone
+    pub fn new(timezone: RtcTimezone) -> Self {
+        Self {
+            timezone,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_column_mapping(mut self, column_mapping: ColumnMapping) -> Self {
+        self.column_mapping = column_mapping;
+        self
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.pending_line.extend_from_slice(bytes);
+
+        while let Some(end) = self.pending_line.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.pending_line.drain(..=end).collect();
+            self.parse_line(&line);
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.measurements.len()
+    }
+
+    pub fn parse_errors(&self) -> usize {
+        self.report.rejected_rows.len()
+    }
+
+    /// The measurement of the last complete row parsed so far
+    pub fn last_measurement(&self) -> Option<&Measurement> {
+        self.measurements.last()
+    }
+
+    pub fn timezone(&self) -> &RtcTimezone {
+        &self.timezone
+    }
+
+    /// Drops the bytes received after the last newline, if the rest of that line will be received again
+    pub fn discard_partial_line(&mut self) {
+        self.pending_line.clear();
+    }
+
+    /// Parses the last line even if it's not terminated by a newline
+    pub fn finish(mut self) -> anyhow::Result<ParsedMeasurements> {
+        let line = std::mem::take(&mut self.pending_line);
+        if !line.is_empty() {
+            self.parse_line(&line);
+        }
+        self.finish_incomplete()
+    }
+
+    /// Discards the last line if it's not terminated by a newline, as it may have been cut off
+    pub fn finish_incomplete(self) -> anyhow::Result<ParsedMeasurements> {
+        if let Some(layout_error) = self.layout_error {
+            return Err(anyhow!(layout_error));
+        }
+        Ok(ParsedMeasurements {
+            measurements: self.measurements,
+            report: self.report,
+        })
+    }
+
+    fn parse_line(&mut self, line: &[u8]) {
+        self.line_number += 1;
+        let line = String::from_utf8_lossy(line);
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() {
+            self.report.blank_lines += 1;
+            return;
+        }
+        if is_header_row(trimmed) {
+            let is_first_line = self.report.header_lines == 0 && self.seen_lines.is_empty();
+            match CsvLayout::from_header(&self.column_mapping.apply_to_header(trimmed)) {
+                Ok(layout) => {
+                    self.report.header_lines += 1;
+                    self.layout = layout;
+                    self.layout_error = None;
+                }
+                // Without its header the columns of the file are unknown
+                Err(err) if is_first_line => {
+                    self.report.header_lines += 1;
+                    self.layout_error = Some(format!("Line {}: {err}", self.line_number));
+                }
+                // Firmware messages or corrupted rows between the data rows
+                Err(err) => {
+                    self.seen_lines.insert(trimmed.to_string());
+                    self.reject(
+                        trimmed,
+                        anyhow!("Not a measurement row or a valid header row: {err}"),
+                    );
+                }
+            }
+            return;
+        }
+        if !self.seen_lines.insert(trimmed.to_string()) {
+            self.report.duplicate_lines += 1;
+            return;
+        }
+
+        let result = match &self.layout_error {
+            Some(layout_error) => Err(anyhow!(layout_error.clone())),
+            None => self.layout.parse_row(trimmed, &self.timezone),
+        };
+        match result {
+            Ok(measurement) => self.measurements.push(measurement),
+            Err(err) => self.reject(trimmed, err),
+        };
+    }
+
+    fn reject(&mut self, line: &str, err: anyhow::Error) {
+        log::warn!("Error reading the measurement line \"{line}\": {err:?}");
+        self.report.rejected_rows.push(RejectedRow {
+            line_number: self.line_number,
+            raw: line.to_string(),
+            reason: err.to_string(),
+        });
+    }
+}
+
+pub fn parse_csv_file_contents(
+    contents: String,
+    timezone: RtcTimezone,
+) -> anyhow::Result<ParsedMeasurements> {
+    parse_mapped_csv_file_contents(contents, timezone, ColumnMapping::default())
+}
+
+/// Parses the file after renaming the cells of its header rows with the given mapping
+pub fn parse_mapped_csv_file_contents(
+    contents: String,
+    timezone: RtcTimezone,
+    column_mapping: ColumnMapping,
+) -> anyhow::Result<ParsedMeasurements> {
+    let mut parser = MeasurementsParser::new(timezone).with_column_mapping(column_mapping);
+    parser.push(contents.as_bytes());
+    parser.finish()
+}
+
+/// Parses a single row written by `logData` in `bpv.ino`
+pub fn line_to_measurement(line: &str, timezone: &RtcTimezone) -> anyhow::Result<Measurement> {
+    CsvLayout::default().parse_row(line, timezone)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VoltageUnit {
+    Volts,
+    Millivolts,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Column {
+    Date,
+    Time,
+    DateTime,
+    Temperature(TemperatureUnit),
+    Humidity,
+    LightLevel,
+    Voltage(VoltageUnit),
+}
+
+impl Column {
+    /// A header cell that `parse_column_name` maps back to this column
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Date => "Date",
+            Column::Time => "Time",
+            Column::DateTime => "Timestamp",
+            Column::Temperature(TemperatureUnit::Celsius) => "Temperature (C)",
+            Column::Temperature(TemperatureUnit::Fahrenheit) => "Temperature (F)",
+            Column::Temperature(TemperatureUnit::Kelvin) => "Temperature (K)",
+            Column::Humidity => "Humidity (%)",
+            Column::LightLevel => "Light Level (lux)",
+            Column::Voltage(VoltageUnit::Volts) => "Voltage (V)",
+            Column::Voltage(VoltageUnit::Millivolts) => "Voltage (mV)",
+        }
+    }
+}
+
+/// Which measurement field each column of a CSV file holds, read from its header row
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvLayout {
+    /// `None` for the columns we don't know about, which are ignored
+    columns: Vec<Option<Column>>,
+}
+
+impl Default for CsvLayout {
+    /// The columns written by `bpv.ino`, used for files or transfers without a header row
+    fn default() -> Self {
+        Self {
+            columns: vec![
+                Some(Column::Date),
+                Some(Column::Time),
+                Some(Column::Temperature(TemperatureUnit::Celsius)),
+                Some(Column::Humidity),
+                Some(Column::LightLevel),
+                Some(Column::Voltage(VoltageUnit::Volts)),
+            ],
+        }
+    }
+}
+
+impl CsvLayout {
+    pub fn from_header(header: &str) -> anyhow::Result<Self> {
+        let columns = header
+            .split(',')
+            .map(parse_column_name)
+            .collect::<anyhow::Result<Vec<Option<Column>>>>()?;
+
+        let has = |predicate: fn(&Column) -> bool| columns.iter().flatten().any(predicate);
+
+        let mut missing: Vec<&str> = vec![];
+        let has_date_time = has(|c| matches!(c, Column::DateTime))
+            || (has(|c| matches!(c, Column::Date)) && has(|c| matches!(c, Column::Time)));
+        if !has_date_time {
+            missing.push("Date and Time (or Timestamp)");
+        }
+        if !has(|c| matches!(c, Column::Temperature(_))) {
+            missing.push("Temperature");
+        }
+        if !has(|c| matches!(c, Column::Humidity)) {
+            missing.push("Humidity");
+        }
+        if !has(|c| matches!(c, Column::LightLevel)) {
+            missing.push("Light Level");
+        }
+        if !has(|c| matches!(c, Column::Voltage(_))) {
+            missing.push("Voltage");
+        }
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "The header row is missing the required columns: {}",
+                missing.join(", ")
+            ));
+        }
+
+        Ok(Self { columns })
+    }
+
+    pub fn parse_row(&self, line: &str, timezone: &RtcTimezone) -> anyhow::Result<Measurement> {
+        let cells: Vec<&str> = line.split(',').map(clean_cell).collect();
+
+        // Unknown columns at the end of the row may be left out
+        let required_len = self
+            .columns
+            .iter()
+            .rposition(|column| column.is_some())
+            .map_or(0, |position| position + 1);
+        if cells.len() < required_len {
+            return Err(anyhow!(
+                "Expected at least {required_len} columns, found {}",
+                cells.len()
+            ));
+        }
+
+        let mut date: Option<NaiveDate> = None;
+        let mut time: Option<NaiveTime> = None;
+        let mut date_time: Option<NaiveDateTime> = None;
+        let mut temperature: Option<f64> = None;
+        let mut humidity: Option<f64> = None;
+        let mut light_level: Option<f64> = None;
+        let mut voltage: Option<f64> = None;
+
+        for (column, cell) in self.columns.iter().zip(cells) {
+            let Some(column) = column else {
+                continue;
+            };
+            match column {
+                Column::Date => {
+                    date = Some(
+                        NaiveDate::parse_from_str(cell, "%Y-%m-%d")
+                            .map_err(|_| anyhow!("Invalid date \"{cell}\""))?,
+                    )
+                }
+                Column::Time => {
+                    time = Some(
+                        NaiveTime::parse_from_str(cell, "%H:%M:%S")
+                            .map_err(|_| anyhow!("Invalid time \"{cell}\""))?,
+                    )
+                }
+                Column::DateTime => {
+                    date_time = Some(
+                        NaiveDateTime::parse_from_str(cell, "%Y-%m-%d %H:%M:%S")
+                            .or_else(|_| NaiveDateTime::parse_from_str(cell, "%Y-%m-%dT%H:%M:%S"))
+                            .map_err(|_| anyhow!("Invalid timestamp \"{cell}\""))?,
+                    )
+                }
+                Column::Temperature(unit) => {
+                    let value = parse_number(cell, "temperature")?;
+                    temperature = Some(match unit {
+                        TemperatureUnit::Celsius => value,
+                        TemperatureUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+                        TemperatureUnit::Kelvin => value - 273.15,
+                    });
+                }
+                Column::Humidity => humidity = Some(parse_number(cell, "humidity")?),
+                Column::LightLevel => light_level = Some(parse_number(cell, "light level")?),
+                Column::Voltage(unit) => {
+                    let value = parse_number(cell, "voltage")?;
+                    voltage = Some(match unit {
+                        VoltageUnit::Volts => value,
+                        VoltageUnit::Millivolts => value / 1000.0,
+                    });
+                }
+            }
+        }
+
+        let date_time = date_time
+            .or(date.zip(time).map(|(date, time)| date.and_time(time)))
+            .ok_or(anyhow!("Missing date and time"))?;
+        let timestamp = timezone.to_timestamp(&from_rtc_year(date_time))?;
+
+        let temperature = temperature.ok_or(anyhow!("Missing temperature"))?;
+        let humidity = humidity.ok_or(anyhow!("Missing humidity"))?;
+        let lightlevel = light_level.ok_or(anyhow!("Missing light level"))?;
+        let voltage = voltage.ok_or(anyhow!("Missing voltage"))?;
+
+        let measurement = Measurement {
+            timestamp,
+            humidity_percentage: to_milli_units(humidity, "humidity")?,
+            temperature_celsius: to_milli_units(temperature, "temperature")?,
+            light_level_lux: to_milli_units(lightlevel, "light level")?,
+            voltage_millivolts: to_milli_units(voltage, "voltage")?,
+            raw_timestamp: None,
+        };
+        Ok(measurement)
+    }
+}
+
+/// Data rows start with their date, so a line starting with a letter may be a header row
+fn is_header_row(line: &str) -> bool {
+    clean_cell(line).starts_with(|c: char| c.is_alphabetic())
+}
+
+fn clean_cell(cell: &str) -> &str {
+    cell.trim().trim_matches('"').trim()
+}
+
+/// Accepts negative numbers and scientific notation, but not NaN or infinite values
+fn parse_number(cell: &str, name: &str) -> anyhow::Result<f64> {
+    let value: f64 = cell
+        .parse()
+        .map_err(|_| anyhow!("Invalid {name} value \"{cell}\""))?;
+    if !value.is_finite() {
+        return Err(anyhow!("Invalid {name} value \"{cell}\""));
+    }
+    Ok(value)
+}
+
+/// Measurements are stored as thousandths of their unit, to avoid floats in the DHT
+fn to_milli_units(value: f64, name: &str) -> anyhow::Result<i32> {
+    let milli_units = (value * 1000.0).round();
+    if !(i32::MIN as f64..=i32::MAX as f64).contains(&milli_units) {
+        return Err(anyhow!("The {name} value {value} is out of range"));
+    }
+    Ok(milli_units as i32)
+}
+
+/// Maps a header cell like "Temperature (C)" to the column it holds
+///
+/// Returns `None` for unknown columns, and an error for known columns with unsupported units
+fn parse_column_name(header_cell: &str) -> anyhow::Result<Option<Column>> {
+    let header_cell = clean_cell(header_cell).to_lowercase();
+    let (name, unit) = match header_cell.find(['(', '[']) {
+        Some(start) => (
+            header_cell[..start].trim(),
+            header_cell[start + 1..].trim_end_matches([')', ']']).trim(),
+        ),
+        None => (header_cell.as_str(), ""),
+    };
+    let name = name.replace(['_', '-'], " ");
+    let unit = unit.trim_start_matches('°');
+
+    let unsupported_unit = || anyhow!("Unsupported unit \"{unit}\" for column \"{header_cell}\"");
+
+    let column = match name.as_str() {
+        "date" => Column::Date,
+        "time" => Column::Time,
+        "datetime" | "date time" | "timestamp" => Column::DateTime,
+        "temperature" | "temp" => Column::Temperature(match unit {
+            "" | "c" | "celsius" => TemperatureUnit::Celsius,
+            "f" | "fahrenheit" => TemperatureUnit::Fahrenheit,
+            "k" | "kelvin" => TemperatureUnit::Kelvin,
+            _ => return Err(unsupported_unit()),
+        }),
+        "humidity" | "relative humidity" | "rh" => match unit {
+            "" | "%" | "% rh" => Column::Humidity,
+            _ => return Err(unsupported_unit()),
+        },
+        "light level" | "lightlevel" | "light" | "illuminance" => match unit {
+            "" | "lux" | "lx" => Column::LightLevel,
+            _ => return Err(unsupported_unit()),
+        },
+        "voltage" | "volt" => Column::Voltage(match unit {
+            "" | "v" => VoltageUnit::Volts,
+            "mv" => VoltageUnit::Millivolts,
+            _ => return Err(unsupported_unit()),
+        }),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(column))
+}
@@ -0,0 +1,132 @@
+use anyhow::anyhow;
+use chrono::{
+    DateTime, Datelike, FixedOffset, Local, LocalResult, NaiveDateTime, Offset, TimeDelta,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use holochain_types::prelude::Timestamp;
+use living_power_integrity::DeviceTimezone;
+use tauri::AppHandle;
+
+use crate::zome_calls::call_living_power_zome;
+
+/// The timezone used to interpret the dates and times written by the real time clock of a BPV device
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum RtcTimezone {
+    /// The timezone of this computer, for devices that don't have one configured
+    #[default]
+    Local,
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl RtcTimezone {
+    pub fn from_device_timezone(timezone: Option<DeviceTimezone>) -> anyhow::Result<Self> {
+        match timezone {
+            None => Ok(Self::Local),
+            Some(DeviceTimezone::Named { name }) => {
+                let timezone: Tz = name
+                    .parse()
+                    .map_err(|_| anyhow!("Unknown timezone \"{name}\""))?;
+                Ok(Self::Named(timezone))
+            }
+            Some(DeviceTimezone::UtcOffset { offset_minutes }) => {
+                let offset = FixedOffset::east_opt(offset_minutes * 60)
+                    .ok_or(anyhow!("Invalid UTC offset of {offset_minutes} minutes"))?;
+                Ok(Self::Fixed(offset))
+            }
+        }
+    }
+
+    /// Converts a date and time read from the device to a timestamp
+    ///
+    /// Times that happen twice when the clocks go back resolve to their first occurrence,
+    /// and times that don't exist when the clocks go forward are read with the offset from before the change
+    pub fn to_timestamp(&self, date_time: &NaiveDateTime) -> anyhow::Result<Timestamp> {
+        let utc = match self {
+            Self::Local => resolve_local_date_time(&Local, date_time),
+            Self::Named(timezone) => resolve_local_date_time(timezone, date_time),
+            Self::Fixed(offset) => resolve_local_date_time(offset, date_time),
+        }
+        .ok_or(anyhow!("Date out of range: {date_time}"))?;
+
+        Ok(Timestamp::from_micros(utc.timestamp_micros()))
+    }
+
+    /// Converts a timestamp to the date and time the device clock would show at that instant
+    pub fn to_device_date_time(&self, timestamp: Timestamp) -> anyhow::Result<NaiveDateTime> {
+        let utc = DateTime::<Utc>::from_timestamp_micros(timestamp.as_micros())
+            .ok_or(anyhow!("Invalid timestamp {timestamp}"))?;
+
+        let date_time = match self {
+            Self::Local => utc.with_timezone(&Local).naive_local(),
+            Self::Named(timezone) => utc.with_timezone(timezone).naive_local(),
+            Self::Fixed(offset) => utc.with_timezone(offset).naive_local(),
+        };
+        Ok(date_time)
+    }
+}
+
+/// The RTC only keeps the last two digits of the year, which `bpv.ino` writes padded to four digits like "0024"
+///
+/// Moves those dates to the 2000s, and leaves dates with a full year as they are
+pub fn from_rtc_year(date_time: NaiveDateTime) -> NaiveDateTime {
+    if date_time.year() >= 100 {
+        return date_time;
+    }
+    date_time
+        .with_year(date_time.year() + 2000)
+        .unwrap_or(date_time)
+}
+
+/// Formats the date time as `formatTimestamp` in `bpv.ino` does, with the two-digit year of the RTC
+pub fn format_rtc_date_time(date_time: &NaiveDateTime) -> String {
+    format!(
+        "{:04}-{}",
+        date_time.year() % 100,
+        date_time.format("%m-%d,%H:%M:%S")
+    )
+}
+
+fn resolve_local_date_time<T: TimeZone>(
+    timezone: &T,
+    date_time: &NaiveDateTime,
+) -> Option<DateTime<Utc>> {
+    match timezone.from_local_datetime(date_time) {
+        LocalResult::Single(date_time) => Some(date_time.to_utc()),
+        LocalResult::Ambiguous(earliest, _) => Some(earliest.to_utc()),
+        LocalResult::None => {
+            // Daylight saving changes are months apart, so a day before we are sure to be on the old offset
+            let before_change = date_time.checked_sub_signed(TimeDelta::days(1))?;
+            let offset = timezone
+                .offset_from_local_datetime(&before_change)
+                .earliest()?;
+            Some(
+                timezone
+                    .from_utc_datetime(&(*date_time - offset.fix()))
+                    .to_utc(),
+            )
+        }
+    }
+}
+
+/// Fetches the timezone configured for the given device, or the local timezone if it has none
+pub async fn rtc_timezone_for_device(
+    app: &AppHandle,
+    arduino_serial_number: String,
+) -> anyhow::Result<RtcTimezone> {
+    let timezone: Option<DeviceTimezone> =
+        call_living_power_zome(app, "get_bpv_device_timezone", arduino_serial_number).await?;
+    RtcTimezone::from_device_timezone(timezone)
+}
+
+/// Same as `rtc_timezone_for_device`, with the local timezone for boards whose serial number is unknown
+pub async fn rtc_timezone_for_serial_number(
+    app: &AppHandle,
+    arduino_serial_number: Option<String>,
+) -> anyhow::Result<RtcTimezone> {
+    match arduino_serial_number {
+        Some(arduino_serial_number) => rtc_timezone_for_device(app, arduino_serial_number).await,
+        None => Ok(RtcTimezone::Local),
+    }
+}
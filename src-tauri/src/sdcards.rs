@@ -1,43 +1,233 @@
-use std::{collections::BTreeMap, fs::read_to_string, path::PathBuf};
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
 
-use living_power_integrity::Measurement;
+use anyhow::anyhow;
+use serde::Serialize;
+use tauri::AppHandle;
 
-use crate::collect_measurements::parse_csv_file_contents;
+use crate::measurements_parser::{MeasurementsParser, ParsedMeasurements};
+use crate::rtc_timezone::{rtc_timezone_for_device, RtcTimezone};
+
+/// Written by `bpv.ino` next to its data file, with a `key=value` pair per line
+///
+/// The SD library only supports 8.3 file names, so it has no extension
+const MANIFEST_FILE_NAME: &str = "manifest";
+const SERIAL_FILE_NAME: &str = "serial";
+const DATA_FILE_NAME: &str = "data.csv";
+
+/// A directory of a mounted volume that holds the data files of a BPV device
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MeasurementsSdcard {
+    pub mountpoint: PathBuf,
+    /// The mountpoint itself, or one of its subdirectories
+    pub directory: PathBuf,
+    pub arduino_serial_number: Option<String>,
+    /// The current data file first, followed by the archived ones in name order
+    pub data_files: Vec<PathBuf>,
+    /// Why the card couldn't be read, the other fields may be incomplete
+    pub error: Option<String>,
+}
 
 #[tauri::command]
-pub fn list_measurements_sdcards() -> Result<BTreeMap<String, PathBuf>, String> {
-    internal_list_measurements_sdcards().map_err(|err| err.to_string())
+pub fn list_measurements_sdcards() -> Result<Vec<MeasurementsSdcard>, String> {
+    let mountpaths = mountpoints::mountpaths().map_err(|err| err.to_string())?;
+    Ok(internal_list_measurements_sdcards(mountpaths))
+}
+
+/// Looks for BPV directories at the root of each mountpath and in its direct subdirectories
+///
+/// Errors reading a single card are reported in its `error` instead of failing the whole list
+pub fn internal_list_measurements_sdcards(mountpaths: Vec<PathBuf>) -> Vec<MeasurementsSdcard> {
+    let mut sdcards = vec![];
+
+    for mountpath in mountpaths {
+        let mut directories = vec![mountpath.clone()];
+        match subdirectories(&mountpath) {
+            Ok(subdirectories) => directories.extend(subdirectories),
+            Err(err) => log::debug!("Could not list the directories of {mountpath:?}: {err:?}"),
+        }
+
+        for directory in directories {
+            if let Some(sdcard) = read_measurements_sdcard(&mountpath, &directory) {
+                sdcards.push(sdcard);
+            }
+        }
+    }
+
+    sdcards
 }
-fn internal_list_measurements_sdcards() -> anyhow::Result<BTreeMap<String, PathBuf>> {
-    let mountpaths = mountpoints::mountpaths()?;
 
-    let measurements_sdcards: Vec<PathBuf> = mountpaths
+/// Returns `None` if the directory doesn't belong to a BPV device
+///
+/// It does if it has a manifest for a BPV device, or a `serial` file and at least one data file
+pub fn read_measurements_sdcard(mountpoint: &Path, directory: &Path) -> Option<MeasurementsSdcard> {
+    let entries = match read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!("Could not read the directory {directory:?}: {err:?}");
+            return None;
+        }
+    };
+
+    let mut manifest = None;
+    let mut serial = None;
+    let mut current_data_file = None;
+    let mut archived_data_files = vec![];
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy().to_lowercase();
+        if file_name == MANIFEST_FILE_NAME {
+            manifest = Some(path);
+        } else if file_name == SERIAL_FILE_NAME {
+            serial = Some(path);
+        } else if file_name == DATA_FILE_NAME {
+            current_data_file = Some(path);
+        } else if is_archived_data_file(&file_name) {
+            archived_data_files.push(path);
+        }
+    }
+    archived_data_files.sort();
+    let data_files: Vec<PathBuf> = current_data_file
         .into_iter()
-        .filter(|mountpath| {
-            mountpath.join("data.csv").exists() && mountpath.join("serial").exists()
-        })
+        .chain(archived_data_files)
+        .collect();
+
+    let mut sdcard = MeasurementsSdcard {
+        mountpoint: mountpoint.to_path_buf(),
+        directory: directory.to_path_buf(),
+        arduino_serial_number: None,
+        data_files,
+        error: None,
+    };
+
+    let identified = match (manifest, serial) {
+        (Some(manifest), _) => read_manifest(&manifest).and_then(|manifest| {
+            if !manifest.is_bpv_device() {
+                return Ok(None);
+            }
+            manifest
+                .serial_number()
+                .ok_or(anyhow!("The manifest has no serial number"))
+                .map(Some)
+        }),
+        (None, Some(serial)) if !sdcard.data_files.is_empty() => {
+            read_serial_number(&serial).map(Some)
+        }
+        _ => Ok(None),
+    };
+
+    match identified {
+        Ok(None) => return None,
+        Ok(Some(serial_number)) => sdcard.arduino_serial_number = Some(serial_number),
+        Err(err) => sdcard.error = Some(err.to_string()),
+    }
+    if sdcard.error.is_none() && sdcard.data_files.is_empty() {
+        sdcard.error = Some(String::from("The card has no data files"));
+    }
+
+    Some(sdcard)
+}
+
+/// `data_2024-05.csv` style rotated files, and `YYMMDDnn.CSV` archives made by the 'a' command of `bpv.ino`
+fn is_archived_data_file(file_name: &str) -> bool {
+    let Some(stem) = file_name.strip_suffix(".csv") else {
+        return false;
+    };
+    stem.starts_with("data_") || (stem.len() == 8 && stem.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn subdirectories(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut subdirectories: Vec<PathBuf> = read_dir(path)?
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
         .collect();
+    subdirectories.sort();
+    Ok(subdirectories)
+}
 
-    let mut sdcards: BTreeMap<String, PathBuf> = BTreeMap::new();
+struct Manifest(Vec<(String, String)>);
+
+impl Manifest {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
 
-    for sdcard in measurements_sdcards {
-        let serial_number = read_to_string(sdcard.join("serial"))?.trim().to_string();
-        sdcards.insert(serial_number, sdcard);
+    fn is_bpv_device(&self) -> bool {
+        self.get("device")
+            .is_some_and(|device| device.eq_ignore_ascii_case("bpv"))
     }
 
-    Ok(sdcards)
+    fn serial_number(&self) -> Option<String> {
+        self.get("serial_number")
+            .filter(|serial_number| !serial_number.is_empty())
+            .map(String::from)
+    }
+}
+
+fn read_manifest(path: &Path) -> anyhow::Result<Manifest> {
+    let contents = read_to_string(path)
+        .map_err(|err| anyhow!("Could not read the manifest {path:?}: {err}"))?;
+    let pairs = contents
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    Ok(Manifest(pairs))
+}
+
+fn read_serial_number(path: &Path) -> anyhow::Result<String> {
+    let serial_number = read_to_string(path)
+        .map_err(|err| anyhow!("Could not read the serial number {path:?}: {err}"))?
+        .trim()
+        .to_string();
+    if serial_number.is_empty() {
+        return Err(anyhow!("The serial number file {path:?} is empty"));
+    }
+    Ok(serial_number)
 }
 
 #[tauri::command]
 pub async fn collect_measurements_from_sdcard(
-    mountpoint: PathBuf,
-) -> Result<Vec<Measurement>, String> {
-    internal_collect_measurements_from_sdcard(mountpoint).map_err(|err| err.to_string())
+    app: AppHandle,
+    directory: PathBuf,
+) -> Result<ParsedMeasurements, String> {
+    let sdcard = read_measurements_sdcard(&directory, &directory)
+        .ok_or(format!("{directory:?} is not a BPV SD card"))?;
+    let serial_number = match (sdcard.error, sdcard.arduino_serial_number) {
+        (Some(error), _) => return Err(error),
+        (None, Some(serial_number)) => serial_number,
+        (None, None) => return Err(format!("{directory:?} has no serial number")),
+    };
+    let timezone = rtc_timezone_for_device(&app, serial_number)
+        .await
+        .map_err(|err| err.to_string())?;
+    internal_collect_measurements_from_sdcard(&sdcard.data_files, timezone)
+        .map_err(|err| err.to_string())
 }
 
-fn internal_collect_measurements_from_sdcard(
-    mountpoint: PathBuf,
-) -> anyhow::Result<Vec<Measurement>> {
-    let contents = std::fs::read_to_string(mountpoint.join("data.csv"))?;
-    parse_csv_file_contents(contents.trim().to_string())
+/// Parses all the data files together, so rows repeated across the archives are only returned once
+pub fn internal_collect_measurements_from_sdcard(
+    data_files: &[PathBuf],
+    timezone: RtcTimezone,
+) -> anyhow::Result<ParsedMeasurements> {
+    let mut parser = MeasurementsParser::new(timezone);
+    for data_file in data_files {
+        let contents = std::fs::read(data_file)
+            .map_err(|err| anyhow!("Could not read {data_file:?}: {err}"))?;
+        parser.push(&contents);
+        // Otherwise the last row of a file would be joined with the header of the next one
+        if !contents.ends_with(b"\n") {
+            parser.push(b"\n");
+        }
+    }
+    parser.finish()
 }
@@ -0,0 +1,96 @@
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use serialport::{SerialPort, SerialPortInfo};
+
+/// An open connection to a serial device
+pub trait SerialConnection: Read + Write + Send {
+    fn set_timeout(&mut self, timeout: Duration) -> anyhow::Result<()>;
+
+    /// Closes the connection when it's dropped instead of keeping it open for the next command,
+    /// for when the device may still be sending the response to a previous one
+    fn discard(&mut self) {}
+}
+
+/// Opens connections to serial devices
+///
+/// Production code uses [`SerialportTransport`], tests can swap it for a simulated device
+pub trait SerialTransport: Send + Sync {
+    fn available_ports(&self) -> anyhow::Result<Vec<SerialPortInfo>>;
+
+    fn open(
+        &self,
+        port_name: &str,
+        baud_rate: u32,
+        timeout: Duration,
+    ) -> anyhow::Result<Box<dyn SerialConnection>>;
+
+    /// Same as `open`, but fails with [`PortBusy`] right away if another command is using the port
+    fn try_open(
+        &self,
+        port_name: &str,
+        baud_rate: u32,
+        timeout: Duration,
+    ) -> anyhow::Result<Box<dyn SerialConnection>> {
+        self.open(port_name, baud_rate, timeout)
+    }
+}
+
+/// Another command is using the port
+#[derive(Debug)]
+pub struct PortBusy(pub String);
+
+impl Display for PortBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The device at {} is busy with another command", self.0)
+    }
+}
+
+impl std::error::Error for PortBusy {}
+
+/// Talks to real hardware through the `serialport` crate
+pub struct SerialportTransport;
+
+impl SerialTransport for SerialportTransport {
+    fn available_ports(&self) -> anyhow::Result<Vec<SerialPortInfo>> {
+        Ok(serialport::available_ports()?)
+    }
+
+    fn open(
+        &self,
+        port_name: &str,
+        baud_rate: u32,
+        timeout: Duration,
+    ) -> anyhow::Result<Box<dyn SerialConnection>> {
+        let port = serialport::new(port_name, baud_rate)
+            .timeout(timeout)
+            .open()?;
+        Ok(Box::new(SerialportConnection(port)))
+    }
+}
+
+struct SerialportConnection(Box<dyn SerialPort>);
+
+impl Read for SerialportConnection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for SerialportConnection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl SerialConnection for SerialportConnection {
+    fn set_timeout(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        self.0.set_timeout(timeout)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serialport::SerialPortInfo;
+use tauri::{AppHandle, Manager};
+
+use crate::serial_transport::{PortBusy, SerialConnection, SerialTransport};
+
+/// The BPV boards go into deep sleep if they don't receive anything for 5 seconds (`SYNC_IDLE_TIMEOUT` in the firmware)
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a command waits for another one to finish with the same port
+///
+/// Collecting a big data file takes a while, so this is longer than any single read timeout
+const BUSY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Owns one open connection per BPV port, and lends it to a single command at a time
+///
+/// Between commands, the ports of the boards that are kept alive receive a 'p' regularly,
+/// so that they know they are still connected to a computer and they don't go into deep sleep
+pub struct SessionManager {
+    transport: Box<dyn SerialTransport>,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+}
+
+#[derive(Default)]
+struct Session {
+    state: Mutex<SessionState>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct SessionState {
+    /// `None` while a command is using it, or before it's opened
+    connection: Option<OpenConnection>,
+    in_use: bool,
+    /// Set when the port is closed while a command is using it, so that its connection is dropped once it's returned
+    closed: bool,
+    keep_alive: bool,
+    /// The baud rate of the device profile, to open the connection with for the keepalives
+    baud_rate: u32,
+}
+
+struct OpenConnection {
+    connection: Box<dyn SerialConnection>,
+    baud_rate: u32,
+}
+
+impl Session {
+    fn lock(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().expect("Session state poisoned")
+    }
+}
+
+impl SessionManager {
+    pub fn new(transport: impl SerialTransport + 'static) -> Self {
+        Self {
+            transport: Box::new(transport),
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Starts keeping the connection to the given port open and sending keepalives to it at the given baud rate
+    pub fn keep_alive(&self, port_name: &str, baud_rate: u32) {
+        let session = self.session(port_name);
+        let mut state = session.lock();
+        state.keep_alive = true;
+        state.baud_rate = baud_rate;
+    }
+
+    /// Closes the connection to the given port, once the command using it finishes if there is one
+    ///
+    /// The session itself is kept, so that a command for a device reconnected to the same port
+    /// still waits for the one using the old connection
+    pub fn close(&self, port_name: &str) {
+        let Some(session) = self.lock().get(port_name).cloned() else {
+            return;
+        };
+        let mut state = session.lock();
+        state.keep_alive = false;
+        state.connection = None;
+        state.closed = state.in_use;
+    }
+
+    /// Sends a keepalive to every port that is kept alive and not in use by a command
+    pub fn send_keepalives(&self) {
+        let sessions: Vec<(String, Arc<Session>)> = self
+            .lock()
+            .iter()
+            .map(|(port_name, session)| (port_name.clone(), session.clone()))
+            .collect();
+
+        for (port_name, session) in sessions {
+            let mut state = session.lock();
+            if !state.keep_alive || state.in_use {
+                continue;
+            }
+            let baud_rate = state.baud_rate;
+            if state
+                .connection
+                .as_ref()
+                .is_some_and(|open| open.baud_rate != baud_rate)
+            {
+                state.connection = None;
+            }
+            if state.connection.is_none() {
+                match self.transport.open(&port_name, baud_rate, Duration::ZERO) {
+                    Ok(connection) => {
+                        state.connection = Some(OpenConnection {
+                            connection,
+                            baud_rate,
+                        })
+                    }
+                    Err(err) => {
+                        log::debug!("Failed to open {port_name} to send keepalives: {err:?}");
+                        continue;
+                    }
+                }
+            }
+            if let Some(open) = &mut state.connection {
+                if open.connection.write_all(b"p").is_err() {
+                    // Reopen it on the next keepalive
+                    state.connection = None;
+                }
+            }
+        }
+    }
+
+    /// Lends the connection to the port once no other command is using it, waiting up to `busy_timeout`
+    fn lend(
+        &self,
+        port_name: &str,
+        baud_rate: u32,
+        timeout: Duration,
+        busy_timeout: Duration,
+    ) -> anyhow::Result<Box<dyn SerialConnection>> {
+        let session = self.session(port_name);
+
+        let deadline = Instant::now() + busy_timeout;
+        let mut state = session.lock();
+        while state.in_use {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(PortBusy(port_name.to_string()).into());
+            }
+            state = session
+                .available
+                .wait_timeout(state, remaining)
+                .expect("Session state poisoned")
+                .0;
+        }
+
+        // A connection kept open at another baud rate can't be reused
+        let connection = match state.connection.take() {
+            Some(open) if open.baud_rate == baud_rate => open.connection,
+            _ => self.transport.open(port_name, baud_rate, timeout)?,
+        };
+        state.in_use = true;
+        drop(state);
+
+        let mut connection = SessionConnection {
+            session,
+            connection: Some(connection),
+            baud_rate,
+            failed: false,
+        };
+        connection.set_timeout(timeout)?;
+        Ok(Box::new(connection))
+    }
+
+    fn session(&self, port_name: &str) -> Arc<Session> {
+        self.lock()
+            .entry(port_name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<Session>>> {
+        self.sessions.lock().expect("Sessions poisoned")
+    }
+}
+
+impl SerialTransport for SessionManager {
+    fn available_ports(&self) -> anyhow::Result<Vec<SerialPortInfo>> {
+        self.transport.available_ports()
+    }
+
+    /// Waits until no other command is using the port, and lends its connection until the returned one is dropped
+    fn open(
+        &self,
+        port_name: &str,
+        baud_rate: u32,
+        timeout: Duration,
+    ) -> anyhow::Result<Box<dyn SerialConnection>> {
+        self.lend(port_name, baud_rate, timeout, BUSY_TIMEOUT)
+    }
+
+    fn try_open(
+        &self,
+        port_name: &str,
+        baud_rate: u32,
+        timeout: Duration,
+    ) -> anyhow::Result<Box<dyn SerialConnection>> {
+        self.lend(port_name, baud_rate, timeout, Duration::ZERO)
+    }
+}
+
+/// A connection lent to a command, returned to its session when dropped
+struct SessionConnection {
+    session: Arc<Session>,
+    connection: Option<Box<dyn SerialConnection>>,
+    baud_rate: u32,
+    /// Set when the connection broke, so that it's reopened instead of reused
+    failed: bool,
+}
+
+impl SessionConnection {
+    fn connection(&mut self) -> &mut Box<dyn SerialConnection> {
+        self.connection
+            .as_mut()
+            .expect("Connection is only taken when dropped")
+    }
+
+    fn check<T>(&mut self, result: std::io::Result<T>) -> std::io::Result<T> {
+        if let Err(err) = &result {
+            if !matches!(
+                err.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            ) {
+                self.failed = true;
+            }
+        }
+        result
+    }
+}
+
+impl Read for SessionConnection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let result = self.connection().read(buf);
+        self.check(result)
+    }
+}
+
+impl Write for SessionConnection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let result = self.connection().write(buf);
+        self.check(result)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let result = self.connection().flush();
+        self.check(result)
+    }
+}
+
+impl SerialConnection for SessionConnection {
+    fn set_timeout(&mut self, timeout: Duration) -> anyhow::Result<()> {
+        self.connection().set_timeout(timeout)
+    }
+
+    fn discard(&mut self) {
+        self.failed = true;
+    }
+}
+
+impl Drop for SessionConnection {
+    fn drop(&mut self) {
+        let mut state = self.session.lock();
+        state.in_use = false;
+        // Ports that aren't kept alive are closed right away, so that they are free for other apps
+        if state.keep_alive && !self.failed && !state.closed {
+            state.connection = self.connection.take().map(|connection| OpenConnection {
+                connection,
+                baud_rate: self.baud_rate,
+            });
+        }
+        state.closed = false;
+        drop(state);
+        self.session.available.notify_one();
+    }
+}
+
+/// Sends keepalives to the BPV boards in a background thread for as long as the app runs
+pub fn spawn_keepalives(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        app.state::<SessionManager>().send_keepalives();
+        std::thread::sleep(KEEPALIVE_INTERVAL);
+    });
+}
@@ -0,0 +1,45 @@
+use anyhow::anyhow;
+use holochain_client::ZomeCallTarget;
+use holochain_types::prelude::{ActionHash, ExternIO};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_holochain::HolochainExt;
+
+use crate::app_id;
+
+/// Calls a function of the `living_power` zome in our installed hApp
+pub async fn call_living_power_zome<P, R>(
+    handle: &AppHandle,
+    fn_name: &str,
+    payload: P,
+) -> anyhow::Result<R>
+where
+    P: Serialize + std::fmt::Debug,
+    R: DeserializeOwned + std::fmt::Debug,
+{
+    let app_ws = handle.holochain()?.app_websocket(app_id()).await?;
+    let result = app_ws
+        .call_zome(
+            ZomeCallTarget::RoleName("living_power".into()),
+            "living_power".into(),
+            fn_name.into(),
+            ExternIO::encode(payload)?,
+        )
+        .await
+        .map_err(|err| anyhow!("Error calling zome function {fn_name}: {err:?}"))?;
+
+    let r: R = result
+        .decode()
+        .map_err(|err| anyhow!("Error decoding result from zome function {fn_name}: {err:?}"))?;
+    Ok(r)
+}
+
+/// What the `create_new_measurement_collections` zome function returns
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatedMeasurementCollections {
+    pub action_hashes: Vec<ActionHash>,
+    /// Measurements that were not committed because the device already had one at their timestamp
+    pub skipped_measurements: usize,
+    /// Measurements that were not committed because they are invalid, like the ones from before 2000 or in the future
+    pub rejected_measurements: usize,
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use tauri_app_lib::archive::{
+    internal_archive_data_file, internal_get_data_file_summary, verify_collection,
+};
+use tauri_app_lib::collect_measurements::internal_collect_measurements;
+use tauri_app_lib::collection_progress::CollectionControl;
+use tauri_app_lib::device_profiles::DeviceProfile;
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+
+const PORT_NAME: &str = "/dev/ttyACM0";
+
+const HEADER: &str = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r\n";
+
+const DATA_CSV: &str = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+2024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+2024-05-01,12:00:00,23.25,43.10,512.75,0.547\r
+";
+
+#[test]
+fn verified_collection_is_archived() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+    let summary =
+        internal_get_data_file_summary(&device, PORT_NAME.into(), &DeviceProfile::default())
+            .unwrap();
+
+    assert_eq!(summary.rows, 3);
+    verify_collection(&collected, &summary).unwrap();
+
+    let archive_file_name = internal_archive_data_file(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        summary.checksum,
+    )
+    .unwrap();
+
+    assert_eq!(
+        device.archives(),
+        vec![(archive_file_name.clone(), DATA_CSV.to_string())]
+    );
+    assert!(archive_file_name.ends_with("00.CSV"));
+    assert_eq!(device.data_csv(), HEADER);
+}
+
+#[test]
+fn data_file_is_not_archived_if_it_changed_after_the_collection() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+    device.log_row("2024-05-01,13:00:00,23.50,42.90,498.00,0.551");
+
+    let summary =
+        internal_get_data_file_summary(&device, PORT_NAME.into(), &DeviceProfile::default())
+            .unwrap();
+    assert!(verify_collection(&collected, &summary).is_err());
+
+    // Even if the computer asks for it, the device checks the data file wasn't modified since
+    let result = internal_archive_data_file(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        collected.checksum,
+    );
+
+    assert!(result.unwrap_err().to_string().contains("changed"));
+    assert!(device.archives().is_empty());
+    assert!(device.data_csv().ends_with("0.551\r\n"));
+}
+
+#[test]
+fn collection_with_unreadable_rows_is_not_verified() {
+    let data_csv = format!("{DATA_CSV}2024-05-01,13:00:00,not a number,42.90,498.00,0.551\r\n");
+    let device = SimulatedBpvDevice::new(PORT_NAME, &data_csv);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+    let summary =
+        internal_get_data_file_summary(&device, PORT_NAME.into(), &DeviceProfile::default())
+            .unwrap();
+
+    assert_eq!(collected.checksum, summary.checksum);
+    assert!(verify_collection(&collected, &summary).is_err());
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use tauri_app_lib::arduino::{
+    arduino_serial_number_for_port, internal_list_connected_arduinos, ArduinoIdentification,
+    ArduinoIdentifier, UsbId,
+};
+use tauri_app_lib::serial_transport::SerialTransport;
+use tauri_app_lib::sessions::SessionManager;
+
+const PORT_NAME: &str = "/dev/ttyUSB0";
+
+#[test]
+fn arduino_with_product_string_is_listed_with_its_usb_serial_number() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").with_serial_number("ABC123");
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification {
+        usb_ids: vec![],
+        probe_serial_number: true,
+    });
+
+    let arduinos = internal_list_connected_arduinos(&device, &identifier, &[9600]).unwrap();
+
+    assert_eq!(arduinos.len(), 1);
+    assert_eq!(arduinos[0].serial_port_info.port_name, PORT_NAME);
+    assert_eq!(
+        arduinos[0].arduino_serial_number,
+        Some(String::from("ABC123"))
+    );
+    // The USB serial number is enough, the board isn't probed
+    assert!(device.received_commands().is_empty());
+}
+
+#[test]
+fn clone_without_product_string_is_identified_by_usb_ids_and_probed() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "")
+        .with_serial_number("ABC123")
+        .with_usb_serial_adapter(0x1A86, 0x7523);
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification::default());
+
+    let arduinos = internal_list_connected_arduinos(&device, &identifier, &[9600]).unwrap();
+
+    assert_eq!(arduinos.len(), 1);
+    assert_eq!(
+        arduinos[0].arduino_serial_number,
+        Some(String::from("ABC123"))
+    );
+
+    // The probed serial number is remembered while the port stays connected
+    internal_list_connected_arduinos(&device, &identifier, &[9600]).unwrap();
+    assert_eq!(device.received_commands(), b"n");
+}
+
+#[test]
+fn ports_outside_the_allow_list_are_ignored() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").with_usb_serial_adapter(0x0403, 0x6001);
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification::default());
+
+    assert!(
+        internal_list_connected_arduinos(&device, &identifier, &[9600])
+            .unwrap()
+            .is_empty()
+    );
+
+    let mut identification = ArduinoIdentification::default();
+    identification.usb_ids.push(UsbId {
+        vid: 0x0403,
+        pid: Some(0x6001),
+    });
+    identification.probe_serial_number = false;
+    let identifier = ArduinoIdentifier::new(identification);
+
+    let arduinos = internal_list_connected_arduinos(&device, &identifier, &[9600]).unwrap();
+
+    assert_eq!(arduinos.len(), 1);
+    assert_eq!(arduinos[0].arduino_serial_number, None);
+}
+
+#[test]
+fn the_serial_number_of_a_port_includes_probed_ones() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "")
+        .with_serial_number("ABC123")
+        .with_usb_serial_adapter(0x1A86, 0x7523);
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification::default());
+
+    assert_eq!(
+        arduino_serial_number_for_port(&device, &identifier, &[9600], PORT_NAME).unwrap(),
+        Some(String::from("ABC123"))
+    );
+    assert_eq!(
+        arduino_serial_number_for_port(&device, &identifier, &[9600], "/dev/ttyUSB1").unwrap(),
+        None
+    );
+}
+
+#[test]
+fn ports_in_use_are_probed_once_they_are_free() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "")
+        .with_serial_number("ABC123")
+        .with_usb_serial_adapter(0x1A86, 0x7523);
+    let sessions = SessionManager::new(device.clone());
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification::default());
+
+    // A collection is using the port
+    let connection = sessions
+        .open(PORT_NAME, 9600, Duration::from_millis(100))
+        .unwrap();
+
+    let start = Instant::now();
+    let arduinos = internal_list_connected_arduinos(&sessions, &identifier, &[9600]).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(arduinos.len(), 1);
+    assert_eq!(arduinos[0].arduino_serial_number, None);
+
+    drop(connection);
+
+    let arduinos = internal_list_connected_arduinos(&sessions, &identifier, &[9600]).unwrap();
+    assert_eq!(
+        arduinos[0].arduino_serial_number,
+        Some(String::from("ABC123"))
+    );
+}
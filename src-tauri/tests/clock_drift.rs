@@ -0,0 +1,106 @@
+use holochain_types::prelude::Timestamp;
+use living_power_integrity::{measurement_collection::Measurement, ClockOffset};
+use tauri_app_lib::clock_drift::correct_clock_drift;
+
+const HOUR: i64 = 3_600_000_000;
+
+fn measurement_at(micros: i64) -> Measurement {
+    Measurement {
+        timestamp: Timestamp::from_micros(micros),
+        humidity_percentage: 45000,
+        temperature_celsius: 21000,
+        light_level_lux: 120000,
+        voltage_millivolts: 512,
+        raw_timestamp: None,
+    }
+}
+
+fn clock_offset(host_time: i64, offset: i64) -> ClockOffset {
+    ClockOffset {
+        device_time: Timestamp::from_micros(host_time + offset),
+        host_time: Timestamp::from_micros(host_time),
+        clock_set: false,
+    }
+}
+
+#[test]
+fn drift_is_interpolated_linearly_between_clock_offsets() {
+    // The clock was set right at 0, and was 100 seconds ahead 100 hours later
+    let clock_offsets = vec![clock_offset(0, 0), clock_offset(100 * HOUR, 100_000_000)];
+    let measurements = vec![measurement_at(50 * HOUR + 50_000_000)];
+
+    let corrected = correct_clock_drift(measurements, &clock_offsets);
+
+    assert_eq!(corrected[0].timestamp.as_micros(), 50 * HOUR);
+    assert_eq!(
+        corrected[0].raw_timestamp,
+        Some(Timestamp::from_micros(50 * HOUR + 50_000_000))
+    );
+}
+
+#[test]
+fn correcting_twice_has_no_further_effect() {
+    let clock_offsets = vec![clock_offset(0, 0), clock_offset(100 * HOUR, 100_000_000)];
+    let measurements = vec![measurement_at(10 * HOUR), measurement_at(150 * HOUR)];
+
+    let corrected = correct_clock_drift(measurements, &clock_offsets);
+    let corrected_twice = correct_clock_drift(corrected.clone(), &clock_offsets);
+
+    assert_eq!(corrected, corrected_twice);
+}
+
+#[test]
+fn drift_starts_over_when_the_clock_is_set() {
+    let mut clock_set = clock_offset(100 * HOUR + 60_000_000, 0);
+    clock_set.clock_set = true;
+    // 100 seconds ahead when it was set, then the clock drifts at half the rate
+    let clock_offsets = vec![
+        clock_offset(0, 0),
+        clock_offset(100 * HOUR, 100_000_000),
+        clock_set,
+        clock_offset(100 * HOUR + 60_000_010, 1_000_000),
+        clock_offset(200 * HOUR + 60_000_000, 50_000_000),
+    ];
+    let measurements = vec![
+        measurement_at(50 * HOUR + 50_000_000),
+        measurement_at(150 * HOUR + 60_000_000 + 25_000_000),
+    ];
+
+    let corrected = correct_clock_drift(measurements, &clock_offsets);
+
+    assert_eq!(corrected[0].timestamp.as_micros(), 50 * HOUR);
+    assert_eq!(corrected[1].timestamp.as_micros(), 150 * HOUR + 60_000_000);
+}
+
+#[test]
+fn clock_offsets_read_seconds_apart_dont_make_up_a_drift_rate() {
+    // Both read within a collection, the second one rounded to the next second
+    let clock_offsets = vec![clock_offset(0, 0), clock_offset(5_000_000, 1_000_000)];
+    let measurements = vec![measurement_at(30 * 24 * HOUR)];
+
+    let corrected = correct_clock_drift(measurements, &clock_offsets);
+
+    assert_eq!(corrected[0].timestamp.as_micros(), 30 * 24 * HOUR);
+}
+
+#[test]
+fn drift_is_extrapolated_for_a_week_at_most() {
+    // 1 second ahead every hour
+    let clock_offsets = vec![clock_offset(0, 0), clock_offset(100 * HOUR, 100_000_000)];
+    let measurements = vec![measurement_at(100 * HOUR + 365 * 24 * HOUR)];
+
+    let corrected = correct_clock_drift(measurements, &clock_offsets);
+
+    let offset = 100 * HOUR + 365 * 24 * HOUR - corrected[0].timestamp.as_micros();
+    let max_offset = 100_000_000 + 7 * 24 * 1_000_000;
+    assert!((offset - max_offset).abs() < 1_000_000);
+}
+
+#[test]
+fn measurements_are_unchanged_without_clock_offsets() {
+    let measurements = vec![measurement_at(10 * HOUR)];
+
+    let corrected = correct_clock_drift(measurements.clone(), &[]);
+
+    assert_eq!(corrected, measurements);
+}
@@ -0,0 +1,488 @@
+mod common;
+
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use tauri_app_lib::collect_measurements::{
+    internal_collect_measurements, internal_collect_measurements_since,
+    internal_get_last_measurement,
+};
+use tauri_app_lib::collection_progress::{CollectionControl, CollectionProgress};
+use tauri_app_lib::device_profiles::DeviceProfile;
+use tauri_app_lib::frame::crc32;
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+use tauri_app_lib::serial_transport::SerialTransport;
+use tauri_app_lib::sessions::SessionManager;
+
+const PORT_NAME: &str = "/dev/ttyACM0";
+
+const DATA_CSV: &str = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+0024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+0024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+0024-05-01,12:00:00,23.25,43.10,512.75,0.547\r
+";
+
+#[test]
+fn get_last_measurement_returns_the_last_logged_row() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+
+    let measurement = internal_get_last_measurement(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+    )
+    .unwrap()
+    .measurement
+    .unwrap();
+
+    assert_eq!(measurement.temperature_celsius, 23250);
+    assert_eq!(measurement.voltage_millivolts, 547);
+}
+
+#[test]
+fn get_last_measurement_returns_none_for_an_empty_log() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "");
+
+    let last_measurement = internal_get_last_measurement(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+    )
+    .unwrap();
+
+    assert!(last_measurement.measurement.is_none());
+}
+
+#[test]
+fn get_last_measurement_reads_the_device_clock() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).with_clock_offset(120);
+
+    let last_measurement = internal_get_last_measurement(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+    )
+    .unwrap();
+
+    let offset_seconds = last_measurement.clock_offset.unwrap().offset_micros() / 1_000_000;
+    assert!((118..=121).contains(&offset_seconds));
+}
+
+#[test]
+fn get_last_measurement_without_clock_support() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).with_legacy_firmware();
+
+    let last_measurement = internal_get_last_measurement(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+    )
+    .unwrap();
+
+    assert!(last_measurement.measurement.is_some());
+    assert!(last_measurement.clock_offset.is_none());
+}
+
+#[test]
+fn collect_measurements_uses_the_framed_protocol() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 3);
+    assert_eq!(device.received_commands(), vec![b'f']);
+}
+
+#[test]
+fn collect_measurements_falls_back_to_legacy_firmware() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).with_legacy_firmware();
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 3);
+    assert_eq!(device.received_commands(), vec![b'f', b'c']);
+}
+
+#[test]
+fn collect_measurements_skips_garbage_before_the_transfer() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV)
+        .with_garbage(b"Disconnected from Wi-Fi\r\n\xB9");
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 3);
+}
+
+#[test]
+fn collect_measurements_tolerates_slow_responses() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV)
+        .with_response_delay(Duration::from_millis(500));
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 3);
+}
+
+#[test]
+fn collecting_twice_returns_only_the_new_rows() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let timezone = RtcTimezone::default();
+
+    let first = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &timezone,
+        &CollectionControl::new(),
+    )
+    .unwrap();
+    assert_eq!(first.measurements.len(), 3);
+    let mut committed = first.measurements;
+
+    device.log_row("0024-05-01,13:00:00,23.50,42.90,530.00,0.551");
+    device.log_row("0024-05-01,14:00:00,23.75,42.70,545.25,0.556");
+
+    let since = committed.iter().map(|m| m.timestamp).max().unwrap();
+    let second = internal_collect_measurements_since(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        since,
+        &timezone,
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(second.measurements.len(), 2);
+    assert!(second.measurements.iter().all(|m| m.timestamp > since));
+    assert_eq!(device.received_commands(), vec![b'f', b't', b's']);
+
+    committed.extend(second.measurements);
+    let since = committed.iter().map(|m| m.timestamp).max().unwrap();
+    let third = internal_collect_measurements_since(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        since,
+        &timezone,
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert!(third.measurements.is_empty());
+    assert_eq!(committed.len(), 5);
+}
+
+#[test]
+fn collecting_since_after_the_device_clock_was_reset_collects_all_rows() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let timezone = RtcTimezone::default();
+    let first = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &timezone,
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    // The RTC lost its power and started again from 2000-01-01
+    let reset_time = chrono::NaiveDate::from_ymd_opt(2000, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let device =
+        device.with_clock_offset((reset_time - chrono::Local::now().naive_local()).num_seconds());
+    device.log_row("0000-01-01,00:00:05,23.50,42.90,530.00,0.551");
+
+    let since = first
+        .measurements
+        .iter()
+        .map(|m| m.timestamp)
+        .max()
+        .unwrap();
+    let collected = internal_collect_measurements_since(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        since,
+        &timezone,
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 4);
+    assert_eq!(device.received_commands(), vec![b'f', b't', b'f']);
+}
+
+#[test]
+fn collecting_since_with_legacy_firmware_filters_the_whole_file() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).with_legacy_firmware();
+    let timezone = RtcTimezone::default();
+    let all = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &timezone,
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    let collected = internal_collect_measurements_since(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        all.measurements[1].timestamp,
+        &timezone,
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements, all.measurements[2..].to_vec());
+}
+
+/// Enough rows to be sent in several frames
+fn long_data_csv() -> String {
+    let mut data_csv =
+        String::from("Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r\n");
+    for hour in 0..24 {
+        data_csv.push_str(&format!(
+            "0024-05-01,{hour:02}:00:00,21.50,45.20,120.00,0.512\r\n"
+        ));
+    }
+    data_csv
+}
+
+#[test]
+fn collect_measurements_resumes_after_the_device_disconnects() {
+    let data_csv = long_data_csv();
+    let device = SimulatedBpvDevice::new(PORT_NAME, &data_csv).disconnect_after_bytes(500);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 24);
+    assert!(!collected.incomplete);
+    assert_eq!(collected.checksum, crc32(data_csv.as_bytes()));
+    assert!(device.received_commands().contains(&b's'));
+}
+
+#[test]
+fn collect_measurements_resumes_after_the_rows_it_rejected() {
+    let mut data_csv =
+        String::from("Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r\n");
+    for hour in 0..24 {
+        // The connection breaks while these rows are sent
+        let humidity = if (3..13).contains(&hour) {
+            "ERR"
+        } else {
+            "45.20"
+        };
+        data_csv.push_str(&format!(
+            "0024-05-01,{hour:02}:00:00,21.50,{humidity},120.00,0.512\r\n"
+        ));
+    }
+    let device = SimulatedBpvDevice::new(PORT_NAME, &data_csv).disconnect_after_bytes(500);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert!(device.received_commands().contains(&b's'));
+    assert_eq!(collected.measurements.len(), 14);
+    assert_eq!(collected.report.rejected_rows.len(), 10);
+    assert_eq!(collected.checksum, crc32(data_csv.as_bytes()));
+}
+
+#[test]
+fn collect_measurements_restarts_when_the_device_disconnects_before_any_row() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).disconnect_after_bytes(20);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 3);
+    assert_eq!(collected.checksum, crc32(DATA_CSV.as_bytes()));
+}
+
+#[test]
+fn collect_measurements_keeps_the_partial_data_when_the_device_is_unplugged() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, &long_data_csv()).unplug_after_bytes(500);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert!(collected.incomplete);
+    assert!(!collected.measurements.is_empty());
+    assert!(collected.measurements.len() < 24);
+}
+
+#[test]
+fn collect_measurements_fails_when_the_device_is_unplugged_before_any_row() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).unplug_after_bytes(20);
+
+    let result = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    );
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn collect_measurements_reports_progress() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let reports: Arc<Mutex<Vec<CollectionProgress>>> = Arc::new(Mutex::new(vec![]));
+    let reports_clone = reports.clone();
+    let control = CollectionControl::new()
+        .on_progress(move |progress| reports_clone.lock().unwrap().push(progress));
+
+    internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &control,
+    )
+    .unwrap();
+
+    let reports = reports.lock().unwrap();
+    let last_report = reports.last().unwrap();
+    assert_eq!(last_report.rows_received, 3);
+    assert_eq!(last_report.bytes_received, DATA_CSV.len());
+    assert_eq!(last_report.total_bytes, Some(DATA_CSV.len()));
+    assert_eq!(last_report.parse_errors, 0);
+}
+
+#[test]
+fn cancelled_collection_stops_the_transfer() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let control = CollectionControl::new();
+    control.cancel();
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &control,
+    )
+    .unwrap();
+
+    assert!(collected.cancelled);
+    assert!(collected.measurements.is_empty());
+}
+
+/// Whether the device sent nothing that the next command using the port would receive
+fn nothing_left_to_read(sessions: &SessionManager) -> bool {
+    let mut connection = sessions
+        .open(PORT_NAME, 9600, Duration::from_millis(100))
+        .unwrap();
+    let mut buffer = [0u8; 64];
+    matches!(
+        connection.read(&mut buffer),
+        Err(err) if err.kind() == std::io::ErrorKind::TimedOut
+    )
+}
+
+#[test]
+fn cancelled_collection_leaves_nothing_for_the_next_command() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, &long_data_csv());
+    let sessions = SessionManager::new(device.clone());
+    sessions.keep_alive(PORT_NAME, 9600);
+    let control = CollectionControl::new();
+    control.cancel();
+
+    let collected = internal_collect_measurements(
+        &sessions,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &control,
+    )
+    .unwrap();
+
+    assert!(collected.cancelled);
+    assert!(nothing_left_to_read(&sessions));
+}
+
+#[test]
+fn cancelled_legacy_collection_leaves_nothing_for_the_next_command() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, &long_data_csv()).with_legacy_firmware();
+    let sessions = SessionManager::new(device.clone());
+    sessions.keep_alive(PORT_NAME, 9600);
+    let control = CollectionControl::new();
+    control.cancel();
+
+    let collected = internal_collect_measurements(
+        &sessions,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        &control,
+    )
+    .unwrap();
+
+    assert!(collected.cancelled);
+    assert!(nothing_left_to_read(&sessions));
+}
@@ -0,0 +1,4 @@
+// Each test only uses part of the simulated device
+#![allow(dead_code)]
+
+pub mod simulated_bpv;
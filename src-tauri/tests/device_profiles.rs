@@ -0,0 +1,157 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use tauri_app_lib::arduino::{
+    internal_list_connected_arduinos, ArduinoIdentification, ArduinoIdentifier,
+};
+use tauri_app_lib::collect_measurements::internal_collect_measurements;
+use tauri_app_lib::collection_progress::CollectionControl;
+use tauri_app_lib::device_profiles::{CommandSet, DeviceProfile, DeviceProfiles, LineEnding};
+use tauri_app_lib::device_settings::{
+    internal_get_logging_interval, internal_set_logging_interval,
+};
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+
+const PORT_NAME: &str = "/dev/ttyACM0";
+
+const DATA_CSV: &str = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+2024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+";
+
+fn fast_profile() -> DeviceProfile {
+    DeviceProfile {
+        name: String::from("fast"),
+        baud_rate: 115200,
+        ..DeviceProfile::default()
+    }
+}
+
+#[test]
+fn collect_measurements_uses_the_baud_rate_of_the_profile() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).with_baud_rate(115200);
+
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &fast_profile(),
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+    assert_eq!(collected.measurements.len(), 2);
+
+    let short_timeouts = DeviceProfile {
+        command_timeout_ms: 100,
+        transfer_timeout_ms: 100,
+        ..DeviceProfile::default()
+    };
+    let result = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &short_timeouts,
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn legacy_command_set_only_sends_the_legacy_commands() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV).with_legacy_firmware();
+    let profile = DeviceProfile {
+        command_set: CommandSet::Legacy,
+        ..DeviceProfile::default()
+    };
+
+    let started_at = Instant::now();
+    let collected = internal_collect_measurements(
+        &device,
+        PORT_NAME.into(),
+        &profile,
+        &RtcTimezone::default(),
+        &CollectionControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(collected.measurements.len(), 2);
+    assert_eq!(device.received_commands(), b"c".to_vec());
+    // Without waiting for the framed protocol to time out
+    assert!(started_at.elapsed() < Duration::from_millis(1000));
+
+    assert!(internal_get_logging_interval(&device, PORT_NAME.into(), &profile).is_err());
+    assert_eq!(device.received_commands(), b"c".to_vec());
+}
+
+#[test]
+fn commands_are_terminated_with_the_line_ending_of_the_profile() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "");
+    let profile = DeviceProfile {
+        line_ending: LineEnding::CrLf,
+        ..DeviceProfile::default()
+    };
+
+    let applied =
+        internal_set_logging_interval(&device, PORT_NAME.into(), &profile, 599000).unwrap();
+
+    assert_eq!(applied, 599000);
+    assert_eq!(
+        internal_get_logging_interval(&device, PORT_NAME.into(), &profile).unwrap(),
+        599000
+    );
+}
+
+#[test]
+fn devices_without_an_assigned_profile_use_the_default_one() {
+    let default_profile = DeviceProfile {
+        command_timeout_ms: 4000,
+        ..DeviceProfile::default()
+    };
+    let profiles = DeviceProfiles {
+        profiles: vec![default_profile.clone(), fast_profile()],
+        assignments: BTreeMap::from([(String::from("FAST123"), String::from("fast"))]),
+    };
+    profiles.validate().unwrap();
+
+    assert_eq!(profiles.profile_for(Some("FAST123")), fast_profile());
+    assert_eq!(profiles.profile_for(Some("OTHER")), default_profile);
+    assert_eq!(profiles.profile_for(None), default_profile);
+}
+
+#[test]
+fn profiles_assigned_to_a_device_must_exist() {
+    let profiles = DeviceProfiles {
+        profiles: vec![DeviceProfile::default()],
+        assignments: BTreeMap::from([(String::from("FAST123"), String::from("fast"))]),
+    };
+
+    assert!(profiles.validate().is_err());
+}
+
+#[test]
+fn boards_are_probed_at_the_baud_rate_of_each_profile() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV)
+        .with_serial_number("FAST123")
+        .with_usb_serial_adapter(0x1A86, 0x7523)
+        .with_baud_rate(115200);
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification::default());
+    let profiles = DeviceProfiles {
+        profiles: vec![DeviceProfile::default(), fast_profile()],
+        assignments: BTreeMap::from([(String::from("FAST123"), String::from("fast"))]),
+    };
+
+    // The profile of the board isn't known until its serial number is
+    assert_eq!(profiles.probe_baud_rates(), vec![9600, 115200]);
+
+    let arduinos =
+        internal_list_connected_arduinos(&device, &identifier, &profiles.probe_baud_rates())
+            .unwrap();
+
+    assert_eq!(
+        arduinos[0].arduino_serial_number,
+        Some(String::from("FAST123"))
+    );
+}
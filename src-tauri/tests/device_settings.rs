@@ -0,0 +1,122 @@
+mod common;
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use holochain_types::prelude::Timestamp;
+use tauri_app_lib::device_profiles::DeviceProfile;
+use tauri_app_lib::device_settings::{
+    internal_get_device_time, internal_get_logging_interval, internal_get_sleep_mode,
+    internal_set_device_time, internal_set_logging_interval, internal_set_sleep_mode, SleepMode,
+};
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+
+const PORT_NAME: &str = "/dev/ttyACM0";
+
+fn now() -> Timestamp {
+    Timestamp::from_micros(chrono::Utc::now().timestamp_micros())
+}
+
+#[test]
+fn setting_the_time_corrects_the_device_clock() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").with_clock_offset(-3600);
+
+    let clock_offset = internal_set_device_time(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        now(),
+    )
+    .unwrap();
+
+    assert!(clock_offset.offset_micros().abs() <= 2_000_000);
+    assert!(device.clock_offset().abs() <= 1);
+
+    let clock_offset = internal_get_device_time(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+    )
+    .unwrap();
+    assert!(clock_offset.offset_micros().abs() <= 2_000_000);
+}
+
+#[test]
+fn logging_interval_is_read_back_after_setting_it() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "");
+
+    let applied =
+        internal_set_logging_interval(&device, PORT_NAME.into(), &DeviceProfile::default(), 599000)
+            .unwrap();
+
+    assert_eq!(applied, 599000);
+    assert_eq!(device.logging_interval_ms(), 599000);
+    assert_eq!(
+        internal_get_logging_interval(&device, PORT_NAME.into(), &DeviceProfile::default())
+            .unwrap(),
+        599000
+    );
+}
+
+#[test]
+fn sleep_mode_is_read_back_after_setting_it() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "");
+
+    assert_eq!(
+        internal_get_sleep_mode(&device, PORT_NAME.into(), &DeviceProfile::default()).unwrap(),
+        SleepMode::Deep
+    );
+    let applied = internal_set_sleep_mode(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        SleepMode::Off,
+    )
+    .unwrap();
+
+    assert_eq!(applied, SleepMode::Off);
+    assert_eq!(
+        internal_get_sleep_mode(&device, PORT_NAME.into(), &DeviceProfile::default()).unwrap(),
+        SleepMode::Off
+    );
+}
+
+#[test]
+fn settings_that_are_not_applied_are_reported() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "")
+        .with_clock_offset(-3600)
+        .with_read_only_settings();
+
+    assert!(internal_set_device_time(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        now()
+    )
+    .is_err());
+    assert!(internal_set_logging_interval(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        599000
+    )
+    .is_err());
+    assert!(internal_set_sleep_mode(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        SleepMode::Light
+    )
+    .is_err());
+}
+
+#[test]
+fn legacy_firmware_does_not_support_settings() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").with_legacy_firmware();
+
+    let err = internal_get_logging_interval(&device, PORT_NAME.into(), &DeviceProfile::default())
+        .unwrap_err();
+
+    assert!(err.to_string().contains("does not support"));
+}
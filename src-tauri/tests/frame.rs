@@ -0,0 +1,155 @@
+use tauri_app_lib::frame::{
+    crc32, encode_frame, Crc32, Frame, FrameDecoder, FrameError, FrameKind, MAX_PAYLOAD_LEN,
+};
+
+/// The header and the first rows of a data file, as `bpv.ino` writes them
+const DATA_CSV: &[u8] = b"Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+0024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+0024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+";
+
+const ROW: &[u8] = b"0024-05-01,12:00:00,23.25,43.10,512.75,0.547\r\n";
+
+fn data_frame(sequence: u16, payload: &[u8]) -> Frame {
+    Frame {
+        kind: FrameKind::Data,
+        sequence,
+        payload: payload.to_vec(),
+    }
+}
+
+#[test]
+fn crc32_matches_the_ieee_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+    let mut crc = Crc32::new();
+    crc.update(b"1234");
+    crc.update(b"56789");
+    assert_eq!(crc.finish(), 0xCBF4_3926);
+}
+
+#[test]
+fn encoded_frames_decode_back() {
+    let frames = vec![
+        Frame {
+            kind: FrameKind::Begin,
+            sequence: 0,
+            payload: (DATA_CSV.len() as u32).to_le_bytes().to_vec(),
+        },
+        data_frame(1, DATA_CSV),
+        data_frame(2, &[]),
+        data_frame(3, &[0xAB; MAX_PAYLOAD_LEN]),
+        Frame {
+            kind: FrameKind::Error,
+            sequence: 4,
+            payload: b"Failed to open data file".to_vec(),
+        },
+    ];
+
+    let mut decoder = FrameDecoder::new();
+    for frame in &frames {
+        decoder.push(&encode_frame(frame).unwrap());
+    }
+
+    for frame in frames {
+        assert_eq!(decoder.next_frame(), Some(Ok(frame)));
+    }
+    assert_eq!(decoder.next_frame(), None);
+}
+
+#[test]
+fn payloads_over_the_maximum_are_not_encoded() {
+    let frame = data_frame(0, &[0; MAX_PAYLOAD_LEN + 1]);
+
+    assert_eq!(
+        encode_frame(&frame),
+        Err(FrameError::PayloadTooLarge(MAX_PAYLOAD_LEN + 1))
+    );
+}
+
+#[test]
+fn corrupted_frames_are_rejected_and_skipped() {
+    let mut corrupted = encode_frame(&data_frame(1, DATA_CSV)).unwrap();
+    // Flip the last byte of the payload, right before the 4 bytes of the CRC
+    let last_payload_byte = corrupted.len() - 5;
+    corrupted[last_payload_byte] ^= 0xFF;
+    let next = data_frame(2, ROW);
+
+    let mut decoder = FrameDecoder::new();
+    decoder.push(&corrupted);
+    decoder.push(&encode_frame(&next).unwrap());
+
+    assert!(matches!(
+        decoder.next_frame(),
+        Some(Err(FrameError::ChecksumMismatch { .. }))
+    ));
+    assert_eq!(decoder.next_frame(), Some(Ok(next)));
+}
+
+#[test]
+fn bytes_before_the_magic_are_skipped() {
+    let frame = data_frame(7, ROW);
+
+    let mut decoder = FrameDecoder::new();
+    // Printed by the firmware when it boots
+    decoder.push(b"Disconnected from Wi-Fi\r\n");
+    // A lone first magic byte must not be mistaken for the start of a frame
+    let bytes = encode_frame(&frame).unwrap();
+    decoder.push(&[bytes[0], 0x00]);
+    assert_eq!(decoder.next_frame(), None);
+
+    // The first magic byte arrives at the end of a read, and the rest in the next one
+    decoder.push(&[b'x', bytes[0]]);
+    assert_eq!(decoder.next_frame(), None);
+    decoder.push(&bytes[1..]);
+
+    assert_eq!(decoder.next_frame(), Some(Ok(frame)));
+}
+
+#[test]
+fn truncated_frames_wait_for_the_rest_of_their_bytes() {
+    let frame = data_frame(3, ROW);
+    let bytes = encode_frame(&frame).unwrap();
+
+    let mut decoder = FrameDecoder::new();
+    for byte in &bytes[..bytes.len() - 1] {
+        decoder.push(&[*byte]);
+        assert_eq!(decoder.next_frame(), None);
+    }
+    decoder.push(&bytes[bytes.len() - 1..]);
+
+    assert_eq!(decoder.next_frame(), Some(Ok(frame)));
+}
+
+#[test]
+fn a_truncated_frame_fails_its_checksum_and_the_decoder_resyncs() {
+    let truncated = encode_frame(&data_frame(1, DATA_CSV)).unwrap();
+    let next = encode_frame(&data_frame(2, ROW)).unwrap();
+    let end = Frame {
+        kind: FrameKind::End,
+        sequence: 3,
+        payload: [
+            2u32.to_le_bytes(),
+            ((DATA_CSV.len() + ROW.len()) as u32).to_le_bytes(),
+        ]
+        .concat(),
+    };
+
+    let mut decoder = FrameDecoder::new();
+    decoder.push(&truncated[..truncated.len() - 3]);
+    decoder.push(&next);
+    decoder.push(&encode_frame(&end).unwrap());
+
+    let mut results = vec![];
+    while let Some(result) = decoder.next_frame() {
+        results.push(result);
+    }
+
+    // The truncated frame swallows the start of the next one, which is lost with it
+    assert!(matches!(
+        results.first(),
+        Some(Err(FrameError::ChecksumMismatch { .. }))
+    ));
+    assert_eq!(results.last(), Some(&Ok(end)));
+    assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+}
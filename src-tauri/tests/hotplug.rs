@@ -0,0 +1,55 @@
+mod common;
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use tauri_app_lib::arduino::{ArduinoIdentification, ArduinoIdentifier};
+use tauri_app_lib::hotplug::{HotplugEvent, HotplugWatcher};
+
+const PORT_NAME: &str = "/dev/ttyACM0";
+
+#[test]
+fn plugging_and_unplugging_a_board_emits_events() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").with_serial_number("ABC123");
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification::default());
+    let mut watcher = HotplugWatcher::new();
+
+    let events = watcher.poll(&device, &identifier, &[9600]).unwrap();
+    let [HotplugEvent::Connected(arduino)] = events.as_slice() else {
+        panic!("Expected a connection event, got {events:?}");
+    };
+    assert_eq!(arduino.arduino_serial_number, Some(String::from("ABC123")));
+
+    // Nothing changed
+    assert!(watcher
+        .poll(&device, &identifier, &[9600])
+        .unwrap()
+        .is_empty());
+
+    device.unplug();
+    let events = watcher.poll(&device, &identifier, &[9600]).unwrap();
+    let [HotplugEvent::Disconnected(arduino)] = events.as_slice() else {
+        panic!("Expected a disconnection event, got {events:?}");
+    };
+    assert_eq!(arduino.serial_port_info.port_name, PORT_NAME);
+    assert_eq!(arduino.arduino_serial_number, Some(String::from("ABC123")));
+
+    device.plug_in();
+    let events = watcher.poll(&device, &identifier, &[9600]).unwrap();
+    assert!(matches!(events.as_slice(), [HotplugEvent::Connected(_)]));
+}
+
+#[test]
+fn board_that_is_not_an_arduino_emits_no_events() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").with_usb_serial_adapter(0x0403, 0x6001);
+    let identifier = ArduinoIdentifier::new(ArduinoIdentification::default());
+    let mut watcher = HotplugWatcher::new();
+
+    assert!(watcher
+        .poll(&device, &identifier, &[9600])
+        .unwrap()
+        .is_empty());
+    device.unplug();
+    assert!(watcher
+        .poll(&device, &identifier, &[9600])
+        .unwrap()
+        .is_empty());
+}
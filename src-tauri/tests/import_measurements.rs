@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::path::PathBuf;
+
+use tauri_app_lib::import_measurements::internal_parse_import;
+use tauri_app_lib::measurements_parser::ColumnMapping;
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+
+const DATA_CSV: &str = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+2024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+";
+
+/// Exported from a spreadsheet, with column names the parser doesn't know
+const SPREADSHEET_CSV: &str = "When,Temp_F,RH,Lux,Millivolts,Site\r
+2024-05-01 11:00:00,71.60,44.80,340.50,530,north\r
+2024-05-01 12:00:00,73.85,43.10,512.75,547,north\r
+";
+
+/// An empty directory with the files to import, removed when dropped
+struct ImportDirectory(PathBuf);
+
+impl ImportDirectory {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("bpv-import-{}-{name}", std::process::id()));
+        let _ = remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, file: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(file);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for ImportDirectory {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+fn spreadsheet_mapping() -> ColumnMapping {
+    ColumnMapping(BTreeMap::from([
+        (String::from("When"), String::from("Timestamp")),
+        (String::from("Temp_F"), String::from("Temperature (F)")),
+        (String::from("RH"), String::from("Humidity (%)")),
+        (String::from("Lux"), String::from("Light Level (lux)")),
+        (String::from("Millivolts"), String::from("Voltage (mV)")),
+        (String::from("Site"), String::new()),
+    ]))
+}
+
+#[test]
+fn a_single_file_is_imported_with_the_column_mapping() {
+    let directory = ImportDirectory::new("single-file");
+    let file = directory.write("export.csv", SPREADSHEET_CSV);
+
+    let unmapped =
+        internal_parse_import(&file, &RtcTimezone::default(), &ColumnMapping::default()).unwrap();
+    assert!(unmapped.measurements.is_empty());
+    assert!(unmapped.files[0].error.is_some());
+    assert_eq!(unmapped.files[0].header.len(), 6);
+    assert_eq!(unmapped.files[0].header[4].column, None);
+
+    let mapped =
+        internal_parse_import(&file, &RtcTimezone::default(), &spreadsheet_mapping()).unwrap();
+    assert_eq!(mapped.files[0].error, None);
+    assert_eq!(
+        mapped.files[0].header[1].column,
+        Some(String::from("Temperature (F)"))
+    );
+    assert_eq!(mapped.measurements.len(), 2);
+    assert_eq!(mapped.measurements[0].temperature_celsius, 22000);
+    assert_eq!(mapped.measurements[0].voltage_millivolts, 530);
+}
+
+#[test]
+fn the_csv_files_of_a_directory_are_merged_without_duplicates() {
+    let directory = ImportDirectory::new("directory");
+    directory.write("backups/2024-05/data.csv", DATA_CSV);
+    directory.write("export.CSV", SPREADSHEET_CSV);
+    directory.write(
+        "broken.csv",
+        "Date,Time,Notes\r\n2024-05-01,10:00:00,none\r\n",
+    );
+    directory.write("notes.txt", "Not a CSV file");
+    directory.write(".hidden/data.csv", DATA_CSV);
+
+    let parsed = internal_parse_import(
+        &directory.0,
+        &RtcTimezone::default(),
+        &spreadsheet_mapping(),
+    )
+    .unwrap();
+
+    let files: Vec<PathBuf> = parsed.files.iter().map(|file| file.path.clone()).collect();
+    assert_eq!(
+        files,
+        vec![
+            directory.0.join("backups/2024-05/data.csv"),
+            directory.0.join("broken.csv"),
+            directory.0.join("export.CSV"),
+        ]
+    );
+    assert_eq!(parsed.files[0].measurements, 2);
+    assert!(parsed.files[1].error.is_some());
+    assert_eq!(parsed.files[2].measurements, 2);
+
+    // The 11:00 measurement is in both files
+    assert_eq!(parsed.measurements.len(), 3);
+    assert!(parsed
+        .measurements
+        .windows(2)
+        .all(|pair| pair[0].timestamp < pair[1].timestamp));
+}
+
+#[test]
+fn importing_a_directory_without_csv_files_fails() {
+    let directory = ImportDirectory::new("empty");
+    directory.write("notes.txt", "Not a CSV file");
+
+    assert!(internal_parse_import(
+        &directory.0,
+        &RtcTimezone::default(),
+        &ColumnMapping::default()
+    )
+    .is_err());
+    assert!(internal_parse_import(
+        &directory.0.join("missing.csv"),
+        &RtcTimezone::default(),
+        &ColumnMapping::default()
+    )
+    .is_err());
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use tauri_app_lib::device_profiles::DeviceProfile;
+use tauri_app_lib::live_measurements::{internal_stream_live_measurements, LiveControl};
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+
+const PORT_NAME: &str = "/dev/ttyACM0";
+
+#[test]
+fn live_measurements_are_reported_until_stopped() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "");
+    let received = Arc::new(AtomicUsize::new(0));
+    let received_clone = received.clone();
+    let control = LiveControl::new().on_measurement(move |measurement| {
+        assert_eq!(measurement.temperature_celsius, 21500);
+        received_clone.fetch_add(1, Ordering::Relaxed);
+    });
+
+    let stopper = control.clone();
+    std::thread::spawn(move || {
+        while received.load(Ordering::Relaxed) < 2 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        stopper.stop();
+    });
+
+    let recording = internal_stream_live_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        500,
+        &control,
+    )
+    .unwrap();
+
+    assert_eq!(recording.measurements.len(), 2);
+    assert!(!recording.interrupted);
+    assert!(device.received_commands().ends_with(b"vx"));
+}
+
+#[test]
+fn live_measurements_interval_is_the_one_confirmed_by_the_device() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "");
+    let control = LiveControl::new();
+    control.stop();
+
+    let recording = internal_stream_live_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        100,
+        &control,
+    )
+    .unwrap();
+
+    assert_eq!(recording.interval_ms, 500);
+}
+
+#[test]
+fn live_measurements_are_kept_when_the_device_disconnects() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").disconnect_after_bytes(70);
+
+    let recording = internal_stream_live_measurements(
+        &device,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+        500,
+        &LiveControl::new(),
+    )
+    .unwrap();
+
+    assert_eq!(recording.measurements.len(), 1);
+    assert!(recording.interrupted);
+}
+
+#[test]
+fn live_measurements_fail_with_legacy_firmware() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, "").with_legacy_firmware();
+    let profile = DeviceProfile {
+        command_timeout_ms: 200,
+        ..DeviceProfile::default()
+    };
+
+    let result = internal_stream_live_measurements(
+        &device,
+        PORT_NAME.into(),
+        &profile,
+        &RtcTimezone::default(),
+        500,
+        &LiveControl::new(),
+    );
+
+    assert!(result.is_err());
+}
@@ -0,0 +1,153 @@
+use chrono::{FixedOffset, TimeZone, Utc};
+use living_power_integrity::DeviceTimezone;
+use tauri_app_lib::measurements_parser::parse_csv_file_contents;
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+
+#[test]
+fn parse_report_lists_the_rejected_lines() {
+    let contents = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+\r
+2024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+2024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+2024-05-01,12:0\x000,23.25,43.10,512.75,0.547\r
+";
+
+    let parsed = parse_csv_file_contents(contents.to_string(), RtcTimezone::default()).unwrap();
+
+    assert_eq!(parsed.measurements.len(), 2);
+    assert_eq!(parsed.report.header_lines, 1);
+    assert_eq!(parsed.report.blank_lines, 1);
+    assert_eq!(parsed.report.duplicate_lines, 1);
+    assert_eq!(parsed.report.rejected_rows.len(), 1);
+    assert_eq!(parsed.report.rejected_rows[0].line_number, 6);
+}
+
+#[test]
+fn columns_are_read_from_the_header() {
+    let contents = "Voltage (mV),Timestamp,Notes,Humidity (%),Temperature (F),Light Level (lux)\r
+512,2024-05-01T10:00:00,sunny,45.20,50.00,120.00\r
+";
+
+    let parsed = parse_csv_file_contents(contents.to_string(), RtcTimezone::default()).unwrap();
+
+    assert_eq!(parsed.measurements.len(), 1);
+    let measurement = &parsed.measurements[0];
+    assert_eq!(measurement.voltage_millivolts, 512);
+    assert_eq!(measurement.temperature_celsius, 10000);
+    assert_eq!(measurement.humidity_percentage, 45200);
+    assert_eq!(measurement.light_level_lux, 120000);
+}
+
+#[test]
+fn header_without_required_columns_is_an_error() {
+    let contents = "Date,Time,Temperature (C),Humidity (%)\r
+2024-05-01,10:00:00,21.50,45.20\r
+";
+
+    let err = parse_csv_file_contents(contents.to_string(), RtcTimezone::default()).unwrap_err();
+
+    assert!(err.to_string().contains("Light Level, Voltage"));
+}
+
+#[test]
+fn lines_starting_with_a_letter_between_rows_are_rejected_alone() {
+    let contents = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+Failed to initialize MKR ENV shield!\r
+ERROR Failed to open data file\r
+2024-05-01,11:00:00,22.00,44.80,340.50,0.530\r
+";
+
+    let parsed = parse_csv_file_contents(contents.to_string(), RtcTimezone::default()).unwrap();
+
+    assert_eq!(parsed.measurements.len(), 2);
+    assert_eq!(parsed.report.header_lines, 1);
+    let rejected_lines: Vec<usize> = parsed
+        .report
+        .rejected_rows
+        .iter()
+        .map(|row| row.line_number)
+        .collect();
+    assert_eq!(rejected_lines, vec![3, 4]);
+}
+
+#[test]
+fn two_digit_years_written_by_the_rtc_are_in_the_2000s() {
+    // Rows already on the SD cards of deployed devices
+    let contents = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+0024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+0000-01-01,00:00:05,19.75,51.30,0.00,0.498\r
+";
+
+    let parsed = parse_csv_file_contents(
+        contents.to_string(),
+        RtcTimezone::Fixed(FixedOffset::east_opt(0).unwrap()),
+    )
+    .unwrap();
+
+    let timestamps: Vec<i64> = parsed
+        .measurements
+        .iter()
+        .map(|measurement| measurement.timestamp.as_micros())
+        .collect();
+    assert_eq!(
+        timestamps,
+        vec![
+            Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0)
+                .unwrap()
+                .timestamp_micros(),
+            Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 5)
+                .unwrap()
+                .timestamp_micros(),
+        ]
+    );
+}
+
+#[test]
+fn negative_values_and_scientific_notation_are_parsed() {
+    let contents = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-01-15,06:00:00,-12.50,85.00,1.2e3,-0.042\r
+2024-01-15,07:00:00,NaN,85.00,1200,0.042\r
+2024-01-15,08:00:00,-4.00,inf,1200,0.042\r
+";
+
+    let parsed = parse_csv_file_contents(contents.to_string(), RtcTimezone::default()).unwrap();
+
+    assert_eq!(parsed.measurements.len(), 1);
+    let measurement = &parsed.measurements[0];
+    assert_eq!(measurement.temperature_celsius, -12500);
+    assert_eq!(measurement.light_level_lux, 1200000);
+    assert_eq!(measurement.voltage_millivolts, -42);
+    assert_eq!(parsed.report.rejected_rows.len(), 2);
+}
+
+#[test]
+fn daylight_saving_changes_resolve_without_panicking() {
+    let contents = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-03-31,02:30:00,12.00,60.00,0.00,0.410\r
+2024-10-27,02:30:00,14.00,70.00,0.00,0.420\r
+";
+    let timezone = RtcTimezone::from_device_timezone(Some(DeviceTimezone::Named {
+        name: String::from("Europe/Madrid"),
+    }))
+    .unwrap();
+
+    let parsed = parse_csv_file_contents(contents.to_string(), timezone).unwrap();
+
+    assert_eq!(parsed.measurements.len(), 2);
+    // The clocks went forward at 02:00, so 02:30 is read with the winter offset
+    assert_eq!(
+        parsed.measurements[0].timestamp.as_micros(),
+        Utc.with_ymd_and_hms(2024, 3, 31, 1, 30, 0)
+            .unwrap()
+            .timestamp_micros()
+    );
+    // The clocks went back at 03:00, so 02:30 happened twice and resolves to the first one
+    assert_eq!(
+        parsed.measurements[1].timestamp.as_micros(),
+        Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0)
+            .unwrap()
+            .timestamp_micros()
+    );
+}
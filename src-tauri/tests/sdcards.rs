@@ -0,0 +1,119 @@
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::path::PathBuf;
+
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+use tauri_app_lib::sdcards::{
+    internal_collect_measurements_from_sdcard, internal_list_measurements_sdcards,
+};
+
+const HEADER: &str = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r\n";
+
+/// An empty directory standing in for a mounted volume, removed when dropped
+struct Mountpoint(PathBuf);
+
+impl Mountpoint {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("bpv-sdcards-{}-{name}", std::process::id()));
+        let _ = remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, file: &str, contents: &str) -> PathBuf {
+        let path = self.0.join(file);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for Mountpoint {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn sdcards_are_found_by_their_manifest_in_subdirectories() {
+    let mountpoint = Mountpoint::new("manifest");
+    mountpoint.write("notes.txt", "Not a BPV card");
+    mountpoint.write("site-a/manifest", "device=bpv\r\nserial_number=ABC123\r\n");
+    mountpoint.write("site-a/data.csv", HEADER);
+    mountpoint.write("site-a/24050100.CSV", HEADER);
+    mountpoint.write("site-a/data_2024-04.csv", HEADER);
+    mountpoint.write("site-b/manifest", "device=other\r\n");
+    mountpoint.write("site-b/data.csv", HEADER);
+
+    let sdcards = internal_list_measurements_sdcards(vec![mountpoint.0.clone()]);
+
+    assert_eq!(sdcards.len(), 1);
+    let sdcard = &sdcards[0];
+    assert_eq!(sdcard.mountpoint, mountpoint.0);
+    assert_eq!(sdcard.directory, mountpoint.0.join("site-a"));
+    assert_eq!(sdcard.arduino_serial_number, Some(String::from("ABC123")));
+    assert_eq!(sdcard.error, None);
+    assert_eq!(
+        sdcard.data_files,
+        vec![
+            mountpoint.0.join("site-a/data.csv"),
+            mountpoint.0.join("site-a/24050100.CSV"),
+            mountpoint.0.join("site-a/data_2024-04.csv"),
+        ]
+    );
+}
+
+#[test]
+fn unreadable_cards_are_reported_without_failing_the_list() {
+    let broken = Mountpoint::new("broken");
+    broken.write("serial", "");
+    broken.write("data.csv", HEADER);
+    let working = Mountpoint::new("working");
+    working.write("serial", "DEF456\r\n");
+    working.write("data.csv", HEADER);
+    let missing = std::env::temp_dir().join("bpv-sdcards-missing");
+
+    let sdcards =
+        internal_list_measurements_sdcards(vec![broken.0.clone(), missing, working.0.clone()]);
+
+    assert_eq!(sdcards.len(), 2);
+    assert_eq!(sdcards[0].arduino_serial_number, None);
+    assert!(sdcards[0].error.is_some());
+    assert_eq!(
+        sdcards[1].arduino_serial_number,
+        Some(String::from("DEF456"))
+    );
+    assert_eq!(sdcards[1].error, None);
+}
+
+#[test]
+fn a_serial_file_without_data_files_is_not_a_bpv_card() {
+    let mountpoint = Mountpoint::new("serial-only");
+    mountpoint.write("serial", "DEF456");
+
+    assert!(internal_list_measurements_sdcards(vec![mountpoint.0.clone()]).is_empty());
+}
+
+#[test]
+fn rows_repeated_across_data_files_are_collected_once() {
+    let mountpoint = Mountpoint::new("collect");
+    let data_file = mountpoint.write(
+        "data.csv",
+        &format!("{HEADER}2024-05-01,11:00:00,22.00,44.80,340.50,0.530\r\n"),
+    );
+    // The last row of the archive is not terminated by a newline
+    let archive = mountpoint.write(
+        "24050100.CSV",
+        &format!(
+            "{HEADER}2024-05-01,10:00:00,21.50,45.20,120.00,0.512\r\n2024-05-01,11:00:00,22.00,44.80,340.50,0.530"
+        ),
+    );
+
+    let parsed =
+        internal_collect_measurements_from_sdcard(&[data_file, archive], RtcTimezone::default())
+            .unwrap();
+
+    assert_eq!(parsed.measurements.len(), 2);
+    assert_eq!(parsed.report.header_lines, 2);
+    assert_eq!(parsed.report.duplicate_lines, 1);
+    assert!(parsed.report.rejected_rows.is_empty());
+}
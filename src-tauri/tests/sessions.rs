@@ -0,0 +1,116 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::simulated_bpv::SimulatedBpvDevice;
+use tauri_app_lib::collect_measurements::internal_get_last_measurement;
+use tauri_app_lib::device_profiles::DeviceProfile;
+use tauri_app_lib::rtc_timezone::RtcTimezone;
+use tauri_app_lib::serial_transport::SerialTransport;
+use tauri_app_lib::sessions::SessionManager;
+
+const PORT_NAME: &str = "/dev/ttyACM0";
+
+const DATA_CSV: &str = "Date,Time,Temperature (C),Humidity (%),Light Level (lux),Voltage\r
+2024-05-01,10:00:00,21.50,45.20,120.00,0.512\r
+";
+
+#[test]
+fn keepalives_are_only_sent_to_ports_kept_alive() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let sessions = SessionManager::new(device.clone());
+
+    sessions.send_keepalives();
+    assert!(device.received_commands().is_empty());
+
+    sessions.keep_alive(PORT_NAME, 9600);
+    sessions.send_keepalives();
+    assert_eq!(device.received_commands(), b"p");
+
+    sessions.close(PORT_NAME);
+    sessions.send_keepalives();
+    assert_eq!(device.received_commands(), b"p");
+}
+
+#[test]
+fn keepalives_are_not_sent_while_a_command_uses_the_port() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let sessions = SessionManager::new(device.clone());
+    sessions.keep_alive(PORT_NAME, 9600);
+
+    let connection = sessions
+        .open(PORT_NAME, 9600, Duration::from_millis(100))
+        .unwrap();
+    sessions.send_keepalives();
+    assert!(device.received_commands().is_empty());
+
+    drop(connection);
+    sessions.send_keepalives();
+    assert_eq!(device.received_commands(), b"p");
+
+    // The connection kept open by the session is lent to the next command
+    let measurement = internal_get_last_measurement(
+        &sessions,
+        PORT_NAME.into(),
+        &DeviceProfile::default(),
+        &RtcTimezone::default(),
+    )
+    .unwrap()
+    .measurement
+    .unwrap();
+    assert_eq!(measurement.voltage_millivolts, 512);
+}
+
+#[test]
+fn commands_wait_for_exclusive_access_to_the_port() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let sessions = SessionManager::new(device.clone());
+
+    let connection = sessions
+        .open(PORT_NAME, 9600, Duration::from_millis(100))
+        .unwrap();
+
+    std::thread::scope(|scope| {
+        let waiting = scope.spawn(|| {
+            let start = Instant::now();
+            sessions
+                .open(PORT_NAME, 9600, Duration::from_millis(100))
+                .unwrap();
+            start.elapsed()
+        });
+
+        std::thread::sleep(Duration::from_millis(200));
+        drop(connection);
+
+        assert!(waiting.join().unwrap() >= Duration::from_millis(200));
+    });
+}
+
+#[test]
+fn closing_a_port_in_use_keeps_other_commands_waiting() {
+    let device = SimulatedBpvDevice::new(PORT_NAME, DATA_CSV);
+    let sessions = SessionManager::new(device.clone());
+    sessions.keep_alive(PORT_NAME, 9600);
+
+    let connection = sessions
+        .open(PORT_NAME, 9600, Duration::from_millis(100))
+        .unwrap();
+    // The device is unplugged and plugged back in while the command is still running
+    sessions.close(PORT_NAME);
+    sessions.keep_alive(PORT_NAME, 9600);
+
+    std::thread::scope(|scope| {
+        let waiting = scope.spawn(|| {
+            let start = Instant::now();
+            sessions
+                .open(PORT_NAME, 9600, Duration::from_millis(100))
+                .unwrap();
+            start.elapsed()
+        });
+
+        std::thread::sleep(Duration::from_millis(200));
+        drop(connection);
+
+        assert!(waiting.join().unwrap() >= Duration::from_millis(200));
+    });
+}
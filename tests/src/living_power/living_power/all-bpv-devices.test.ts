@@ -1,6 +1,6 @@
 import { toPromise } from '@holochain-open-dev/signals';
 import { dhtSync, runScenario } from '@holochain/tryorama';
-import { assert, test } from 'vitest';
+import { assert, expect, test } from 'vitest';
 
 import { setup } from './setup.js';
 
@@ -33,3 +33,144 @@ test('create a BpvDevice and get all bpv devices', async () => {
 		assert.ok(info.name === 'alicesdevice' || info.name === 'bobsdevice');
 	});
 });
+
+test('invalid device infos and external resistor values are rejected', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		await expect(
+			alice.store.client.setBpvDeviceInfo('someserialnumber', {
+				name: ' ',
+			}),
+		).rejects.toThrow();
+
+		const now = Date.now() * 1000;
+		// The period ends before it starts
+		await expect(
+			alice.store.client.setExternalResistorValue(
+				'someserialnumber',
+				now,
+				now - 1000,
+				100,
+				undefined,
+			),
+		).rejects.toThrow();
+
+		await alice.store.client.setExternalResistorValue(
+			'someserialnumber',
+			now - 1000,
+			now,
+			100,
+			undefined,
+		);
+		const links =
+			await alice.store.client.getAllExternalResistorValues('someserialnumber');
+		assert.equal(links.length, 1);
+	});
+});
+
+test('device infos and external resistor values are updated in place', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		await alice.store.client.setBpvDeviceInfo('someserialnumber', {
+			name: 'first name',
+		});
+		await alice.store.client.setBpvDeviceInfo('someserialnumber', {
+			name: 'second name',
+		});
+
+		const infoLinks =
+			await alice.store.client.getBpvDeviceInfo('someserialnumber');
+		assert.equal(infoLinks.length, 1);
+		const revisions =
+			await alice.store.client.getAllRevisionsForBpvDeviceInfo(
+				infoLinks[0].target,
+			);
+		assert.deepEqual(
+			revisions.map(revision => revision.entry.name),
+			['first name', 'second name'],
+		);
+
+		const now = Date.now() * 1000;
+		const valueHash = await alice.store.client.setExternalResistorValue(
+			'someserialnumber',
+			now - 1000,
+			now,
+			100,
+			undefined,
+		);
+		await alice.store.client.setExternalResistorValue(
+			'someserialnumber',
+			now - 1000,
+			now,
+			220,
+			valueHash,
+		);
+
+		const values = await toPromise(
+			alice.store.bpvDevices.get('someserialnumber').externalResistorValues,
+		);
+		assert.equal(values.length, 1);
+		assert.equal(values[0][1].external_resistor_value_ohms, 220);
+	});
+});
+
+test('device metadata is versioned and references a photo', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		await alice.store.client.setBpvDeviceInfo('someserialnumber', {
+			name: 'rooftop device',
+		});
+
+		const photoHash = await alice.store.client.createBpvDevicePhoto({
+			data_url: 'data:image/png;base64,iVBORw0KGgo=',
+		});
+		await alice.store.client.setBpvDeviceInfo('someserialnumber', {
+			name: 'rooftop device',
+			location: {
+				latitude_microdegrees: 41_387_917,
+				longitude_microdegrees: 2_169_919,
+			},
+			site_description: 'South facing rooftop garden',
+			species: 'Chlorella vulgaris',
+			electrode_material: 'Carbon felt',
+			installation_date: Date.UTC(2024, 4, 1) * 1000,
+			notes: 'Shaded in the afternoon',
+			photo_hash: photoHash,
+		});
+
+		const info = await toPromise(
+			alice.store.bpvDevices.get('someserialnumber').info,
+		);
+		assert.equal(info!.species, 'Chlorella vulgaris');
+		const photo = await alice.store.client.getBpvDevicePhoto(
+			info!.photo_hash!,
+		);
+		assert.equal(photo!.entry.data_url, 'data:image/png;base64,iVBORw0KGgo=');
+
+		const revisions = await toPromise(
+			alice.store.bpvDevices.get('someserialnumber').infoRevisions,
+		);
+		assert.equal(revisions.length, 2);
+		assert.notOk(revisions[0].entry.species);
+
+		// Out of range latitude
+		await expect(
+			alice.store.client.setBpvDeviceInfo('someserialnumber', {
+				name: 'rooftop device',
+				location: {
+					latitude_microdegrees: 91_000_000,
+					longitude_microdegrees: 0,
+				},
+			}),
+		).rejects.toThrow();
+
+		await expect(
+			alice.store.client.createBpvDevicePhoto({
+				data_url: 'not an image',
+			}),
+		).rejects.toThrow();
+	});
+});
@@ -9,11 +9,11 @@ import {
 } from '@holochain/client';
 import { dhtSync, runScenario } from '@holochain/tryorama';
 import { decode } from '@msgpack/msgpack';
-import { assert, test } from 'vitest';
+import { assert, expect, test } from 'vitest';
 
 import { sampleMeasurementCollection } from '../../../../ui/src/living_power/living_power/mocks.js';
 import { MeasurementCollection } from '../../../../ui/src/living_power/living_power/types.js';
-import { setup } from './setup.js';
+import { setup, setupWithAdmin } from './setup.js';
 
 test('create MeasurementCollection', async () => {
 	await runScenario(async scenario => {
@@ -52,6 +52,243 @@ test('create and read MeasurementCollection', async () => {
 	});
 });
 
+test('committing the same measurements twice skips them', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		const sample = await sampleMeasurementCollection(alice.store.client);
+
+		const created =
+			await alice.store.client.createNewMeasurementCollections(sample);
+		assert.equal(created.action_hashes.length, 1);
+		assert.equal(created.skipped_measurements, 0);
+
+		const newMeasurement = {
+			...sample.measurements[0],
+			timestamp: sample.measurements[0].timestamp + 1000,
+		};
+		const createdAgain =
+			await alice.store.client.createNewMeasurementCollections({
+				...sample,
+				measurements: [...sample.measurements, newMeasurement],
+			});
+		assert.equal(createdAgain.action_hashes.length, 1);
+		assert.equal(createdAgain.skipped_measurements, 1);
+
+		const createdThrice =
+			await alice.store.client.createNewMeasurementCollections(sample);
+		assert.equal(createdThrice.action_hashes.length, 0);
+		assert.equal(createdThrice.skipped_measurements, 1);
+	});
+});
+
+test('invalid measurements are rejected without failing the others', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		const sample = await sampleMeasurementCollection(alice.store.client);
+
+		// Logged after the RTC was reset to 2000-01-01, read as the year 0
+		const beforeTheYear2000 = {
+			...sample.measurements[0],
+			timestamp:
+				sample.measurements[0].timestamp -
+				2000 * 365 * 24 * 60 * 60 * 1_000_000,
+		};
+		const inTheFuture = {
+			...sample.measurements[0],
+			timestamp: Date.now() * 1000 + 30 * 24 * 60 * 60 * 1_000_000,
+		};
+		const outOfRange = {
+			...sample.measurements[0],
+			timestamp: sample.measurements[0].timestamp + 1000,
+			humidity_percentage: 1_000_000,
+		};
+		const created =
+			await alice.store.client.createNewMeasurementCollections({
+				...sample,
+				measurements: [
+					...sample.measurements,
+					beforeTheYear2000,
+					inTheFuture,
+					outOfRange,
+				],
+			});
+		assert.equal(created.action_hashes.length, 1);
+		assert.equal(created.skipped_measurements, 0);
+		assert.equal(created.rejected_measurements, 3);
+	});
+});
+
+test('rows sharing a timestamp with a committed one are not counted as committed', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		const sample = await sampleMeasurementCollection(alice.store.client);
+		await alice.store.client.createNewMeasurementCollections(sample);
+
+		// Logged at the same device time after the RTC was reset
+		const sameTimestamp = {
+			...sample.measurements[0],
+			temperature_celsius: sample.measurements[0].temperature_celsius + 1000,
+		};
+		const created =
+			await alice.store.client.createNewMeasurementCollections({
+				...sample,
+				measurements: [sameTimestamp],
+			});
+		assert.equal(created.skipped_measurements, 1);
+
+		assert.equal(
+			await alice.store.client.countCommittedMeasurements(
+				sample.arduino_serial_number,
+				[sample.measurements[0], sameTimestamp],
+			),
+			1,
+		);
+	});
+});
+
+test('incremental collections resume from the device time of corrected measurements', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		const HOUR = 60 * 60 * 1000 * 1000;
+		const now = Date.now() * 1000;
+		// The device clock runs an hour behind, so its measurements are corrected an hour forward
+		const deviceMeasurement = (rawTimestamp: number) => ({
+			humidity_percentage: 40,
+			light_level_lux: 20,
+			temperature_celsius: 10,
+			voltage_millivolts: 300,
+			timestamp: rawTimestamp + HOUR,
+			raw_timestamp: rawTimestamp,
+		});
+
+		await alice.store.client.createNewMeasurementCollections({
+			arduino_serial_number: 'someserialnumber',
+			measurements: [
+				deviceMeasurement(now - 4 * HOUR),
+				deviceMeasurement(now - 3 * HOUR),
+			],
+		});
+
+		const since =
+			await alice.store.client.getLastMeasurementTimestamp('someserialnumber');
+		assert.equal(since, now - 3 * HOUR);
+
+		// Logged after the last committed row, but before its corrected timestamp
+		const logged = [
+			deviceMeasurement(now - 3 * HOUR),
+			deviceMeasurement(now - 2.5 * HOUR),
+			deviceMeasurement(now - 2 * HOUR),
+		];
+		const incremental = logged.filter(m => m.raw_timestamp > since!);
+		assert.equal(incremental.length, 2);
+
+		const created = await alice.store.client.createNewMeasurementCollections({
+			arduino_serial_number: 'someserialnumber',
+			measurements: incremental,
+		});
+		assert.equal(created.action_hashes.length, 1);
+		assert.equal(created.skipped_measurements, 0);
+		assert.equal(
+			await alice.store.client.getLastMeasurementTimestamp('someserialnumber'),
+			now - 2 * HOUR,
+		);
+	});
+});
+
+test('live measurements are left out of incremental collections', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		const HOUR = 60 * 60 * 1000 * 1000;
+		const now = Date.now() * 1000;
+		const measurement = (timestamp: number) => ({
+			humidity_percentage: 40,
+			light_level_lux: 20,
+			temperature_celsius: 10,
+			voltage_millivolts: 300,
+			timestamp,
+		});
+
+		await alice.store.client.createNewMeasurementCollections({
+			arduino_serial_number: 'someserialnumber',
+			measurements: [measurement(now - 3 * HOUR)],
+		});
+		// Live samples streamed after rows that were logged but not collected yet
+		const live = await alice.store.client.createNewMeasurementCollections({
+			arduino_serial_number: 'someserialnumber',
+			measurements: [measurement(now - HOUR), measurement(now - 2 * HOUR)],
+			live: true,
+		});
+		assert.equal(live.action_hashes.length, 1);
+
+		assert.equal(
+			await alice.store.client.getLastMeasurementTimestamp('someserialnumber'),
+			now - 3 * HOUR,
+		);
+
+		// A logged row at the same time as a live sample is still committed
+		const logged = await alice.store.client.createNewMeasurementCollections({
+			arduino_serial_number: 'someserialnumber',
+			measurements: [measurement(now - 2 * HOUR)],
+		});
+		assert.equal(logged.action_hashes.length, 1);
+		assert.equal(logged.skipped_measurements, 0);
+		assert.equal(
+			await alice.store.client.getLastMeasurementTimestamp('someserialnumber'),
+			now - 2 * HOUR,
+		);
+	});
+});
+
+test('invalid measurement collections are rejected', async () => {
+	await runScenario(async scenario => {
+		const { alice } = await setup(scenario);
+
+		const sample = await sampleMeasurementCollection(alice.store.client);
+		const measurement = sample.measurements[0];
+
+		// Implausible humidity
+		await expect(
+			alice.store.client.createMeasurementCollection({
+				...sample,
+				measurements: [{ ...measurement, humidity_percentage: 150000 }],
+			}),
+		).rejects.toThrow();
+
+		// A week in the future
+		await expect(
+			alice.store.client.createMeasurementCollection({
+				...sample,
+				measurements: [
+					{
+						...measurement,
+						timestamp: measurement.timestamp + 7 * 24 * 60 * 60 * 1000 * 1000,
+					},
+				],
+			}),
+		).rejects.toThrow();
+
+		// Before the device clock could have been set
+		await expect(
+			alice.store.client.createMeasurementCollection({
+				...sample,
+				measurements: [{ ...measurement, timestamp: 0 }],
+			}),
+		).rejects.toThrow();
+
+		await expect(
+			alice.store.client.createMeasurementCollection({
+				...sample,
+				arduino_serial_number: ' ',
+			}),
+		).rejects.toThrow();
+	});
+});
+
 test('create and delete MeasurementCollection', async () => {
 	await runScenario(async scenario => {
 		const { alice, bob } = await setup(scenario);
@@ -81,3 +318,130 @@ test('create and delete MeasurementCollection', async () => {
 		assert.equal(deletes.length, 1);
 	});
 });
+
+test('only the author of a MeasurementCollection can delete it', async () => {
+	await runScenario(async scenario => {
+		const { alice, bob } = await setup(scenario);
+
+		const measurementCollectionsHashes: ActionHash[] =
+			await alice.store.client.createMeasurementCollection(
+				await sampleMeasurementCollection(alice.store.client),
+			);
+
+		await dhtSync([alice.player, bob.player], alice.player.cells[0].cell_id[0]);
+
+		// Bob is neither an admin nor an owner of the device
+		assert.deepEqual(await bob.store.client.getMyRoles(), []);
+		await expect(
+			bob.store.client.deleteMeasurementCollection(
+				measurementCollectionsHashes[0],
+			),
+		).rejects.toThrow();
+
+		// Bob can't grant themselves a role either
+		await expect(
+			bob.store.client.grantRole(bob.player.agentPubKey, { type: 'Admin' }),
+		).rejects.toThrow();
+
+		const deleteActionHash =
+			await alice.store.client.deleteMeasurementCollection(
+				measurementCollectionsHashes[0],
+			);
+		assert.ok(deleteActionHash);
+	});
+});
+
+test('admins and device owners can delete the MeasurementCollections of other agents', async () => {
+	await runScenario(async scenario => {
+		// Bob is an admin in the DNA properties
+		const { alice, bob } = await setupWithAdmin(scenario);
+
+		const sample = await sampleMeasurementCollection(alice.store.client);
+
+		const aliceCollectionHashes: ActionHash[] =
+			await alice.store.client.createMeasurementCollection(sample);
+
+		await dhtSync([alice.player, bob.player], alice.player.cells[0].cell_id[0]);
+
+		const adminDeleteActionHash =
+			await bob.store.client.deleteMeasurementCollection(
+				aliceCollectionHashes[0],
+			);
+		assert.ok(adminDeleteActionHash);
+
+		// Bob makes alice an owner of the device
+		const bobCollectionHashes: ActionHash[] =
+			await bob.store.client.createMeasurementCollection(sample);
+		const roleGrantHash = await bob.store.client.grantRole(
+			alice.player.agentPubKey,
+			{
+				type: 'DeviceOwner',
+				arduino_serial_number: sample.arduino_serial_number,
+			},
+		);
+
+		await dhtSync([alice.player, bob.player], alice.player.cells[0].cell_id[0]);
+
+		// The role is only in effect once alice has claimed it
+		await expect(
+			alice.store.client.deleteMeasurementCollection(bobCollectionHashes[0]),
+		).rejects.toThrow();
+
+		await alice.store.client.claimRole(roleGrantHash);
+		assert.deepEqual(await alice.store.client.getMyRoles(), [
+			{
+				type: 'DeviceOwner',
+				arduino_serial_number: sample.arduino_serial_number,
+			},
+		]);
+
+		const ownerDeleteActionHash =
+			await alice.store.client.deleteMeasurementCollection(
+				bobCollectionHashes[0],
+			);
+		assert.ok(ownerDeleteActionHash);
+	});
+});
+
+test('the agent that registers a device owns it', async () => {
+	await runScenario(async scenario => {
+		// The shipped hApp has no admins in its DNA properties
+		const { alice, bob } = await setup(scenario);
+
+		const sample = await sampleMeasurementCollection(alice.store.client);
+		await alice.store.client.setBpvDeviceInfo(sample.arduino_serial_number, {
+			name: 'alicesdevice',
+		});
+		assert.deepEqual(await alice.store.client.getMyRoles(), [
+			{
+				type: 'DeviceOwner',
+				arduino_serial_number: sample.arduino_serial_number,
+			},
+		]);
+
+		await dhtSync([alice.player, bob.player], alice.player.cells[0].cell_id[0]);
+
+		// Bob only edits the info of the device, and can't claim it
+		await bob.store.client.setBpvDeviceInfo(sample.arduino_serial_number, {
+			name: 'bobsdevice',
+		});
+		assert.deepEqual(await bob.store.client.getMyRoles(), []);
+		await expect(
+			bob.store.client.grantRole(bob.player.agentPubKey, {
+				type: 'DeviceOwner',
+				arduino_serial_number: sample.arduino_serial_number,
+			}),
+		).rejects.toThrow();
+
+		const bobCollectionHashes: ActionHash[] =
+			await bob.store.client.createMeasurementCollection(sample);
+
+		await dhtSync([alice.player, bob.player], alice.player.cells[0].cell_id[0]);
+
+		const ownerDeleteActionHash =
+			await alice.store.client.deleteMeasurementCollection(
+				bobCollectionHashes[0],
+			);
+		assert.ok(ownerDeleteActionHash);
+	});
+});
@@ -1,4 +1,9 @@
-import { Scenario } from '@holochain/tryorama';
+import { AgentPubKey } from '@holochain/client';
+import {
+	PlayerApp,
+	Scenario,
+	enableAndGetAgentApp,
+} from '@holochain/tryorama';
 
 import { LivingPowerClient } from '../../../../ui/src/living_power/living_power/living-power-client.js';
 import { LivingPowerStore } from '../../../../ui/src/living_power/living_power/living-power-store.js';
@@ -42,3 +47,70 @@ export async function setup(
 		},
 	};
 }
+
+/**
+ * Like `setup`, but with bob as an admin in the DNA properties of the network
+ */
+export async function setupWithAdmin(scenario: Scenario) {
+	const aliceConductor = await scenario.addConductor();
+	const bobConductor = await scenario.addConductor();
+
+	// The admins need to be in the DNA properties before the app is installed
+	const bobPubKey = await bobConductor.adminWs().generateAgentPubKey();
+	const rolesSettings = {
+		living_power: {
+			type: 'provisioned' as const,
+			modifiers: {
+				// The properties go through YAML, which has no binary type
+				properties: { admins: [Array.from(bobPubKey)] },
+			},
+		},
+	};
+
+	const installPlayer = async (
+		conductor: typeof aliceConductor,
+		agentPubKey?: AgentPubKey,
+	): Promise<PlayerApp> => {
+		const appInfo = await conductor.installApp(
+			{ path: appPath },
+			{
+				agentPubKey,
+				networkSeed: scenario.networkSeed,
+				rolesSettings,
+			},
+		);
+		const port = await conductor.attachAppInterface();
+		const issued = await conductor.adminWs().issueAppAuthenticationToken({
+			installed_app_id: appInfo.installed_app_id,
+		});
+		const appWs = await conductor.connectAppWs(issued.token, port);
+		const agentApp = await enableAndGetAgentApp(
+			conductor.adminWs(),
+			appWs,
+			appInfo,
+		);
+		return { conductor, appWs, ...agentApp };
+	};
+
+	const alice = await installPlayer(aliceConductor);
+	const bob = await installPlayer(bobConductor, bobPubKey);
+
+	// Shortcut peer discovery through gossip and register all agents in every
+	// conductor of the scenario.
+	await scenario.shareAllAgents();
+
+	return {
+		alice: {
+			player: alice,
+			store: new LivingPowerStore(
+				new LivingPowerClient(alice.appWs as any, 'living_power', 'living_power'),
+			),
+		},
+		bob: {
+			player: bob,
+			store: new LivingPowerStore(
+				new LivingPowerClient(bob.appWs as any, 'living_power', 'living_power'),
+			),
+		},
+	};
+}
@@ -1,6 +1,6 @@
 import { toPromise } from '@holochain-open-dev/signals';
 import { decodePath } from '@holochain-open-dev/utils';
-import { CellId, CellType } from '@holochain/client';
+import { CellId, CellType, Link } from '@holochain/client';
 import {
 	AgentApp,
 	dhtSync,
@@ -8,6 +8,7 @@ import {
 	pause,
 	runScenario,
 } from '@holochain/tryorama';
+import { decode } from '@msgpack/msgpack';
 import { assert, test } from 'vitest';
 
 import { LivingPowerClient } from '../../ui/src/living_power/living_power/living-power-client.js';
@@ -26,9 +27,20 @@ test('migrate from the previous happ to the new version, assert that the data is
 
 		const arduinoSerialNumber = 'someserialnumber';
 
+		// The previous version stored the device info and the external resistor values in link tags,
+		// so they are set with its own zome API instead of the current client
+		const oldCell = alice.player.cells[0];
+
 		// Alice adds a BpvDevice
-		await alice.store.client.setBpvDeviceInfo(arduinoSerialNumber, {
-			name: 'alicesdevice',
+		await oldCell.callZome({
+			zome_name: 'living_power',
+			fn_name: 'set_bpv_device_info',
+			payload: {
+				arduino_serial_number: arduinoSerialNumber,
+				info: {
+					name: 'alicesdevice',
+				},
+			},
 		});
 
 		await dhtSync([alice.player, bob.player], alice.player.cells[0].cell_id[0]);
@@ -37,7 +49,12 @@ test('migrate from the previous happ to the new version, assert that the data is
 		collectionOutput = await toPromise(bob.store.allBpvDevices);
 		assert.equal(collectionOutput.size, 1);
 		assert.equal(Array.from(collectionOutput.keys())[0], arduinoSerialNumber);
-		const info = await toPromise(Array.from(collectionOutput.values())[0].info);
+		const infoLinks: Array<Link> = await bob.player.cells[0].callZome({
+			zome_name: 'living_power',
+			fn_name: 'get_bpv_device_info',
+			payload: arduinoSerialNumber,
+		});
+		const info = decode(infoLinks[0].tag) as { name: string };
 
 		assert.equal(info.name, 'alicesdevice');
 
@@ -47,14 +64,51 @@ test('migrate from the previous happ to the new version, assert that the data is
 		await alice.store.client.createMeasurementCollection(
 			await sampleMeasurementCollection(alice.store.client),
 		);
+		// Committed before the measurements were validated
+		const sample = await sampleMeasurementCollection(alice.store.client);
+		await alice.store.client.createMeasurementCollection({
+			...sample,
+			measurements: [
+				// Unset clock of a device read in UTC+4, before 2000 in UTC
+				{
+					...sample.measurements[0],
+					timestamp: Date.UTC(1999, 11, 31, 20) * 1000,
+				},
+				{ ...sample.measurements[0], timestamp: Date.UTC(1990, 0, 1) * 1000 },
+			],
+		});
+		await alice.store.client.createMeasurementCollection({
+			...sample,
+			measurements: [{ ...sample.measurements[0], humidity_percentage: 150000 }],
+		});
 
-		await alice.store.client.setExternalResistorValue(
-			arduinoSerialNumber,
-			Date.now() - 1000 * 60 * 60,
-			Date.now(),
-			10,
-			undefined,
-		);
+		await oldCell.callZome({
+			zome_name: 'living_power',
+			fn_name: 'set_external_resistor_value',
+			payload: {
+				arduino_serial_number: arduinoSerialNumber,
+				previous_create_link_action_hash: undefined,
+				external_resistor_value: {
+					from: Date.now() - 1000 * 60 * 60,
+					to: Date.now(),
+					external_resistor_value_ohms: 10,
+				},
+			},
+		});
+		// The previous version didn't validate that the periods start before they end
+		await oldCell.callZome({
+			zome_name: 'living_power',
+			fn_name: 'set_external_resistor_value',
+			payload: {
+				arduino_serial_number: arduinoSerialNumber,
+				previous_create_link_action_hash: undefined,
+				external_resistor_value: {
+					from: Date.now(),
+					to: Date.now() - 1000 * 60 * 60,
+					external_resistor_value_ohms: 20,
+				},
+			},
+		});
 
 		const appInfo = await alice.player.conductor.installApp(
 			{
@@ -82,12 +136,16 @@ test('migrate from the previous happ to the new version, assert that the data is
 			previousAppInfo.cell_info['living_power'][0][CellType.Provisioned]
 				.cell_id;
 
-		await appWs.callZome({
+		const report = await appWs.callZome({
 			zome_name: 'living_power',
 			role_name: 'living_power',
 			payload: previousCellId,
 			fn_name: 'migrate_from_old_cell',
 		});
+		// The invalid measurements are skipped instead of failing the whole migration
+		assert.equal(report.skipped_measurements, 2);
+		assert.equal(report.skipped_measurement_collections, 1);
+		assert.equal(report.skipped_external_resistor_values, 1);
 
 		await pause(200);
 		const aliceStore2 = new LivingPowerStore(
@@ -106,11 +164,32 @@ test('migrate from the previous happ to the new version, assert that the data is
 			aliceStore2.bpvDevices.get(arduinoSerialNumber).measurementCollections
 				.live,
 		);
-		assert.equal(measurements.size, 2);
+		assert.equal(measurements.size, 3);
 
 		const externalResistorValues = await toPromise(
 			aliceStore2.bpvDevices.get(arduinoSerialNumber).externalResistorValues,
 		);
 		assert.equal(externalResistorValues.length, 1);
+		assert.equal(externalResistorValues[0][1].external_resistor_value_ohms, 10);
+
+		// Migrating again doesn't duplicate the info or the external resistor values
+		await appWs.callZome({
+			zome_name: 'living_power',
+			role_name: 'living_power',
+			payload: previousCellId,
+			fn_name: 'migrate_from_old_cell',
+		});
+		const externalResistorValueLinks =
+			await aliceStore2.client.getAllExternalResistorValues(
+				arduinoSerialNumber,
+			);
+		assert.equal(externalResistorValueLinks.length, 1);
+		const infoLinks2 = await aliceStore2.client.getBpvDeviceInfo(
+			arduinoSerialNumber,
+		);
+		const revisions = await aliceStore2.client.getAllRevisionsForBpvDeviceInfo(
+			infoLinks2[0].target,
+		);
+		assert.equal(revisions.length, 1);
 	});
 });
@@ -1,27 +1,141 @@
+import { ActionHash } from '@holochain/client';
 import { invoke } from '@tauri-apps/api/core';
+import { UnlistenFn, listen } from '@tauri-apps/api/event';
 
 import { Measurement } from '../living_power/living_power/types.js';
 
+export interface RejectedRow {
+	line_number: number;
+	raw: string;
+	reason: string;
+}
+
+export interface ParseReport {
+	rejected_rows: Array<RejectedRow>;
+	header_lines: number;
+	blank_lines: number;
+	duplicate_lines: number;
+}
+
+export interface ParsedMeasurements {
+	measurements: Array<Measurement>;
+	report: ParseReport;
+}
+
+export interface CollectedMeasurements {
+	measurements: Array<Measurement>;
+	report: ParseReport;
+	cancelled: boolean;
+	/**
+	 * The transfer was interrupted and couldn't be resumed, so only part of the measurements were received
+	 */
+	incomplete: boolean;
+	checksum: number;
+}
+
+export interface ArchivedMeasurements {
+	collected: CollectedMeasurements;
+	action_hashes: Array<ActionHash>;
+	/**
+	 * Measurements that were already committed by an earlier collection
+	 */
+	skipped_measurements: number;
+	/**
+	 * Measurements that were not committed because they are invalid, like the ones from before 2000 or in the future
+	 */
+	rejected_measurements: number;
+	archive_file_name: string;
+}
+
+export interface ClockOffset {
+	device_time: number;
+	host_time: number;
+	clock_set?: boolean;
+}
+
+export interface LastMeasurement {
+	measurement: Measurement | undefined;
+	clock_offset: ClockOffset | undefined;
+}
+
+export interface CollectionProgress {
+	port_name: string;
+	bytes_received: number;
+	total_bytes: number | undefined;
+	rows_received: number;
+	parse_errors: number;
+	estimated_remaining_ms: number | undefined;
+}
+
 export function collectMeasurements(
 	portName: string,
-): Promise<Array<Measurement>> {
+): Promise<CollectedMeasurements> {
 	return invoke('collect_measurements', {
 		portName,
 	});
 }
 
+export function collectNewMeasurements(
+	portName: string,
+	arduinoSerialNumber: string,
+): Promise<CollectedMeasurements> {
+	return invoke('collect_new_measurements', {
+		portName,
+		arduinoSerialNumber,
+	});
+}
+
+export function collectAndArchiveMeasurements(
+	portName: string,
+	arduinoSerialNumber: string,
+	correctClockDrift: boolean,
+): Promise<ArchivedMeasurements> {
+	return invoke('collect_and_archive_measurements', {
+		portName,
+		arduinoSerialNumber,
+		correctClockDrift,
+	});
+}
+
+export function cancelCollection(portName: string): Promise<void> {
+	return invoke('cancel_collection', {
+		portName,
+	});
+}
+
+export function onCollectionProgress(
+	handler: (progress: CollectionProgress) => void,
+): Promise<UnlistenFn> {
+	return listen<CollectionProgress>('collect-measurements-progress', event =>
+		handler(event.payload),
+	);
+}
+
 export async function getLastMeasurement(
 	portName: string,
-): Promise<Measurement | undefined> {
+): Promise<LastMeasurement> {
 	return invoke('get_last_measurement', {
 		portName,
 	});
 }
 
-export function collectMeasurementsFromSdcard(
-	sdcardPath: string,
+export function correctClockDrift(
+	arduinoSerialNumber: string,
+	measurements: Array<Measurement>,
 ): Promise<Array<Measurement>> {
+	return invoke('correct_measurements_clock_drift', {
+		arduinoSerialNumber,
+		measurements,
+	});
+}
+
+/**
+ * Parses all the data files of the sdcard directory, including the archived ones
+ */
+export function collectMeasurementsFromSdcard(
+	directory: string,
+): Promise<ParsedMeasurements> {
 	return invoke('collect_measurements_from_sdcard', {
-		mountpoint: sdcardPath,
+		directory,
 	});
 }
@@ -1,53 +1,124 @@
 import { AsyncSignal, AsyncState, Signal } from '@holochain-open-dev/signals';
 import { core } from '@tauri-apps/api';
+import { UnlistenFn, listen } from '@tauri-apps/api/event';
 import isEqual from 'lodash-es/isEqual.js';
 
 export interface SerialPortInfo {
 	port_name: string;
 	port_type: PortType | undefined;
+	/**
+	 * The serial number of the board, read from the USB port or asked to the board itself
+	 */
+	arduino_serial_number: string | undefined;
 }
 export type PortType = {
 	UsbPort: {
-		manufacturer: string;
+		manufacturer: string | undefined;
 		pid: number;
-		product: string;
-		serial_number: string;
+		product: string | undefined;
+		serial_number: string | undefined;
 		vid: number;
 	};
 };
 
-export function connectedArduinos(
-	intervalMs: number = 1000,
-): AsyncSignal<Array<SerialPortInfo>> {
-	let interval: any;
+export interface UsbId {
+	vid: number;
+	/**
+	 * Matches every product of the vendor if undefined
+	 */
+	pid: number | undefined;
+}
+
+export interface ArduinoIdentification {
+	usb_ids: Array<UsbId>;
+	probe_serial_number: boolean;
+}
+
+export function getArduinoIdentification(): Promise<ArduinoIdentification> {
+	return core.invoke('get_arduino_identification');
+}
+
+export function setArduinoIdentification(
+	identification: ArduinoIdentification,
+): Promise<void> {
+	return core.invoke('set_arduino_identification', {
+		identification,
+	});
+}
+
+export function onArduinoConnected(
+	handler: (arduino: SerialPortInfo) => void,
+): Promise<UnlistenFn> {
+	return listen<SerialPortInfo>('arduino-connected', event =>
+		handler(event.payload),
+	);
+}
+
+export function onArduinoDisconnected(
+	handler: (arduino: SerialPortInfo) => void,
+): Promise<UnlistenFn> {
+	return listen<SerialPortInfo>('arduino-disconnected', event =>
+		handler(event.payload),
+	);
+}
+
+/**
+ * The connected Arduinos, listed once and then kept up to date with the hotplug events
+ */
+export function connectedArduinos(): AsyncSignal<Array<SerialPortInfo>> {
+	let unlisteners: Array<Promise<UnlistenFn>> = [];
 	const arduinos = new AsyncState<Array<SerialPortInfo>>(
 		{
 			status: 'pending',
 		},
 		{
 			[Signal.subtle.watched]: () => {
-				interval = setInterval(() => {
-					core
-						.invoke('list_connected_arduinos')
-						.then(listedArduinos => {
-							arduinos.set({
-								status: 'completed',
-								value: listedArduinos as Array<SerialPortInfo>,
-							});
-						})
-						.catch(error => {
-							arduinos.set({
-								status: 'error',
-								error,
-							});
+				const update = (
+					fn: (arduinos: Array<SerialPortInfo>) => Array<SerialPortInfo>,
+				) => {
+					const current = arduinos.get();
+					if (current.status !== 'completed') return;
+					arduinos.set({
+						status: 'completed',
+						value: fn(current.value),
+					});
+				};
+				unlisteners = [
+					onArduinoConnected(arduino =>
+						update(value => [
+							...value.filter(a => a.port_name !== arduino.port_name),
+							arduino,
+						]),
+					),
+					onArduinoDisconnected(arduino =>
+						update(value =>
+							value.filter(a => a.port_name !== arduino.port_name),
+						),
+					),
+				];
+				core
+					.invoke('list_connected_arduinos')
+					.then(listedArduinos => {
+						arduinos.set({
+							status: 'completed',
+							value: listedArduinos as Array<SerialPortInfo>,
+						});
+					})
+					.catch(error => {
+						arduinos.set({
+							status: 'error',
+							error,
 						});
-				}, intervalMs);
+					});
 			},
 			[Signal.subtle.unwatched]: () => {
 				arduinos.set({
 					status: 'pending',
 				});
-				clearInterval(interval);
+				for (const unlisten of unlisteners) {
+					unlisten.then(u => u());
+				}
+				unlisteners = [];
 			},
 			equals: isEqual,
 		},
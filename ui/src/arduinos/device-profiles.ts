@@ -0,0 +1,37 @@
+import { core } from '@tauri-apps/api';
+
+export type LineEnding = 'lf' | 'cr_lf';
+
+/**
+ * 'framed' falls back to the legacy commands if the device doesn't answer the newer ones,
+ * 'legacy' only sends 'c' and 'l'
+ */
+export type CommandSet = 'framed' | 'legacy';
+
+export interface DeviceProfile {
+	name: string;
+	baud_rate: number;
+	command_timeout_ms: number;
+	response_timeout_ms: number;
+	transfer_timeout_ms: number;
+	line_ending: LineEnding;
+	command_set: CommandSet;
+}
+
+export interface DeviceProfiles {
+	profiles: Array<DeviceProfile>;
+	/**
+	 * Name of the profile to use for each arduino_serial_number, the others use the "default" profile
+	 */
+	assignments: Record<string, string>;
+}
+
+export function getDeviceProfiles(): Promise<DeviceProfiles> {
+	return core.invoke('get_device_profiles');
+}
+
+export function setDeviceProfiles(profiles: DeviceProfiles): Promise<void> {
+	return core.invoke('set_device_profiles', {
+		profiles,
+	});
+}
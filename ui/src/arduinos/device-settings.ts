@@ -0,0 +1,56 @@
+import { invoke } from '@tauri-apps/api/core';
+
+import { ClockOffset } from './collect-measurements.js';
+
+export type SleepMode = 'deep' | 'light' | 'off';
+
+export function getDeviceTime(portName: string): Promise<ClockOffset> {
+	return invoke('get_device_time', {
+		portName,
+	});
+}
+
+/**
+ * Sets the clock of the device to the current time
+ */
+export function setDeviceTime(
+	portName: string,
+	arduinoSerialNumber: string,
+): Promise<ClockOffset> {
+	return invoke('set_device_time', {
+		portName,
+		arduinoSerialNumber,
+	});
+}
+
+export function getLoggingInterval(portName: string): Promise<number> {
+	return invoke('get_logging_interval', {
+		portName,
+	});
+}
+
+export function setLoggingInterval(
+	portName: string,
+	loggingIntervalMs: number,
+): Promise<number> {
+	return invoke('set_logging_interval', {
+		portName,
+		loggingIntervalMs,
+	});
+}
+
+export function getSleepMode(portName: string): Promise<SleepMode> {
+	return invoke('get_sleep_mode', {
+		portName,
+	});
+}
+
+export function setSleepMode(
+	portName: string,
+	sleepMode: SleepMode,
+): Promise<SleepMode> {
+	return invoke('set_sleep_mode', {
+		portName,
+		sleepMode,
+	});
+}
@@ -0,0 +1,96 @@
+import { ActionHash } from '@holochain/client';
+import { invoke } from '@tauri-apps/api/core';
+
+import { Measurement } from '../living_power/living_power/types.js';
+import { ParseReport } from './collect-measurements.js';
+
+/**
+ * The column names the parser understands, to map the header cells of a CSV file to
+ */
+export const COLUMN_NAMES = [
+	'Date',
+	'Time',
+	'Timestamp',
+	'Temperature (C)',
+	'Temperature (F)',
+	'Temperature (K)',
+	'Humidity (%)',
+	'Light Level (lux)',
+	'Voltage (V)',
+	'Voltage (mV)',
+];
+
+/**
+ * Header cell of the file to one of the COLUMN_NAMES, or to '' to ignore the column
+ */
+export type ColumnMapping = Record<string, string>;
+
+export interface HeaderColumn {
+	header: string;
+	/**
+	 * One of the COLUMN_NAMES after applying the mapping, undefined if the column is ignored
+	 */
+	column: string | undefined;
+	error: string | undefined;
+}
+
+export interface ImportedFile {
+	path: string;
+	header: Array<HeaderColumn>;
+	measurements: number;
+	report: ParseReport;
+	/**
+	 * Why the file couldn't be parsed at all, like a header with missing columns
+	 */
+	error: string | undefined;
+}
+
+export interface ImportPreview {
+	files: Array<ImportedFile>;
+	/**
+	 * The first measurements that would be imported
+	 */
+	measurements: Array<Measurement>;
+	total_measurements: number;
+}
+
+export interface ImportedMeasurements {
+	files: Array<ImportedFile>;
+	imported_measurements: number;
+	/**
+	 * Measurements that were not imported because the device already had one at their timestamp
+	 */
+	skipped_measurements: number;
+	/**
+	 * Measurements that were not imported because they are invalid, like the ones from before 2000 or in the future
+	 */
+	rejected_measurements: number;
+	action_hashes: Array<ActionHash>;
+}
+
+/**
+ * Parses the CSV file, or all the CSV files in the directory, without importing them
+ */
+export function previewMeasurementsImport(
+	path: string,
+	arduinoSerialNumber: string,
+	columnMapping: ColumnMapping = {},
+): Promise<ImportPreview> {
+	return invoke('preview_measurements_import', {
+		path,
+		arduinoSerialNumber,
+		columnMapping,
+	});
+}
+
+export function importMeasurements(
+	path: string,
+	arduinoSerialNumber: string,
+	columnMapping: ColumnMapping = {},
+): Promise<ImportedMeasurements> {
+	return invoke('import_measurements', {
+		path,
+		arduinoSerialNumber,
+		columnMapping,
+	});
+}
@@ -0,0 +1,87 @@
+import { ActionHash } from '@holochain/client';
+import { invoke } from '@tauri-apps/api/core';
+import { UnlistenFn, listen } from '@tauri-apps/api/event';
+
+import { Measurement } from '../living_power/living_power/types.js';
+
+export interface LiveMeasurement {
+	port_name: string;
+	measurement: Measurement;
+}
+
+export interface LiveMeasurementsEnded {
+	port_name: string;
+	error: string;
+}
+
+export interface LiveRecording {
+	measurements: Array<Measurement>;
+	/**
+	 * The interval confirmed by the device, which may be longer than the requested one
+	 */
+	interval_ms: number;
+	/**
+	 * The stream ended because the connection to the device broke
+	 */
+	interrupted: boolean;
+}
+
+export interface StoppedLiveMeasurements {
+	recording: LiveRecording;
+	action_hashes: Array<ActionHash>;
+	/**
+	 * Samples that were not saved because the device already had a measurement at their timestamp
+	 */
+	skipped_measurements: number;
+	/**
+	 * Samples that were not saved because they are invalid, like the ones read while the clock of the device was reset
+	 */
+	rejected_measurements: number;
+}
+
+/**
+ * Starts streaming the sensor readings of the device, the port stays busy until stopLiveMeasurements is called
+ */
+export function startLiveMeasurements(
+	portName: string,
+	arduinoSerialNumber: string,
+	intervalMs: number,
+): Promise<void> {
+	return invoke('start_live_measurements', {
+		portName,
+		arduinoSerialNumber,
+		intervalMs,
+	});
+}
+
+/**
+ * Stops the stream, saving its readings as a new measurement collection if persist is true
+ */
+export function stopLiveMeasurements(
+	portName: string,
+	persist: boolean,
+): Promise<StoppedLiveMeasurements> {
+	return invoke('stop_live_measurements', {
+		portName,
+		persist,
+	});
+}
+
+export function onLiveMeasurement(
+	handler: (liveMeasurement: LiveMeasurement) => void,
+): Promise<UnlistenFn> {
+	return listen<LiveMeasurement>('live-measurement', event =>
+		handler(event.payload),
+	);
+}
+
+/**
+ * Fires when a stream ends without being stopped, like when the device is disconnected
+ */
+export function onLiveMeasurementsEnded(
+	handler: (ended: LiveMeasurementsEnded) => void,
+): Promise<UnlistenFn> {
+	return listen<LiveMeasurementsEnded>('live-measurements-ended', event =>
+		handler(event.payload),
+	);
+}
@@ -2,11 +2,31 @@ import { AsyncSignal, AsyncState, Signal } from '@holochain-open-dev/signals';
 import { core } from '@tauri-apps/api';
 import isEqual from 'lodash-es/isEqual.js';
 
+/**
+ * A directory of a mounted volume that holds the data files of a BPV device
+ */
+export interface MeasurementsSdcard {
+	mountpoint: string;
+	/**
+	 * The mountpoint itself, or one of its subdirectories
+	 */
+	directory: string;
+	arduino_serial_number: string | undefined;
+	/**
+	 * The current data file first, followed by the archived ones
+	 */
+	data_files: Array<string>;
+	/**
+	 * Why the card couldn't be read, the other fields may be incomplete
+	 */
+	error: string | undefined;
+}
+
 export function measurementsSdcards(
 	intervalMs: number = 1000,
-): AsyncSignal<Record<string, string>> {
+): AsyncSignal<Array<MeasurementsSdcard>> {
 	let interval: any;
-	const measurementsSdcards = new AsyncState<Record<string, string>>(
+	const measurementsSdcards = new AsyncState<Array<MeasurementsSdcard>>(
 		{
 			status: 'pending',
 		},
@@ -18,7 +38,7 @@ export function measurementsSdcards(
 						.then(sdcards => {
 							measurementsSdcards.set({
 								status: 'completed',
-								value: sdcards as Record<string, string>,
+								value: sdcards as Array<MeasurementsSdcard>,
 							});
 						})
 						.catch(error => {
@@ -176,6 +176,15 @@ export class HomePage extends SignalWatcher(LitElement) {
 			</sl-select>
 			<div style="flex: 1"></div>
 
+			<sl-button
+				style="margin-right: 8px"
+				@click=${() => {
+					(
+						this.shadowRoot!.querySelector('bpv-device') as BpvDevice
+					).routes.goto(`info`);
+				}}
+				>${msg('Device Info')}</sl-button
+			>
 			<sl-button
 				@click=${() => {
 					(
@@ -0,0 +1,296 @@
+import { notifyError, onSubmit } from '@holochain-open-dev/elements';
+import { SignalWatcher, joinAsync } from '@holochain-open-dev/signals';
+import { EntryRecord } from '@holochain-open-dev/utils';
+import { EntryHash } from '@holochain/client';
+import { consume } from '@lit/context';
+import { msg } from '@lit/localize';
+import '@shoelace-style/shoelace/dist/components/button/button.js';
+import '@shoelace-style/shoelace/dist/components/card/card.js';
+import '@shoelace-style/shoelace/dist/components/input/input.js';
+import '@shoelace-style/shoelace/dist/components/spinner/spinner.js';
+import '@shoelace-style/shoelace/dist/components/textarea/textarea.js';
+import { LitElement, css, html } from 'lit';
+import { customElement, property, state } from 'lit/decorators.js';
+
+import { appStyles } from '../../../app-styles.js';
+import { livingPowerStoreContext } from '../context.js';
+import { LivingPowerStore } from '../living-power-store.js';
+import { BpvDeviceInfo, GpsLocation } from '../types.js';
+
+const MICRODEGREES_PER_DEGREE = 1_000_000;
+
+function readAsDataUrl(file: File): Promise<string> {
+	return new Promise((resolve, reject) => {
+		const reader = new FileReader();
+		reader.onload = () => resolve(reader.result as string);
+		reader.onerror = () => reject(reader.error);
+		reader.readAsDataURL(file);
+	});
+}
+
+function optionalText(value: string | undefined): string | undefined {
+	const trimmed = value?.trim();
+	return trimmed ? trimmed : undefined;
+}
+
+/**
+ * Edits the metadata of a BPV device, and shows how it changed over time
+ */
+@customElement('bpv-device-info')
+export class BpvDeviceInfoElement extends SignalWatcher(LitElement) {
+	@property()
+	arduinoSerialNumber!: string;
+
+	/**
+	 * @internal
+	 */
+	@consume({ context: livingPowerStoreContext, subscribe: true })
+	livingPowerStore!: LivingPowerStore;
+
+	@state()
+	committing = false;
+
+	async saveInfo(currentInfo: BpvDeviceInfo, fields: Record<string, any>) {
+		this.committing = true;
+		try {
+			let photoHash: EntryHash | undefined = currentInfo.photo_hash;
+			const photoInput = this.shadowRoot!.getElementById(
+				'photo',
+			) as HTMLInputElement;
+			const photo = photoInput.files?.[0];
+			if (photo) {
+				photoHash = await this.livingPowerStore.client.createBpvDevicePhoto({
+					data_url: await readAsDataUrl(photo),
+				});
+			}
+
+			let location: GpsLocation | undefined;
+			if (fields.latitude !== '' && fields.longitude !== '') {
+				location = {
+					latitude_microdegrees: Math.round(
+						parseFloat(fields.latitude) * MICRODEGREES_PER_DEGREE,
+					),
+					longitude_microdegrees: Math.round(
+						parseFloat(fields.longitude) * MICRODEGREES_PER_DEGREE,
+					),
+				};
+			}
+
+			await this.livingPowerStore.client.setBpvDeviceInfo(
+				this.arduinoSerialNumber,
+				{
+					name: fields.name,
+					timezone: currentInfo.timezone,
+					location,
+					site_description: optionalText(fields.site_description),
+					species: optionalText(fields.species),
+					electrode_material: optionalText(fields.electrode_material),
+					installation_date: fields.installation_date
+						? new Date(fields.installation_date).valueOf() * 1000
+						: undefined,
+					notes: optionalText(fields.notes),
+					photo_hash: photoHash,
+				},
+			);
+		} catch (e: unknown) {
+			console.error(e);
+			notifyError(msg('Error saving the device info.'));
+		}
+		this.committing = false;
+	}
+
+	renderPhoto(photoHash: EntryHash | undefined) {
+		if (!photoHash) return html``;
+		const photo = this.livingPowerStore.bpvDevicePhotos.get(photoHash).get();
+		if (photo.status !== 'completed' || !photo.value) return html``;
+		return html`<img
+			src=${photo.value.entry.data_url}
+			style="max-width: 320px; max-height: 240px; object-fit: contain"
+		/>`;
+	}
+
+	renderForm(info: BpvDeviceInfo) {
+		const installationDate = info.installation_date
+			? new Date(info.installation_date / 1000).toISOString().slice(0, 10)
+			: '';
+		return html`<sl-card style="flex: 1">
+			<form
+				class="column"
+				style="gap: 12px; flex: 1"
+				${onSubmit(fields => this.saveInfo(info, fields))}
+			>
+				<sl-input
+					name="name"
+					.label=${msg('Name')}
+					required
+					.defaultValue=${info.name}
+				></sl-input>
+				<sl-textarea
+					name="site_description"
+					.label=${msg('Site Description')}
+					.defaultValue=${info.site_description ?? ''}
+				></sl-textarea>
+				<div class="row" style="gap: 12px">
+					<sl-input
+						name="latitude"
+						type="number"
+						step="any"
+						min="-90"
+						max="90"
+						.label=${msg('Latitude')}
+						.defaultValue=${info.location
+							? `${info.location.latitude_microdegrees / MICRODEGREES_PER_DEGREE}`
+							: ''}
+						style="flex: 1"
+					></sl-input>
+					<sl-input
+						name="longitude"
+						type="number"
+						step="any"
+						min="-180"
+						max="180"
+						.label=${msg('Longitude')}
+						.defaultValue=${info.location
+							? `${info.location.longitude_microdegrees / MICRODEGREES_PER_DEGREE}`
+							: ''}
+						style="flex: 1"
+					></sl-input>
+				</div>
+				<sl-input
+					name="species"
+					.label=${msg('Plant or Microbial Species')}
+					.defaultValue=${info.species ?? ''}
+				></sl-input>
+				<sl-input
+					name="electrode_material"
+					.label=${msg('Electrode Material')}
+					.defaultValue=${info.electrode_material ?? ''}
+				></sl-input>
+				<sl-input
+					name="installation_date"
+					type="date"
+					.label=${msg('Installation Date')}
+					.defaultValue=${installationDate}
+				></sl-input>
+				<sl-textarea
+					name="notes"
+					.label=${msg('Notes')}
+					.defaultValue=${info.notes ?? ''}
+				></sl-textarea>
+				<div class="column" style="gap: 8px">
+					<span>${msg('Photo')}</span>
+					${this.renderPhoto(info.photo_hash)}
+					<input id="photo" type="file" accept="image/*" />
+				</div>
+				<sl-button type="submit" variant="primary" .loading=${this.committing}
+					>${msg('Save Device Info')}</sl-button
+				>
+			</form>
+		</sl-card>`;
+	}
+
+	renderRevision(
+		revision: EntryRecord<BpvDeviceInfo>,
+		previous: BpvDeviceInfo | undefined,
+	) {
+		const info = revision.entry;
+		const fields: Array<[keyof BpvDeviceInfo, string, string | undefined]> = [
+			['name', msg('Name'), info.name],
+			['site_description', msg('Site Description'), info.site_description],
+			[
+				'location',
+				msg('Location'),
+				info.location
+					? `${info.location.latitude_microdegrees / MICRODEGREES_PER_DEGREE}, ${info.location.longitude_microdegrees / MICRODEGREES_PER_DEGREE}`
+					: undefined,
+			],
+			['species', msg('Plant or Microbial Species'), info.species],
+			[
+				'electrode_material',
+				msg('Electrode Material'),
+				info.electrode_material,
+			],
+			[
+				'installation_date',
+				msg('Installation Date'),
+				info.installation_date
+					? new Date(info.installation_date / 1000).toLocaleDateString()
+					: undefined,
+			],
+			['notes', msg('Notes'), info.notes],
+		];
+		// Only what changed from the previous revision
+		const changed = (key: keyof BpvDeviceInfo) =>
+			!previous || JSON.stringify(previous[key]) !== JSON.stringify(info[key]);
+
+		return html`<div class="column" style="gap: 4px">
+			<span class="placeholder"
+				>${new Date(revision.action.timestamp / 1000).toLocaleString()}</span
+			>
+			${fields
+				.filter(([key, _, value]) => !!value && changed(key))
+				.map(
+					([_, label, value]) =>
+						html`<span><strong>${label}:</strong> ${value}</span>`,
+				)}
+			${previous && changed('photo_hash')
+				? html`<span>${msg('New photo')}</span>`
+				: html``}
+		</div>`;
+	}
+
+	renderHistory(revisions: Array<EntryRecord<BpvDeviceInfo>>) {
+		return html`<sl-card style="flex: 1">
+			<div class="column" style="gap: 16px">
+				<span class="title">${msg('History')}</span>
+				${revisions
+					.map((revision, i) =>
+						this.renderRevision(revision, revisions[i - 1]?.entry),
+					)
+					.reverse()}
+			</div>
+		</sl-card>`;
+	}
+
+	render() {
+		const bpvDevice = this.livingPowerStore.bpvDevices.get(
+			this.arduinoSerialNumber,
+		);
+		const result = joinAsync([
+			bpvDevice.info.get(),
+			bpvDevice.infoRevisions.get(),
+		]);
+
+		switch (result.status) {
+			case 'pending':
+				return html`<div
+					style="display: flex; flex-direction: column; align-items: center; justify-content: center; flex: 1;"
+				>
+					<sl-spinner style="font-size: 2rem;"></sl-spinner>
+				</div>`;
+			case 'error':
+				return html`<display-error
+					.headline=${msg('Error fetching the info of this BPV device.')}
+					.error=${result.error}
+				></display-error>`;
+			case 'completed':
+				const [info, revisions] = result.value;
+				if (!info)
+					return html`<span class="placeholder"
+						>${msg('This BPV device has no info yet.')}</span
+					>`;
+				return html`<div class="row" style="gap: 16px; flex: 1">
+					${this.renderForm(info)} ${this.renderHistory(revisions)}
+				</div>`;
+		}
+	}
+
+	static styles = [
+		...appStyles,
+		css`
+			:host {
+				display: flex;
+			}
+		`,
+	];
+}
@@ -9,6 +9,7 @@ import { customElement, property } from 'lit/decorators.js';
 import { appStyles } from '../../../app-styles.js';
 import { livingPowerStoreContext } from '../context.js';
 import { LivingPowerStore } from '../living-power-store.js';
+import './bpv-device-info.js';
 import './bpv-device-measurements.js';
 import './external-resistors-values.js';
 
@@ -62,6 +63,27 @@ export class BpvDevice extends SignalWatcher(LitElement) {
 			`;
 			},
 		},
+		{
+			path: 'info',
+			render: () => html`
+				<div class="column" style="position: fixed; width: 100vw; height: 100vh; top: 0; bottom: 0; right: 0; left: 0; z-index: 10; background-color: #ededed;">
+					<div class="row top-bar" style="align-items: center; gap: 12px">
+						<sl-icon-button
+							style="color: white"
+							.src=${wrapPathInSvg(mdiArrowLeft)}
+							@click=${() => this.routes.goto('')}
+						></sl-icon-button>
+						<span class="title" style="color: white"
+							>${msg('Device Info')}</span
+						>
+					</div>
+					<bpv-device-info
+						style="flex: 1; margin: 16px; overflow-y: auto"
+						.arduinoSerialNumber=${this.arduinoSerialNumber}
+					></bpv-device-info>
+				</div>
+			`,
+		},
 	]);
 
 	render() {
@@ -8,9 +8,10 @@ import {
 import { EntryRecord, HoloHashMap } from '@holochain-open-dev/utils';
 import { ActionHash, encodeHashToBase64 } from '@holochain/client';
 import { consume } from '@lit/context';
-import { msg } from '@lit/localize';
+import { msg, str } from '@lit/localize';
 import {
 	mdiAlertOutline,
+	mdiArchiveArrowUpOutline,
 	mdiDatabaseArrowUpOutline,
 	mdiInformationOffOutline,
 	mdiInformationOutline,
@@ -19,17 +20,29 @@ import {
 import { SlDialog, SlInput } from '@shoelace-style/shoelace';
 import '@shoelace-style/shoelace/dist/components/alert/alert.js';
 import '@shoelace-style/shoelace/dist/components/button/button.js';
+import '@shoelace-style/shoelace/dist/components/checkbox/checkbox.js';
 import '@shoelace-style/shoelace/dist/components/dialog/dialog.js';
 import '@shoelace-style/shoelace/dist/components/icon/icon.js';
+import { UnlistenFn } from '@tauri-apps/api/event';
 import { LitElement, css, html } from 'lit';
 import { customElement, property, state } from 'lit/decorators.js';
 
 import { appStyles } from '../../../app-styles.js';
 import {
-	collectMeasurements,
+	ClockOffset,
+	CollectionProgress,
+	ParseReport,
+	cancelCollection,
+	collectAndArchiveMeasurements,
 	collectMeasurementsFromSdcard,
+	collectNewMeasurements,
+	correctClockDrift,
+	onCollectionProgress,
 } from '../../../arduinos/collect-measurements.js';
-import { SerialPortInfo } from '../../../arduinos/connected-arduinos.js';
+import {
+	SerialPortInfo,
+	onArduinoConnected,
+} from '../../../arduinos/connected-arduinos.js';
 import { showDialog } from '../../../utils.js';
 import { livingPowerStoreContext } from '../context.js';
 import { LivingPowerStore } from '../living-power-store.js';
@@ -37,11 +50,28 @@ import { Measurement, MeasurementCollection } from '../types.js';
 import './enter-new-resistors-values-dialog.js';
 import { EnterNewResistorsValuesDialog } from './enter-new-resistors-values-dialog.js';
 
+const AUTO_COLLECT_STORAGE_KEY = 'auto-collect-bpv-devices';
+
+function autoCollectDevices(): Array<string> {
+	return JSON.parse(localStorage.getItem(AUTO_COLLECT_STORAGE_KEY) || '[]');
+}
+
 @customElement('collect-measurements-alert')
 export class CollectMeasurementsAlert extends SignalWatcher(LitElement) {
 	@state()
 	collecting = false;
 
+	@state()
+	progress: CollectionProgress | undefined;
+
+	@state()
+	correctDrift = true;
+
+	@state()
+	autoCollect: Array<string> = autoCollectDevices();
+
+	private unlistenArduinoConnected: Promise<UnlistenFn> | undefined;
+
 	/**
 	 * @internal
 	 */
@@ -49,26 +79,183 @@ export class CollectMeasurementsAlert extends SignalWatcher(LitElement) {
 	@property()
 	_livingPowerStore!: LivingPowerStore;
 
+	connectedCallback() {
+		super.connectedCallback();
+		this.unlistenArduinoConnected = onArduinoConnected(arduino => {
+			if (
+				arduino.arduino_serial_number &&
+				this.autoCollect.includes(arduino.arduino_serial_number) &&
+				!this.collecting
+			) {
+				this.collectMeasurements(arduino.arduino_serial_number, arduino);
+			}
+		});
+	}
+
+	disconnectedCallback() {
+		super.disconnectedCallback();
+		this.unlistenArduinoConnected?.then(unlisten => unlisten());
+		this.unlistenArduinoConnected = undefined;
+	}
+
+	setAutoCollect(arduinoSerialNumber: string, autoCollect: boolean) {
+		const devices = autoCollectDevices().filter(
+			d => d !== arduinoSerialNumber,
+		);
+		if (autoCollect) devices.push(arduinoSerialNumber);
+		localStorage.setItem(AUTO_COLLECT_STORAGE_KEY, JSON.stringify(devices));
+		this.autoCollect = devices;
+	}
+
 	async collectMeasurements(
 		arduinoSerialNumber: string,
 		serialPortInfo: SerialPortInfo,
 	) {
 		this.collecting = true;
+		const unlisten = await onCollectionProgress(progress => {
+			if (progress.port_name === serialPortInfo.port_name) {
+				this.progress = progress;
+			}
+		});
 		try {
-			const newMeasurements = await collectMeasurements(
+			const collected = await collectNewMeasurements(
 				serialPortInfo.port_name,
-			);
-			await this.createMeasurementCollection(
 				arduinoSerialNumber,
-				newMeasurements,
 			);
+			if (collected.report.rejected_rows.length > 0) {
+				notifyError(
+					msg(
+						str`${collected.report.rejected_rows.length} rows could not be read and were skipped.`,
+					),
+				);
+			}
+			if (collected.incomplete) {
+				notifyError(
+					msg(
+						'The device was disconnected before all the data was received. Only part of it was saved, connect it again to collect the rest.',
+					),
+				);
+			}
+			if (collected.measurements.length > 0) {
+				const measurements = this.correctDrift
+					? await correctClockDrift(
+							arduinoSerialNumber,
+							collected.measurements,
+						)
+					: collected.measurements;
+				await this.createMeasurementCollection(
+					arduinoSerialNumber,
+					measurements,
+				);
+			}
 		} catch (e) {
 			console.error(e);
 			notifyError(msg('Error synchronizing the data.'));
 		}
+		unlisten();
+		this.progress = undefined;
 		this.collecting = false;
 	}
 
+	/**
+	 * Collects and commits all the measurements of the device, and then clears its data file
+	 * so that the following collections are faster
+	 */
+	async collectAndArchiveMeasurements(
+		arduinoSerialNumber: string,
+		serialPortInfo: SerialPortInfo,
+	) {
+		this.collecting = true;
+		const unlisten = await onCollectionProgress(progress => {
+			if (progress.port_name === serialPortInfo.port_name) {
+				this.progress = progress;
+			}
+		});
+		try {
+			const archived = await collectAndArchiveMeasurements(
+				serialPortInfo.port_name,
+				arduinoSerialNumber,
+				this.correctDrift,
+			);
+			if (archived.action_hashes.length > 0) {
+				this.dispatchEvent(
+					new CustomEvent('measurement-collections-created', {
+						composed: true,
+						bubbles: true,
+						detail: {
+							measurementCollectionsHashes: archived.action_hashes,
+						},
+					}),
+				);
+			}
+		} catch (e) {
+			console.error(e);
+			notifyError(msg(str`Error archiving the data: ${e}`));
+		}
+		unlisten();
+		this.progress = undefined;
+		this.collecting = false;
+	}
+
+	renderParseReport(report: ParseReport) {
+		if (report.rejected_rows.length === 0) return html``;
+		return html`<span
+			title=${report.rejected_rows
+				.map(row => `${row.line_number}: ${row.reason} (${row.raw})`)
+				.join('\n')}
+			>${msg(
+				str`${report.rejected_rows.length} rows could not be read and were skipped.`,
+			)}</span
+		>`;
+	}
+
+	renderClockDrift(clockOffset: ClockOffset | undefined) {
+		if (!clockOffset) return html``;
+		const offsetSeconds = Math.round(
+			(clockOffset.device_time - clockOffset.host_time) / 1_000_000,
+		);
+		// The device clock only has second precision, and reading it takes a moment
+		if (Math.abs(offsetSeconds) < 60) return html``;
+
+		return html`<div class="column" style="gap: 4px">
+			<span
+				>${offsetSeconds > 0
+					? msg(str`The device clock is ${offsetSeconds}s ahead.`)
+					: msg(str`The device clock is ${-offsetSeconds}s behind.`)}</span
+			>
+			<sl-checkbox
+				.checked=${this.correctDrift}
+				@sl-change=${(e: Event) => {
+					this.correctDrift = (e.target as HTMLInputElement).checked;
+				}}
+				>${msg(
+					'Correct the clock drift of the collected measurements',
+				)}</sl-checkbox
+			>
+		</div>`;
+	}
+
+	renderProgress(portName: string) {
+		if (!this.progress) return html``;
+		const { rows_received, parse_errors, estimated_remaining_ms } =
+			this.progress;
+		return html`<div class="row" style="gap: 12px; align-items: center">
+			<span
+				>${msg(str`${rows_received} rows received`)}${parse_errors > 0
+					? msg(str`, ${parse_errors} unreadable`)
+					: ''}${estimated_remaining_ms !== undefined &&
+				estimated_remaining_ms !== null
+					? msg(
+							str`, ${Math.ceil(estimated_remaining_ms / 1000)}s remaining`,
+						)
+					: ''}</span
+			>
+			<sl-button size="small" @click=${() => cancelCollection(portName)}
+				>${msg('Cancel')}</sl-button
+			>
+		</div>`;
+	}
+
 	async createMeasurementCollection(
 		arduinoSerialNumber: string,
 		measurements: Measurement[],
@@ -168,14 +355,17 @@ export class CollectMeasurementsAlert extends SignalWatcher(LitElement) {
 					</div>
 				</sl-alert>
 			`;
-		if (lastMeasurement.status !== 'completed' || !lastMeasurement.value)
+		if (
+			lastMeasurement.status !== 'completed' ||
+			!lastMeasurement.value.measurement
+		)
 			return html``;
 		const measurementsByTimestampDescending = measurements.sort(
 			(m1, m2) => m2.timestamp - m1.timestamp,
 		);
 		if (
 			measurementsByTimestampDescending.length !== 0 &&
-			lastMeasurement.value.timestamp <=
+			lastMeasurement.value.measurement.timestamp <=
 				measurementsByTimestampDescending[0].timestamp
 		)
 			return html``;
@@ -194,6 +384,23 @@ export class CollectMeasurementsAlert extends SignalWatcher(LitElement) {
 								'There are new measurements stored in the connected BPV device.',
 							)}</span
 						>
+						${this.renderClockDrift(lastMeasurement.value.clock_offset)}
+						<sl-checkbox
+							.checked=${this.autoCollect.includes(arduinoSerialNumber)}
+							@sl-change=${(e: Event) =>
+								this.setAutoCollect(
+									arduinoSerialNumber,
+									(e.target as HTMLInputElement).checked,
+								)}
+							>${msg(
+								'Collect the measurements automatically when this device is connected',
+							)}</sl-checkbox
+						>
+						${this.collecting
+							? this.renderProgress(
+									connectedArduino.value.serialPortInfo.port_name,
+								)
+							: html``}
 					</div>
 					<sl-button
 						variant="primary"
@@ -210,6 +417,23 @@ export class CollectMeasurementsAlert extends SignalWatcher(LitElement) {
 						></sl-icon>
 						${msg('Collect Measurements')}</sl-button
 					>
+					<sl-button
+						.loading=${this.collecting}
+						title=${msg(
+							'Collect all the measurements, and clear them from the device once they are saved',
+						)}
+						@click=${() =>
+							this.collectAndArchiveMeasurements(
+								arduinoSerialNumber,
+								connectedArduino.value!.serialPortInfo,
+							)}
+					>
+						<sl-icon
+							slot="prefix"
+							.src=${wrapPathInSvg(mdiArchiveArrowUpOutline)}
+						></sl-icon>
+						${msg('Collect and Archive')}</sl-button
+					>
 				</div>
 			</sl-alert>
 		`;
@@ -254,7 +478,7 @@ export class CollectMeasurementsAlert extends SignalWatcher(LitElement) {
 				</sl-alert>
 			`;
 
-		const newMeasurements = sdcardMeasurements.value.filter(
+		const newMeasurements = sdcardMeasurements.value.measurements.filter(
 			m => !measurements.find(m2 => m2.timestamp === m.timestamp),
 		);
 		const newMeasurementsByTimestampDescending = newMeasurements.sort(
@@ -277,6 +501,7 @@ export class CollectMeasurementsAlert extends SignalWatcher(LitElement) {
 								'There are new measurements stored in the connected SD card.',
 							)}</span
 						>
+						${this.renderParseReport(sdcardMeasurements.value.report)}
 					</div>
 					<sl-button
 						variant="primary"
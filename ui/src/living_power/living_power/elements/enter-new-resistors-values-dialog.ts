@@ -315,6 +315,7 @@ export class EnterNewResistorsValuesDialog extends SignalWatcher(LitElement) {
 						@set-external-resistor-value=${async (e: CustomEvent) => {
 							const index = e.detail.existingExternalResistorValueToEdit;
 							const value = {
+								arduino_serial_number: this.arduinoSerialNumber,
 								from: e.detail.from,
 								to: e.detail.to,
 								external_resistor_value_ohms: e.detail.externalResistorValue,
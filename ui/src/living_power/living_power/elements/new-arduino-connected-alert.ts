@@ -17,7 +17,6 @@ import '@shoelace-style/shoelace/dist/components/input/input.js';
 import SlInput from '@shoelace-style/shoelace/dist/components/input/input.js';
 import { LitElement, html } from 'lit';
 import { customElement, property } from 'lit/decorators.js';
-import { pickBy } from 'lodash-es';
 
 import { appStyles } from '../../../app-styles.js';
 import { SerialPortInfo } from '../../../arduinos/connected-arduinos.js';
@@ -44,10 +43,10 @@ export class NewArduinoConnectedAlert extends SignalWatcher(LitElement) {
 
 		const newDevices = connectedArduinos.value.filter(
 			arduino =>
-				arduino.port_type?.UsbPort.serial_number &&
+				arduino.arduino_serial_number &&
 				!Array.from(allBpvDevices.value.keys()).find(
 					arduinoSerialNumber =>
-						arduinoSerialNumber === arduino.port_type?.UsbPort.serial_number,
+						arduinoSerialNumber === arduino.arduino_serial_number,
 				),
 		);
 		return {
@@ -63,11 +62,12 @@ export class NewArduinoConnectedAlert extends SignalWatcher(LitElement) {
 		const allBpvDevices = this._livingPowerStore.allBpvDevices.get();
 		if (allBpvDevices.status !== 'completed') return allBpvDevices;
 
-		const newSdcards = pickBy(
-			sdcards.value,
-			(_, serialnumber) =>
+		const newSdcards = sdcards.value.filter(
+			sdcard =>
+				sdcard.arduino_serial_number &&
 				!Array.from(allBpvDevices.value.keys()).find(
-					arduinoSerialNumber => arduinoSerialNumber === serialnumber,
+					arduinoSerialNumber =>
+						arduinoSerialNumber === sdcard.arduino_serial_number,
 				),
 		);
 		return {
@@ -83,6 +83,11 @@ export class NewArduinoConnectedAlert extends SignalWatcher(LitElement) {
 				arduinoSerialNumber,
 				{
 					name,
+					// The device clock is set from this computer, so it uses its timezone
+					timezone: {
+						type: 'Named',
+						name: Intl.DateTimeFormat().resolvedOptions().timeZone,
+					},
 				},
 			);
 
@@ -188,7 +193,7 @@ export class NewArduinoConnectedAlert extends SignalWatcher(LitElement) {
 		return html`
 			${newBpvDevices.value.map(d =>
 				this.renderCreateNewDeviceAlert(
-					d.port_type!.UsbPort.serial_number,
+					d.arduino_serial_number!,
 					'usbserial',
 				),
 			)}
@@ -201,16 +206,49 @@ export class NewArduinoConnectedAlert extends SignalWatcher(LitElement) {
 		if (newSdcards.status !== 'completed' || !newSdcards.value) return html``;
 
 		return html`
-			${Object.keys(newSdcards.value).map(serialnumber =>
-				this.renderCreateNewDeviceAlert(serialnumber, 'sdcard'),
+			${newSdcards.value.map(sdcard =>
+				this.renderCreateNewDeviceAlert(
+					sdcard.arduino_serial_number!,
+					'sdcard',
+				),
 			)}
 		`;
 	}
 
+	renderUnreadableSdcardsAlerts() {
+		const sdcards = this._livingPowerStore.measurementsSdcards.get();
+		if (sdcards.status !== 'completed') return html``;
+
+		return html`
+			${sdcards.value
+				.filter(sdcard => sdcard.error)
+				.map(
+					sdcard => html`
+						<sl-alert open variant="danger">
+							<sl-icon
+								slot="icon"
+								.src=${wrapPathInSvg(mdiAlertOutline)}
+							></sl-icon>
+							<div class="column">
+								<span>
+									<strong
+										>${msg('Error reading the SD card')}
+										${sdcard.directory}</strong
+									></span
+								>
+								<span>${sdcard.error}</span>
+							</div>
+						</sl-alert>
+					`,
+				)}
+		`;
+	}
+
 	render() {
 		return html`
 			<div class="column" style="gap: 12px">
 				${this.renderBpvDevicesAlerts()} ${this.renderSdcardsAlerts()}
+				${this.renderUnreadableSdcardsAlerts()}
 			</div>
 		`;
 	}
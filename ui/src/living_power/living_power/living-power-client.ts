@@ -17,7 +17,15 @@ import {
 	SignedActionHashed,
 } from '@holochain/client';
 
-import { BpvDeviceInfo, MeasurementCollection } from './types.js';
+import {
+	BpvDeviceInfo,
+	BpvDevicePhoto,
+	CreatedMeasurementCollections,
+	ExternalResistorValue,
+	Measurement,
+	MeasurementCollection,
+	Role,
+} from './types.js';
 import { LivingPowerSignal } from './types.js';
 
 export class LivingPowerClient extends ZomeClient<LivingPowerSignal> {
@@ -30,13 +38,16 @@ export class LivingPowerClient extends ZomeClient<LivingPowerSignal> {
 	}
 	/** Bpv Device */
 
+	/**
+	 * Saves the info as a new revision of the latest info of the device, if it already has one
+	 */
 	async setBpvDeviceInfo(
 		arduinoSerialNumber: string,
-		info: BpvDeviceInfo,
-	): Promise<void> {
-		await this.callZome('set_bpv_device_info', {
+		info: Omit<BpvDeviceInfo, 'arduino_serial_number'>,
+	): Promise<ActionHash> {
+		return this.callZome('set_bpv_device_info', {
+			...info,
 			arduino_serial_number: arduinoSerialNumber,
-			info,
 		});
 	}
 
@@ -44,6 +55,43 @@ export class LivingPowerClient extends ZomeClient<LivingPowerSignal> {
 		return this.callZome('get_bpv_device_info', arduinoSerialNumber);
 	}
 
+	async getLatestBpvDeviceInfo(
+		originalBpvDeviceInfoHash: ActionHash,
+	): Promise<EntryRecord<BpvDeviceInfo> | undefined> {
+		const record: Record | undefined = await this.callZome(
+			'get_latest_bpv_device_info',
+			originalBpvDeviceInfoHash,
+		);
+		return record ? new EntryRecord(record) : undefined;
+	}
+
+	async getAllRevisionsForBpvDeviceInfo(
+		originalBpvDeviceInfoHash: ActionHash,
+	): Promise<Array<EntryRecord<BpvDeviceInfo>>> {
+		const records: Record[] = await this.callZome(
+			'get_all_revisions_for_bpv_device_info',
+			originalBpvDeviceInfoHash,
+		);
+		return records.map(r => new EntryRecord(r));
+	}
+
+	/**
+	 * Commits the photo, returning the hash to set as the photo_hash of the device info
+	 */
+	async createBpvDevicePhoto(photo: BpvDevicePhoto): Promise<EntryHash> {
+		return this.callZome('create_bpv_device_photo', photo);
+	}
+
+	async getBpvDevicePhoto(
+		photoHash: EntryHash,
+	): Promise<EntryRecord<BpvDevicePhoto> | undefined> {
+		const record: Record | undefined = await this.callZome(
+			'get_bpv_device_photo',
+			photoHash,
+		);
+		return record ? new EntryRecord(record) : undefined;
+	}
+
 	async bpvDeviceHash(arduinoSerialNumber: string): Promise<EntryHash> {
 		return this.callZome('bpv_device_hash', arduinoSerialNumber);
 	}
@@ -64,6 +112,40 @@ export class LivingPowerClient extends ZomeClient<LivingPowerSignal> {
 		);
 	}
 
+	/**
+	 * Skips the measurements already committed for the device, and returns how many were skipped
+	 */
+	async createNewMeasurementCollections(
+		measurementCollection: MeasurementCollection,
+	): Promise<CreatedMeasurementCollections> {
+		return this.callZome(
+			'create_new_measurement_collections',
+			measurementCollection,
+		);
+	}
+
+	/**
+	 * The timestamp as read from the device of its last committed measurement, before any clock drift correction
+	 */
+	async getLastMeasurementTimestamp(
+		arduinoSerialNumber: string,
+	): Promise<number | undefined> {
+		return this.callZome('get_last_measurement_timestamp', arduinoSerialNumber);
+	}
+
+	/**
+	 * How many of the measurements read from the device were committed with the same values
+	 */
+	async countCommittedMeasurements(
+		arduinoSerialNumber: string,
+		measurements: Array<Measurement>,
+	): Promise<number> {
+		return this.callZome('count_committed_measurements', {
+			arduino_serial_number: arduinoSerialNumber,
+			measurements,
+		});
+	}
+
 	async getMeasurementCollection(
 		measurementCollectionHash: ActionHash,
 	): Promise<EntryRecord<MeasurementCollection> | undefined> {
@@ -123,17 +205,20 @@ export class LivingPowerClient extends ZomeClient<LivingPowerSignal> {
 
 	/** External resistor value */
 
+	/**
+	 * Adds a new value, or corrects the value whose latest revision is previousExternalResistorValueHash
+	 */
 	async setExternalResistorValue(
 		arduinoSerialNumber: string,
 		from: number,
 		to: number,
 		externalResistorValueOhms: number,
-		previousCreateLinkActionHash: ActionHash | undefined,
-	) {
-		await this.callZome('set_external_resistor_value', {
-			arduino_serial_number: arduinoSerialNumber,
-			previous_create_link_action_hash: previousCreateLinkActionHash,
+		previousExternalResistorValueHash: ActionHash | undefined,
+	): Promise<ActionHash> {
+		return this.callZome('set_external_resistor_value', {
+			previous_external_resistor_value_hash: previousExternalResistorValueHash,
 			external_resistor_value: {
+				arduino_serial_number: arduinoSerialNumber,
 				from,
 				to,
 				external_resistor_value_ohms: externalResistorValueOhms,
@@ -149,4 +234,45 @@ export class LivingPowerClient extends ZomeClient<LivingPowerSignal> {
 			arduinoSerialNumber,
 		);
 	}
+
+	async getLatestExternalResistorValue(
+		originalExternalResistorValueHash: ActionHash,
+	): Promise<EntryRecord<ExternalResistorValue> | undefined> {
+		const record: Record | undefined = await this.callZome(
+			'get_latest_external_resistor_value',
+			originalExternalResistorValueHash,
+		);
+		return record ? new EntryRecord(record) : undefined;
+	}
+
+	async getAllRevisionsForExternalResistorValue(
+		originalExternalResistorValueHash: ActionHash,
+	): Promise<Array<EntryRecord<ExternalResistorValue>>> {
+		const records: Record[] = await this.callZome(
+			'get_all_revisions_for_external_resistor_value',
+			originalExternalResistorValueHash,
+		);
+		return records.map(r => new EntryRecord(r));
+	}
+
+	/** Roles */
+
+	async grantRole(agent: AgentPubKey, role: Role): Promise<ActionHash> {
+		return this.callZome('grant_role', {
+			agent,
+			role,
+		});
+	}
+
+	async claimRole(roleGrantHash: ActionHash): Promise<ActionHash> {
+		return this.callZome('claim_role', roleGrantHash);
+	}
+
+	async getRoleGrantsForAgent(agent: AgentPubKey): Promise<Array<Link>> {
+		return this.callZome('get_role_grants_for_agent', agent);
+	}
+
+	async getMyRoles(): Promise<Array<Role>> {
+		return this.callZome('get_my_roles', undefined);
+	}
 }
@@ -33,7 +33,6 @@ import {
 	NewEntryAction,
 	Record,
 } from '@holochain/client';
-import { decode } from '@msgpack/msgpack';
 
 import {
 	collectMeasurementsFromSdcard,
@@ -108,31 +107,43 @@ export class LivingPowerStore {
 		const pathHash = fromPromise(() =>
 			this.client.bpvDeviceHash(arduinoSerialNumber),
 		);
+		// The original hash of the most recently linked info of the device
+		const infoHash = pipe(
+			pathHash,
+			hash =>
+				liveLinksSignal(
+					this.client,
+					hash,
+					() => this.client.getBpvDeviceInfo(arduinoSerialNumber),
+					'BpvDeviceToBpvDeviceInfo',
+				),
+			links => {
+				if (links.length === 0) return undefined;
+				const sortedLinks = links.sort(
+					(linkA, linkB) => linkB.timestamp - linkA.timestamp,
+				);
+				return sortedLinks[0].target as ActionHash;
+			},
+		);
 		return {
 			pathHash,
 			info: pipe(
-				pathHash,
+				infoHash,
 				hash =>
-					liveLinksSignal(
-						this.client,
-						hash,
-						() => this.client.getBpvDeviceInfo(arduinoSerialNumber),
-						'BpvDeviceToBpvDeviceInfo',
-					),
-				links => {
-					if (links.length === 0) return undefined;
-					const sortedLinks = links.sort(
-						(linkA, linkB) => linkB.timestamp - linkA.timestamp,
-					);
-					const latestLink = sortedLinks[0];
-
-					const info = decode(latestLink.tag) as BpvDeviceInfo;
-					return info;
-				},
+					hash ? this.bpvDeviceInfos.get(hash).latestVersion : undefined,
+				latestVersion => latestVersion?.entry,
+			),
+			/**
+			 * All the revisions of the info of the device, from the oldest to the latest
+			 */
+			infoRevisions: pipe(infoHash, hash =>
+				hash
+					? this.bpvDeviceInfos.get(hash).allRevisions
+					: ([] as Array<EntryRecord<BpvDeviceInfo>>),
 			),
 			connectedArduino: pipe(this.connectedArduinos, arduinos => {
 				const serialPortInfo = arduinos.find(
-					a => a.port_type?.UsbPort.serial_number === arduinoSerialNumber,
+					a => a.arduino_serial_number === arduinoSerialNumber,
 				);
 
 				if (!serialPortInfo) return undefined;
@@ -145,8 +156,13 @@ export class LivingPowerStore {
 				};
 			}),
 			measurementSdcard: pipe(this.measurementsSdcards, sdcards => {
-				const sdcardPath = sdcards[arduinoSerialNumber];
-				if (!sdcardPath) return undefined;
+				const sdcard = sdcards.find(
+					sdcard =>
+						sdcard.arduino_serial_number === arduinoSerialNumber &&
+						!sdcard.error,
+				);
+				if (!sdcard) return undefined;
+				const sdcardPath = sdcard.directory;
 				return {
 					sdcardPath,
 					measurements: fromPromise(() =>
@@ -202,9 +218,17 @@ export class LivingPowerStore {
 						'BpvDeviceToExternalResistorValues',
 					),
 				links =>
-					links.map(
-						link =>
-							[link.create_link_hash, decode(link.tag)] as [
+					new AsyncComputed(() =>
+						joinAsync(
+							links.map(link =>
+								this.externalResistorValues.get(link.target).latestVersion.get(),
+							),
+						),
+					),
+				latestVersions =>
+					latestVersions.map(
+						latestVersion =>
+							[latestVersion.actionHash, latestVersion.entry] as [
 								ActionHash,
 								ExternalResistorValue,
 							],
@@ -213,6 +237,36 @@ export class LivingPowerStore {
 		};
 	});
 
+	/** Bpv Device Info */
+
+	bpvDeviceInfos = new LazyHoloHashMap((bpvDeviceInfoHash: ActionHash) => ({
+		latestVersion: latestVersionOfEntrySignal(this.client, () =>
+			this.client.getLatestBpvDeviceInfo(bpvDeviceInfoHash),
+		),
+		allRevisions: allRevisionsOfEntrySignal(this.client, () =>
+			this.client.getAllRevisionsForBpvDeviceInfo(bpvDeviceInfoHash),
+		),
+	}));
+
+	bpvDevicePhotos = new LazyHoloHashMap((photoHash: EntryHash) =>
+		immutableEntrySignal(() => this.client.getBpvDevicePhoto(photoHash)),
+	);
+
+	/** External Resistor Value */
+
+	externalResistorValues = new LazyHoloHashMap(
+		(externalResistorValueHash: ActionHash) => ({
+			latestVersion: latestVersionOfEntrySignal(this.client, () =>
+				this.client.getLatestExternalResistorValue(externalResistorValueHash),
+			),
+			allRevisions: allRevisionsOfEntrySignal(this.client, () =>
+				this.client.getAllRevisionsForExternalResistorValue(
+					externalResistorValueHash,
+				),
+			),
+		}),
+	);
+
 	/** All Bpv Devices */
 
 	allBpvDevices = pipe(
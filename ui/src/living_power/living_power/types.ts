@@ -1,15 +1,58 @@
 import { ActionCommittedSignal } from '@holochain-open-dev/utils';
+import { ActionHash, AgentPubKey, EntryHash } from '@holochain/client';
 
 export type LivingPowerSignal = ActionCommittedSignal<EntryTypes, LinkTypes>;
 
-export type EntryTypes = {
-	type: 'MeasurementCollection';
-} & MeasurementCollection;
+export type EntryTypes =
+	| ({ type: 'BpvDeviceInfo' } & BpvDeviceInfo)
+	| ({ type: 'BpvDevicePhoto' } & BpvDevicePhoto)
+	| ({ type: 'ExternalResistorValue' } & ExternalResistorValue)
+	| ({ type: 'MeasurementCollection' } & MeasurementCollection);
 
 export type LinkTypes = string;
 
+export type DeviceTimezone =
+	| {
+			type: 'Named';
+			name: string;
+	  }
+	| {
+			type: 'UtcOffset';
+			offset_minutes: number;
+	  };
+
+/**
+ * In millionths of a degree
+ */
+export interface GpsLocation {
+	latitude_microdegrees: number;
+	longitude_microdegrees: number;
+}
+
 export interface BpvDeviceInfo {
+	arduino_serial_number: string;
 	name: string;
+	timezone?: DeviceTimezone;
+	location?: GpsLocation;
+	site_description?: string;
+	/**
+	 * The plant or microbial species growing in the device
+	 */
+	species?: string;
+	electrode_material?: string;
+	installation_date?: number;
+	notes?: string;
+	/**
+	 * The entry hash of a BpvDevicePhoto
+	 */
+	photo_hash?: EntryHash;
+}
+
+export interface BpvDevicePhoto {
+	/**
+	 * The image encoded as a data URL, like "data:image/jpeg;base64,..."
+	 */
+	data_url: string;
 }
 
 export interface Measurement {
@@ -18,14 +61,47 @@ export interface Measurement {
 	temperature_celsius: number;
 	light_level_lux: number;
 	voltage_millivolts: number;
+	raw_timestamp?: number;
 }
 
 export interface MeasurementCollection {
 	arduino_serial_number: string;
 	measurements: Array<Measurement>;
+	/** Whether the measurements were streamed live from the device, instead of read from its data file */
+	live?: boolean;
+}
+
+export interface CreatedMeasurementCollections {
+	action_hashes: Array<ActionHash>;
+	/**
+	 * Measurements that were not committed because the device already had one at their timestamp
+	 */
+	skipped_measurements: number;
+	/**
+	 * Measurements that were not committed because they are invalid, like the ones from before 2000 or in the future
+	 */
+	rejected_measurements: number;
+}
+
+/**
+ * Admins can delete any data and grant roles, device owners can delete the data of their device
+ */
+export type Role =
+	| {
+			type: 'Admin';
+	  }
+	| {
+			type: 'DeviceOwner';
+			arduino_serial_number: string;
+	  };
+
+export interface RoleGrant {
+	agent: AgentPubKey;
+	role: Role;
 }
 
 export interface ExternalResistorValue {
+	arduino_serial_number: string;
 	external_resistor_value_ohms: number;
 	from: number;
 	to: number;